@@ -2,8 +2,6 @@ from fastapi import FastAPI, HTTPException
 from typing import Optional
 from fastapi.middleware.cors import CORSMiddleware
 import os
-import platform
-import subprocess
 from quicklauncher_py.search import SearchEngine
 from quicklauncher_py import llm
 
@@ -32,12 +30,17 @@ try:
 except Exception as e:
     print("LLM load failed:", e)
 
+@app.get("/health")
+async def health():
+    # Readiness probe used by the Tauri shell that supervises this process
+    return {"status": "ok"}
+
 @app.get("/search")
-async def search(query: Optional[str] = None, q: Optional[str] = None):
+async def search(query: Optional[str] = None, q: Optional[str] = None, limit: int = 10):
     effective_query = query or q
     if not effective_query:
         raise HTTPException(status_code=400, detail="Missing 'query' or 'q' parameter")
-    results = search_engine.search(effective_query)
+    results = search_engine.search(effective_query, limit=limit)
     return {"results": results}
 
 @app.get("/interpret")
@@ -45,17 +48,3 @@ async def interpret(text: str):
     # Use the LLM to interpret a natural language command
     result = llm.interpret_command(text)
     return {"result": result}
-
-@app.post("/launch")
-async def launch(path: str):
-    # Launch a file or directory using the host OS
-    if not os.path.exists(path):
-        raise HTTPException(status_code=404, detail="Path not found")
-    system = platform.system()
-    if system == "Windows":
-        os.startfile(path)  # type: ignore
-    elif system == "Darwin":
-        subprocess.Popen(["open", path])
-    else:
-        subprocess.Popen(["xdg-open", path])
-    return {"status": "ok"}
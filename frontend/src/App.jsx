@@ -1,15 +1,125 @@
-import React, { useState } from 'react';
+import React, { useEffect, useRef, useState } from 'react';
+
+// The Tauri shell starts the backend on a free port, a new one each time it
+// restarts, and reports the current one here. Outside Tauri (plain `vite` in
+// a browser) fall back to the dev default.
+const backendUrl = () =>
+  window.__TAURI__
+    ? window.__TAURI__.invoke('backend_url').then((url) => url || 'http://localhost:8000')
+    : Promise.resolve('http://localhost:8000');
+
+// Wrap the matched `[start, end)` ranges of `text` in <mark>. Ranges count
+// Unicode code points, which is how Array.from splits a string.
+function Highlight({ text, ranges }) {
+  if (!Array.isArray(ranges) || ranges.length === 0) {
+    return text;
+  }
+  const chars = Array.from(text);
+  const parts = [];
+  let cursor = 0;
+  ranges.forEach(([start, end], i) => {
+    if (start > cursor) {
+      parts.push(chars.slice(cursor, start).join(''));
+    }
+    parts.push(
+      <mark key={i} style={{ background: 'none', color: 'inherit', fontWeight: 'bold' }}>
+        {chars.slice(start, end).join('')}
+      </mark>
+    );
+    cursor = end;
+  });
+  if (cursor < chars.length) {
+    parts.push(chars.slice(cursor).join(''));
+  }
+  return parts;
+}
+
+// Icons are served by the shell's `icon://` protocol; ask for the size they
+// are drawn at, scaled for high-DPI screens.
+const ICON_SIZE = 24;
+const iconSrc = (url) => `${url}?size=${ICON_SIZE}&scale=${Math.max(1, Math.ceil(window.devicePixelRatio || 1))}`;
+
+const SEARCH_LIMIT = 20;
+
+// The shell streams each search as one `search://results` event per
+// provider. Where providers return the same path the higher score wins.
+// A calculator answer comes first, then applications, then everything else,
+// each group ordered by score.
+const KIND_ORDER = { answer: 0, app: 1 };
+const kindOrder = (item) => KIND_ORDER[item.kind] ?? 2;
+
+const mergeBatches = (batches) => {
+  const byPath = new Map();
+  Object.values(batches)
+    .flat()
+    .forEach((item) => {
+      const kept = byPath.get(item.path);
+      if (!kept || (kept.score || 0) < (item.score || 0)) {
+        byPath.set(item.path, item);
+      }
+    });
+  return [...byPath.values()]
+    .sort((a, b) => kindOrder(a) - kindOrder(b) || (b.score || 0) - (a.score || 0))
+    .slice(0, SEARCH_LIMIT);
+};
+
+const emptyStream = (generation) => ({ generation, batches: {} });
 
 function App() {
   const [query, setQuery] = useState('');
   const [results, setResults] = useState([]);
+  // Batches received for the newest search generation, by provider.
+  const stream = useRef(emptyStream(0));
+  // Secondary actions of one result, shown on right click: { index, actions }.
+  const [menu, setMenu] = useState(null);
+  // The last trashed file, offered for undo.
+  const [trashed, setTrashed] = useState(null);
+
+  // Results of older generations can still be in flight; only the newest
+  // generation seen so far is shown. Without a source this just records
+  // that `generation` was started, keeping the old list until its first batch.
+  const receive = (generation, source, items) => {
+    if (generation < stream.current.generation) {
+      return;
+    }
+    if (generation > stream.current.generation) {
+      stream.current = emptyStream(generation);
+    }
+    if (source) {
+      stream.current.batches[source] = Array.isArray(items) ? items : [];
+      setResults(mergeBatches(stream.current.batches));
+    }
+  };
+
+  useEffect(() => {
+    if (!window.__TAURI__) {
+      return undefined;
+    }
+    const unlisten = window.__TAURI__.event.listen('search://results', ({ payload }) => {
+      receive(payload.generation, payload.source, payload.results);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
 
-  const handleChange = async (e) => {
-    const q = e.target.value;
+  const runSearch = async (q) => {
     setQuery(q);
+    setMenu(null);
+    if (window.__TAURI__) {
+      // Even an empty query is sent: starting it cancels the previous one.
+      try {
+        const generation = await window.__TAURI__.invoke('search_stream', { query: q, limit: SEARCH_LIMIT });
+        receive(generation);
+      } catch (error) {
+        console.error('Error during search', error);
+      }
+      return;
+    }
     if (q) {
       try {
-        const response = await fetch(`http://localhost:8000/search?q=${encodeURIComponent(q)}`);
+        const base = await backendUrl();
+        const response = await fetch(`${base}/search?q=${encodeURIComponent(q)}`);
         if (response.ok) {
           const data = await response.json();
           const items = Array.isArray(data?.results) ? data.results : Array.isArray(data) ? data : [];
@@ -25,18 +135,87 @@ function App() {
     }
   };
 
+  const handleChange = (e) => runSearch(e.target.value);
+
+  // A query can come from the command line, either at startup or forwarded
+  // by a second start of the launcher. The startup one is picked up once the
+  // page is ready; later ones arrive as events.
+  useEffect(() => {
+    if (!window.__TAURI__) {
+      return undefined;
+    }
+    window.__TAURI__.invoke('take_query').then((q) => q && runSearch(q));
+    const unlisten = window.__TAURI__.event.listen('query', (event) => {
+      window.__TAURI__.invoke('take_query');
+      runSearch(event.payload);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   const handleLaunch = async (path) => {
+    if (!window.__TAURI__) {
+      console.warn('Launching is only available inside the Tauri shell');
+      return;
+    }
     try {
-      await fetch(`http://localhost:8000/launch?path=${encodeURIComponent(path)}`, {
-        method: 'POST',
-      });
+      await window.__TAURI__.invoke('launch', { path });
     } catch (error) {
       console.error('Error launching file', error);
     }
   };
 
+  const handleMenu = async (e, index, item) => {
+    e.preventDefault();
+    if (!window.__TAURI__ || !item?.path) {
+      return;
+    }
+    try {
+      const actions = await window.__TAURI__.invoke('list_actions', { result: item });
+      setMenu({ index, actions: Array.isArray(actions) ? actions : [] });
+    } catch (error) {
+      console.error('Error listing actions', error);
+    }
+  };
+
+  const handleAction = async (e, path, action) => {
+    e.stopPropagation();
+    setMenu(null);
+    try {
+      const entry = await window.__TAURI__.invoke('run_action', { path, action });
+      if (action.kind === 'trash') {
+        setResults((items) => items.filter((item) => item?.path !== path));
+        // Only trashes the launcher manages hand back an entry to restore.
+        setTrashed(entry || null);
+      }
+    } catch (error) {
+      console.error('Error running action', error);
+    }
+  };
+
+  const handleRestore = async () => {
+    const entry = trashed;
+    setTrashed(null);
+    try {
+      await window.__TAURI__.invoke('trash_restore', { id: entry.id });
+    } catch (error) {
+      console.error('Error restoring from trash', error);
+    }
+  };
+
   return (
-    <div style={{ padding: '1rem', fontFamily: 'Arial, sans-serif' }}>
+    <div
+      style={{
+        padding: '1rem',
+        fontFamily: 'Arial, sans-serif',
+        minHeight: '100vh',
+        boxSizing: 'border-box',
+        background: '#fff',
+        border: '1px solid #ccc',
+        borderRadius: '8px',
+      }}
+    >
       <input
         type="text"
         value={query}
@@ -44,12 +223,29 @@ function App() {
         placeholder="Type a command or search query..."
         style={{ width: '100%', padding: '0.5rem', fontSize: '1rem', marginBottom: '1rem' }}
       />
+      {trashed && (
+        <div style={{ marginBottom: '1rem', fontSize: '0.875rem' }}>
+          Moved {trashed.path} to the trash.{' '}
+          <button onClick={handleRestore}>Undo</button>
+        </div>
+      )}
       <ul style={{ listStyle: 'none', padding: 0 }}>
         {results.map((item, index) => (
           <li
             key={index}
-            onClick={() => item?.path && handleLaunch(item.path)}
+            onClick={(e) => {
+              // Answers and results from plugins say what choosing them does.
+              if (item?.action) {
+                handleAction(e, item.path, item.action);
+              } else if (item?.path) {
+                handleLaunch(item.path);
+              }
+            }}
+            onContextMenu={(e) => handleMenu(e, index, item)}
             style={{
+              display: 'flex',
+              flexWrap: 'wrap',
+              alignItems: 'center',
               padding: '0.5rem',
               marginBottom: '0.25rem',
               cursor: 'pointer',
@@ -57,7 +253,38 @@ function App() {
               borderRadius: '4px',
             }}
           >
-            {item?.title || String(item)}
+            {item?.icon && (
+              <img
+                src={iconSrc(item.icon)}
+                alt=""
+                width={ICON_SIZE}
+                height={ICON_SIZE}
+                style={{ marginRight: '0.5rem', flexShrink: 0 }}
+                onError={(e) => {
+                  e.currentTarget.style.visibility = 'hidden';
+                }}
+              />
+            )}
+            {item?.title ? <Highlight text={item.title} ranges={item.ranges} /> : String(item)}
+            {item?.kind === 'app' && (
+              <span style={{ marginLeft: '0.5rem', fontSize: '0.75rem', color: '#888' }}>Application</span>
+            )}
+            {item?.kind === 'answer' && (
+              <span style={{ marginLeft: '0.5rem', fontSize: '0.75rem', color: '#888' }}>Copy to clipboard</span>
+            )}
+            {menu?.index === index && (
+              <div style={{ flexBasis: '100%', marginTop: '0.5rem' }}>
+                {menu.actions.map((entry, i) => (
+                  <button
+                    key={i}
+                    onClick={(e) => handleAction(e, item.path, entry.action)}
+                    style={{ display: 'block', width: '100%', textAlign: 'left', padding: '0.25rem 0.5rem' }}
+                  >
+                    {entry.label}
+                  </button>
+                ))}
+              </div>
+            )}
           </li>
         ))}
       </ul>
@@ -0,0 +1,296 @@
+//! Secondary actions on search results. Besides opening it, a result can be
+//! revealed in the file manager, have its path copied, be opened with another
+//! application associated with its type, get a terminal in its directory, or
+//! be moved to the trash.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, ClipboardManager, Manager, State};
+
+use crate::apps::Apps;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::icons;
+use crate::index::{Change, Indexer};
+use crate::launch;
+use crate::mime::MimeDb;
+use crate::providers::Providers;
+use crate::search::SearchResult;
+use crate::settings::SettingsStore;
+use crate::trash::{self, TrashEntry};
+
+/// Something that can be done with a result. The webview passes these back
+/// verbatim to `run_action`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Action {
+    Open,
+    Reveal,
+    CopyPath,
+    /// Open with the application with this desktop file ID.
+    OpenWith {
+        app: String,
+    },
+    /// Open a terminal in the result's directory, or the one containing it.
+    Terminal,
+    Trash,
+    /// Put `text` on the clipboard.
+    Copy {
+        text: String,
+    },
+    /// Hand `data` back to the provider that listed the action.
+    Execute {
+        provider: String,
+        data: serde_json::Value,
+    },
+}
+
+/// An action as listed to the webview.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionItem {
+    pub action: Action,
+    pub label: String,
+    /// `icon://` URL, for actions tied to an application.
+    pub icon: Option<String>,
+}
+
+impl ActionItem {
+    pub fn new(action: Action, label: impl Into<String>) -> Self {
+        Self {
+            action,
+            label: label.into(),
+            icon: None,
+        }
+    }
+}
+
+/// The actions offered for `path`, the default one first. Applications can
+/// only be opened, revealed or have their `.desktop` path copied.
+pub fn actions_for(apps: &Apps, mime: &MimeDb, path: &Path) -> Result<Vec<ActionItem>> {
+    let meta = fs::metadata(path)?;
+    let is_app = apps.find(path).is_some();
+    let mut items = vec![ActionItem::new(Action::Open, "Open")];
+    if !is_app {
+        let types = mime.ancestors(mime.guess(path, meta.is_dir()));
+        items.extend(apps.for_types(&types).into_iter().map(|app| ActionItem {
+            label: format!("Open with {}", app.name),
+            icon: app.icon.as_deref().map(icons::name_url),
+            action: Action::OpenWith { app: app.id },
+        }));
+    }
+    items.push(ActionItem::new(Action::Reveal, "Show in File Manager"));
+    items.push(ActionItem::new(Action::CopyPath, "Copy Path"));
+    if !is_app {
+        items.push(ActionItem::new(Action::Terminal, "Open Terminal Here"));
+        items.push(ActionItem::new(Action::Trash, "Move to Trash"));
+    }
+    Ok(items)
+}
+
+/// Ask the file manager to select `path` over D-Bus. Returns `false` when no
+/// running file manager implements `org.freedesktop.FileManager1`.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn show_items(path: &Path) -> bool {
+    let Ok(uri) = tauri::Url::from_file_path(path) else {
+        return false;
+    };
+    // `dbus-send` splits array arguments on commas.
+    let uri = uri.as_str().replace(',', "%2C");
+    Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{uri}"))
+        .arg("string:")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn reveal(path: &Path) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = Command::new("explorer");
+        cmd.arg(format!("/select,{}", path.display()));
+        launch::spawn_detached(cmd)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut cmd = Command::new("open");
+        cmd.arg("-R").arg(path);
+        launch::spawn_detached(cmd)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // The D-Bus call waits for a reply, so keep it off the caller's thread.
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            if show_items(&path) {
+                return;
+            }
+            let dir = path.parent().unwrap_or(&path);
+            if let Err(e) = launch::spawn_detached(launch::opener(dir)) {
+                log::warn!("could not reveal {}: {e}", path.display());
+            }
+        });
+        Ok(())
+    }
+}
+
+fn terminal_in(dir: &Path) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", "cmd"]);
+        cmd
+    };
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let mut cmd = Command::new("open");
+        cmd.args(["-a", "Terminal"]).arg(dir);
+        cmd
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut cmd = Command::new(
+        crate::apps::terminal_program()
+            .ok_or_else(|| Error::Action("no terminal emulator found".into()))?,
+    );
+    cmd.current_dir(dir);
+    launch::spawn_detached(cmd)
+}
+
+/// Finder keeps its own trash, so there is no entry to restore from.
+#[cfg(target_os = "macos")]
+fn trash(path: &Path) -> Result<Option<TrashEntry>> {
+    // The path goes in as an argument rather than into the script, so no
+    // quoting is needed.
+    let output = Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "tell application \"Finder\" to delete POSIX file (item 1 of argv)",
+            "-e",
+            "end run",
+        ])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Action(format!(
+            "cannot move {} to the trash: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(None)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn trash(path: &Path) -> Result<Option<TrashEntry>> {
+    trash::trash(path).map(Some)
+}
+
+/// List the actions available for a result: the ones its provider offers,
+/// or those for its path.
+#[tauri::command]
+pub fn list_actions(
+    apps: State<'_, Apps>,
+    mime: State<'_, MimeDb>,
+    providers: State<'_, Providers>,
+    result: SearchResult,
+) -> Result<Vec<ActionItem>> {
+    let offered = providers
+        .get(&result.source)
+        .and_then(|provider| provider.actions(&result));
+    match offered {
+        Some(actions) => Ok(actions),
+        None => actions_for(&apps, &mime, Path::new(&result.path)),
+    }
+}
+
+/// Run one of the actions listed for a result. Opening a file with another
+/// application and trashing it are subject to the launch policy's allowed
+/// roots, like `launch`. Provider actions run in the background. Trashing
+/// returns the trash entry, if there is one to restore for undo.
+#[tauri::command]
+pub fn run_action(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    apps: State<'_, Apps>,
+    history: State<'_, History>,
+    indexer: State<'_, Indexer>,
+    path: PathBuf,
+    action: Action,
+) -> Result<Option<TrashEntry>> {
+    let policy = settings.get().launch.clone();
+    let mut trashed = None;
+    match action {
+        Action::Open => launch::open(&policy, &apps, &history, &path),
+        Action::Reveal => reveal(&path),
+        Action::CopyPath => app
+            .clipboard_manager()
+            .write_text(path.to_string_lossy())
+            .map_err(|e| Error::Action(format!("cannot copy to the clipboard: {e}"))),
+        Action::Copy { text } => app
+            .clipboard_manager()
+            .write_text(text)
+            .map_err(|e| Error::Action(format!("cannot copy to the clipboard: {e}"))),
+        Action::OpenWith { app: id } => {
+            let path = launch::check_root(&policy, &path)?;
+            let desktop_app = apps
+                .by_id(&id)
+                .ok_or_else(|| Error::Action(format!("unknown application `{id}`")))?;
+            desktop_app.launch(&[path.to_string_lossy().into_owned()])?;
+            launch::record(&history, &path);
+            Ok(())
+        }
+        Action::Terminal => {
+            let dir = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(&path)
+            };
+            terminal_in(dir)
+        }
+        Action::Trash => {
+            // Check the parent rather than the path itself, so a symlink is
+            // trashed instead of the file it points to.
+            let name = path
+                .file_name()
+                .ok_or_else(|| Error::Action(format!("cannot trash {}", path.display())))?;
+            let parent = path.parent().unwrap_or(Path::new("/"));
+            let path = launch::check_root(&policy, parent)?.join(name);
+            trashed = trash(&path)?;
+            // Drop it now rather than waiting for the watcher or a rescan.
+            indexer.apply(&[Change::Remove(path.clone())]);
+            if let Err(e) = history.forget(&path) {
+                log::warn!("could not update launch history: {e}");
+            }
+            Ok(())
+        }
+        Action::Execute { provider, data } => {
+            let provider = app
+                .state::<Providers>()
+                .get(&provider)
+                .ok_or_else(|| Error::Action(format!("unknown provider `{provider}`")))?;
+            // Providers may take a while; keep the main thread free.
+            std::thread::spawn(move || {
+                if let Err(e) = provider.execute(&data) {
+                    log::warn!("`{}` could not run an action: {e}", provider.id());
+                }
+            });
+            Ok(())
+        }
+    }?;
+    Ok(trashed)
+}
@@ -0,0 +1,227 @@
+//! Parser for freedesktop key files: `.desktop` entries and icon theme
+//! indexes.
+
+use std::collections::HashMap;
+use std::env;
+
+const MAIN_GROUP: &str = "Desktop Entry";
+
+/// Locale keys to try for a localized value, most specific first, as derived
+/// from `LC_ALL`, `LC_MESSAGES` or `LANG`.
+#[derive(Debug, Clone, Default)]
+pub struct Locale {
+    candidates: Vec<String>,
+}
+
+impl Locale {
+    pub fn from_env() -> Self {
+        let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|v| !v.is_empty());
+        value.map(|v| Self::parse(&v)).unwrap_or_default()
+    }
+
+    /// Split `lang_COUNTRY.ENCODING@MODIFIER`; the encoding is never matched.
+    pub fn parse(value: &str) -> Self {
+        let (rest, modifier) = match value.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier)),
+            None => (value, None),
+        };
+        let rest = rest.split('.').next().unwrap_or(rest);
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (rest, None),
+        };
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return Self::default();
+        }
+
+        let mut candidates = Vec::new();
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            candidates.push(format!("{lang}_{country}@{modifier}"));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{lang}_{country}"));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{lang}@{modifier}"));
+        }
+        candidates.push(lang.to_string());
+        Self { candidates }
+    }
+}
+
+/// A parsed key file, the format shared by `.desktop` files and icon theme
+/// `index.theme` files.
+#[derive(Debug, Default)]
+pub struct KeyFile {
+    groups: HashMap<String, Group>,
+}
+
+impl KeyFile {
+    pub fn parse(text: &str) -> Self {
+        let mut groups: HashMap<String, Group> = HashMap::new();
+        let mut current: Option<String> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                groups.entry(name.to_string()).or_default();
+                current = Some(name.to_string());
+                continue;
+            }
+            let group = current.as_ref().and_then(|name| groups.get_mut(name));
+            let (Some(group), Some((key, value))) = (group, line.split_once('=')) else {
+                continue;
+            };
+            // The first occurrence of a key wins.
+            group
+                .fields
+                .entry(key.trim_end().to_string())
+                .or_insert_with(|| value.trim_start().to_string());
+        }
+        Self { groups }
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.get(name)
+    }
+
+    pub fn take(&mut self, name: &str) -> Option<Group> {
+        self.groups.remove(name)
+    }
+}
+
+/// The key/value pairs of one group. Localized keys are kept verbatim, e.g.
+/// `Name[de]`.
+#[derive(Debug, Default)]
+pub struct Group {
+    fields: HashMap<String, String>,
+}
+
+impl Group {
+    /// The `[Desktop Entry]` group of a `.desktop` file.
+    pub fn desktop_entry(text: &str) -> Option<Self> {
+        KeyFile::parse(text).take(MAIN_GROUP)
+    }
+
+    fn raw(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    /// A `string` or `localestring` value with escapes resolved.
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.raw(key).map(unescape)
+    }
+
+    pub fn localized(&self, key: &str, locale: &Locale) -> Option<String> {
+        locale
+            .candidates
+            .iter()
+            .find_map(|l| self.raw(&format!("{key}[{l}]")))
+            .or_else(|| self.raw(key))
+            .map(unescape)
+    }
+
+    pub fn boolean(&self, key: &str) -> bool {
+        self.raw(key) == Some("true")
+    }
+
+    /// A `;`-separated list; `\;` is a literal semicolon.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.raw(key).map(split_list).unwrap_or_default()
+    }
+
+    /// A `,`-separated list, as used by icon theme indexes.
+    pub fn comma_list(&self, key: &str) -> Vec<String> {
+        self.raw(key)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn localized_list(&self, key: &str, locale: &Locale) -> Vec<String> {
+        locale
+            .candidates
+            .iter()
+            .find_map(|l| self.raw(&format!("{key}[{l}]")))
+            .or_else(|| self.raw(key))
+            .map(split_list)
+            .unwrap_or_default()
+    }
+}
+
+/// Escape a value for writing, the inverse of `unescape`.
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // Keep other escapes intact for `unescape`, so `\\;` still ends
+            // the item.
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(next) => {
+                    current.push('\\');
+                    current.push(next);
+                }
+                None => current.push('\\'),
+            },
+            ';' => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .into_iter()
+        .map(|item| unescape(&item))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
@@ -0,0 +1,304 @@
+//! `Exec` line handling: quoting rules and field-code expansion as described
+//! by the Desktop Entry Specification.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Terminal emulators tried, in order, for `Terminal=true` entries when
+/// `$TERMINAL` is unset.
+const TERMINALS: &[&str] = &[
+    "x-terminal-emulator",
+    "gnome-terminal",
+    "konsole",
+    "xfce4-terminal",
+    "alacritty",
+    "kitty",
+    "xterm",
+];
+
+/// Split an `Exec` value into arguments. Arguments are separated by spaces;
+/// a double-quoted argument may contain spaces and the escapes `\"`, `` \` ``,
+/// `\$` and `\\`.
+pub fn split(exec: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(e @ ('"' | '`' | '$' | '\\')) => current.push(e),
+                            Some(other) => {
+                                current.push('\\');
+                                current.push(other);
+                            }
+                            None => return Err("unterminated escape".into()),
+                        },
+                        Some(other) => current.push(other),
+                        None => return Err("unterminated quote".into()),
+                    }
+                }
+            }
+            _ => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        return Err("empty command".into());
+    }
+    Ok(args)
+}
+
+/// Join literal arguments into an `Exec` value: `%` is doubled so it is not
+/// read as a field code, and arguments with reserved characters are quoted.
+pub fn join<S: AsRef<str>>(args: &[S]) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    let quoted: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let arg = arg.as_ref().replace('%', "%%");
+            if !arg.is_empty() && !arg.contains(RESERVED) {
+                return arg;
+            }
+            let mut out = String::from('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+            out
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+/// What the field codes of one entry expand to, other than the files.
+pub struct Fields<'a> {
+    pub name: &'a str,
+    pub icon: Option<&'a str>,
+    pub location: &'a Path,
+}
+
+/// Expand the field codes in `args` for `files`. `%f` and `%u` take a single
+/// file, so more than one file yields one command line per file; `%F` and
+/// `%U` take them all. Codes with nothing to expand to are dropped, as are the
+/// deprecated ones.
+pub fn expand(args: &[String], fields: &Fields<'_>, files: &[String]) -> Vec<Vec<String>> {
+    let single = args
+        .iter()
+        .any(|arg| arg.contains("%f") || arg.contains("%u"));
+    if single && files.len() > 1 {
+        return files
+            .iter()
+            .flat_map(|file| expand(args, fields, std::slice::from_ref(file)))
+            .collect();
+    }
+
+    let mut out = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "%F" | "%U" => out.extend(files.iter().cloned()),
+            "%f" | "%u" => out.extend(files.first().cloned()),
+            "%i" => {
+                if let Some(icon) = fields.icon {
+                    out.push("--icon".into());
+                    out.push(icon.into());
+                }
+            }
+            _ => {
+                // An argument made only of codes that expand to nothing is
+                // dropped rather than passed as an empty string.
+                let expanded = expand_inline(arg, fields, files.first());
+                if !expanded.is_empty() || arg.is_empty() {
+                    out.push(expanded);
+                }
+            }
+        }
+    }
+    vec![out]
+}
+
+fn expand_inline(arg: &str, fields: &Fields<'_>, file: Option<&String>) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('f' | 'u') => out.extend(file.map(|f| f.as_str())),
+            Some('c') => out.push_str(fields.name),
+            Some('k') => out.push_str(&fields.location.to_string_lossy()),
+            // `%F`, `%U` and `%i` are only valid as whole arguments; the rest
+            // are deprecated or unknown.
+            Some(_) | None => {}
+        }
+    }
+    out
+}
+
+/// Find `program` on `PATH`, or check it directly if it contains a slash.
+pub fn which(program: &str) -> Option<PathBuf> {
+    let candidate = Path::new(program);
+    if program.contains('/') {
+        return is_executable(candidate).then(|| candidate.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// The terminal emulator to use: `$TERMINAL` if it can be found, otherwise
+/// the first installed one of a list of common emulators.
+pub fn terminal_program() -> Option<String> {
+    env::var("TERMINAL")
+        .ok()
+        .filter(|t| which(t).is_some())
+        .or_else(|| {
+            TERMINALS
+                .iter()
+                .find(|t| which(t).is_some())
+                .map(|t| t.to_string())
+        })
+}
+
+/// The command prefix that runs a program inside a terminal emulator, e.g.
+/// `["xterm", "-e"]`.
+pub fn terminal() -> Option<Vec<String>> {
+    let program = terminal_program()?;
+    let flag = if program.ends_with("gnome-terminal") {
+        "--"
+    } else {
+        "-e"
+    };
+    Some(vec![program, flag.to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(exec: &str) -> Vec<String> {
+        split(exec).unwrap()
+    }
+
+    fn fields() -> Fields<'static> {
+        Fields {
+            name: "Text Editor",
+            icon: Some("accessories-text-editor"),
+            location: Path::new("/usr/share/applications/editor.desktop"),
+        }
+    }
+
+    fn files(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn split_honours_quotes_and_escapes() {
+        assert_eq!(
+            args(r#"editor "a b" "say \"hi\"" "\$HOME" c\d"#),
+            ["editor", "a b", "say \"hi\"", "$HOME", "c\\d"]
+        );
+        assert_eq!(args(r#"editor """#), ["editor", ""]);
+        assert!(split(r#"editor "open"#).is_err());
+        assert!(split("  ").is_err());
+    }
+
+    #[test]
+    fn join_round_trips_through_split() {
+        let original = files(&["/opt/my app/run", "--name=$USER", "100%", ""]);
+        let joined = join(&original);
+        assert!(joined.contains("100%%"));
+        let split = args(&joined);
+        let expanded = expand(&split, &fields(), &[]);
+        assert_eq!(expanded, [original]);
+    }
+
+    #[test]
+    fn whole_argument_codes() {
+        let exec = args("editor %i %F");
+        assert_eq!(
+            expand(&exec, &fields(), &files(&["/a", "/b"])),
+            [files(&[
+                "editor",
+                "--icon",
+                "accessories-text-editor",
+                "/a",
+                "/b"
+            ])]
+        );
+        let no_icon = Fields {
+            icon: None,
+            ..fields()
+        };
+        assert_eq!(expand(&exec, &no_icon, &[]), [files(&["editor"])]);
+    }
+
+    #[test]
+    fn single_file_codes_repeat_the_command() {
+        let exec = args("editor --open=%u");
+        assert_eq!(
+            expand(&exec, &fields(), &files(&["/a", "/b"])),
+            [
+                files(&["editor", "--open=/a"]),
+                files(&["editor", "--open=/b"])
+            ]
+        );
+        assert_eq!(
+            expand(&args("editor %f"), &fields(), &[]),
+            [files(&["editor"])]
+        );
+    }
+
+    #[test]
+    fn inline_codes() {
+        let exec = args(r#"editor "--title=%c" --desktop=%k 50%% %d%n"#);
+        assert_eq!(
+            expand(&exec, &fields(), &[]),
+            [files(&[
+                "editor",
+                "--title=Text Editor",
+                "--desktop=/usr/share/applications/editor.desktop",
+                "50%",
+            ])]
+        );
+    }
+}
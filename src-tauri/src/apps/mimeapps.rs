@@ -0,0 +1,91 @@
+//! Default and associated applications per MIME type, read from the
+//! `mimeapps.list` files of the MIME Applications Associations spec.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+use super::desktop::KeyFile;
+use super::DesktopApp;
+
+const DEFAULT: &str = "Default Applications";
+const ADDED: &str = "Added Associations";
+const REMOVED: &str = "Removed Associations";
+
+/// Every `mimeapps.list` location, most important first: user config,
+/// system config, then the `applications` data directories, each with the
+/// desktop-specific `$desktop-mimeapps.list` before the generic file.
+fn list_files(desktops: &[String]) -> Vec<PathBuf> {
+    let system = env::var_os("XDG_CONFIG_DIRS")
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".into());
+    let dirs = tauri::api::path::config_dir()
+        .into_iter()
+        .chain(env::split_paths(&system))
+        .chain(
+            super::data_dirs()
+                .into_iter()
+                .map(|dir| dir.join("applications")),
+        );
+    dirs.flat_map(|dir| {
+        desktops
+            .iter()
+            .map(|d| format!("{}-mimeapps.list", d.to_lowercase()))
+            .chain(iter::once("mimeapps.list".to_string()))
+            .map(move |file| dir.join(file))
+            .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+fn ids(file: &KeyFile, group: &str, mime: &str) -> Vec<String> {
+    file.group(group).map(|g| g.list(mime)).unwrap_or_default()
+}
+
+/// Applications from `apps` that can open `types`, which are tried in order.
+/// The first installed default comes first, then for each type the added
+/// associations and the applications declaring it, minus removed ones.
+pub fn applications(apps: &[DesktopApp], types: &[String], desktops: &[String]) -> Vec<DesktopApp> {
+    let files: Vec<KeyFile> = list_files(desktops)
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|text| KeyFile::parse(&text))
+        .collect();
+    let by_id: HashMap<&str, &DesktopApp> = apps.iter().map(|app| (app.id.as_str(), app)).collect();
+
+    let mut out: Vec<&DesktopApp> = Vec::new();
+    let default = types.iter().find_map(|mime| {
+        files
+            .iter()
+            .flat_map(|file| ids(file, DEFAULT, mime))
+            .find_map(|id| by_id.get(id.as_str()).copied())
+    });
+    out.extend(default);
+
+    for mime in types {
+        // A file's removals only hide associations from files after it.
+        let mut removed = HashSet::new();
+        let mut found = Vec::new();
+        for file in &files {
+            found.extend(
+                ids(file, ADDED, mime)
+                    .iter()
+                    .filter(|id| !removed.contains(*id))
+                    .filter_map(|id| by_id.get(id.as_str()).copied()),
+            );
+            removed.extend(ids(file, REMOVED, mime));
+        }
+        found.extend(
+            apps.iter()
+                .filter(|app| app.mime_types.contains(mime) && !removed.contains(&app.id)),
+        );
+        for app in found {
+            if !out.iter().any(|a| a.id == app.id) {
+                out.push(app);
+            }
+        }
+    }
+    out.into_iter().cloned().collect()
+}
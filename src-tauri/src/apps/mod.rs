@@ -0,0 +1,346 @@
+//! Installed applications, discovered from freedesktop `.desktop` entries.
+//!
+//! Entries are read from the `applications` directory under
+//! `$XDG_DATA_HOME` and each of `$XDG_DATA_DIRS`, earlier directories taking
+//! precedence by desktop file ID. The catalog reloads itself when one of
+//! those directories changes, checked at most once every [`RECHECK`].
+//! Symlinked directories are not followed.
+
+mod desktop;
+mod exec;
+mod mimeapps;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::{Error, Result};
+use crate::launch;
+
+use desktop::Locale;
+
+pub use desktop::{escape, Group, KeyFile};
+pub use exec::{join as join_exec, split as split_exec, terminal_program};
+
+/// How long the catalog is trusted before the directories are checked for
+/// changes again.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// An application that can be shown and launched.
+#[derive(Debug, Clone)]
+pub struct DesktopApp {
+    /// Desktop file ID, e.g. `org.gnome.Nautilus.desktop`.
+    pub id: String,
+    /// The `.desktop` file this entry was read from.
+    pub path: PathBuf,
+    pub name: String,
+    pub generic_name: Option<String>,
+    pub keywords: Vec<String>,
+    /// MIME types the application can open.
+    pub mime_types: Vec<String>,
+    /// `Exec` split into arguments, field codes not yet expanded.
+    exec: Vec<String>,
+    /// Icon theme name, or an absolute path to an image.
+    pub icon: Option<String>,
+    terminal: bool,
+    working_dir: Option<PathBuf>,
+}
+
+impl DesktopApp {
+    /// Build an app from a parsed entry. Returns `None` for entries that must
+    /// not be shown: other types, `NoDisplay`, `Hidden`, entries restricted to
+    /// other desktops, and those whose `TryExec` is missing.
+    fn from_entry(
+        id: String,
+        path: PathBuf,
+        entry: &Group,
+        locale: &Locale,
+        desktops: &[String],
+    ) -> Option<Self> {
+        if entry.string("Type").as_deref() != Some("Application")
+            || entry.boolean("NoDisplay")
+            || entry.boolean("Hidden")
+        {
+            return None;
+        }
+        let only = entry.list("OnlyShowIn");
+        if !only.is_empty() && !only.iter().any(|d| desktops.contains(d)) {
+            return None;
+        }
+        if entry.list("NotShowIn").iter().any(|d| desktops.contains(d)) {
+            return None;
+        }
+        if let Some(try_exec) = entry.string("TryExec") {
+            exec::which(&try_exec)?;
+        }
+        let exec = match exec::split(&entry.string("Exec")?) {
+            Ok(exec) => exec,
+            Err(e) => {
+                log::debug!("skipping {}: invalid Exec: {e}", path.display());
+                return None;
+            }
+        };
+        Some(Self {
+            name: entry.localized("Name", locale)?,
+            generic_name: entry.localized("GenericName", locale),
+            keywords: entry.localized_list("Keywords", locale),
+            mime_types: entry.list("MimeType"),
+            exec,
+            icon: entry.string("Icon").filter(|i| !i.is_empty()),
+            terminal: entry.boolean("Terminal"),
+            working_dir: entry
+                .string("Path")
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+            id,
+            path,
+        })
+    }
+
+    /// Start the application with `files` (paths or URLs) substituted for
+    /// its field codes.
+    pub fn launch(&self, files: &[String]) -> Result<()> {
+        let fields = exec::Fields {
+            name: &self.name,
+            icon: self.icon.as_deref(),
+            location: &self.path,
+        };
+        for mut argv in exec::expand(&self.exec, &fields, files) {
+            if self.terminal {
+                let prefix = exec::terminal()
+                    .ok_or_else(|| Error::App(self.id.clone(), "no terminal emulator".into()))?;
+                argv.splice(0..0, prefix);
+            }
+            let (program, args) = argv
+                .split_first()
+                .ok_or_else(|| Error::App(self.id.clone(), "empty command".into()))?;
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            if let Some(dir) = self.working_dir.as_ref().filter(|d| d.is_dir()) {
+                cmd.current_dir(dir);
+            }
+            log::info!("launching {} as {argv:?}", self.id);
+            launch::spawn_detached(cmd)?;
+        }
+        Ok(())
+    }
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the spec's defaults.
+/// Empty on platforms that do not follow the XDG layout.
+pub fn data_dirs() -> Vec<PathBuf> {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return Vec::new();
+    }
+    let non_empty = |var: &str| env::var_os(var).filter(|v| !v.is_empty());
+    let home = non_empty("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| tauri::api::path::home_dir().map(|h| h.join(".local/share")));
+    let system = non_empty("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    home.into_iter().chain(env::split_paths(&system)).collect()
+}
+
+/// Desktop names from `$XDG_CURRENT_DESKTOP`, matched against `OnlyShowIn`
+/// and `NotShowIn`.
+fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .map(|v| {
+            v.split(':')
+                .filter(|d| !d.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Collect every `.desktop` file below `dir` with its desktop file ID: the
+/// path relative to `dir` with `/` replaced by `-`.
+fn collect(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf)>) {
+    let Ok(read) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if kind.is_dir() {
+            collect(&path, &format!("{prefix}{name}-"), out);
+        } else if name.ends_with(".desktop") {
+            out.push((format!("{prefix}{name}"), path));
+        }
+    }
+}
+
+fn discover(dirs: &[PathBuf]) -> Vec<DesktopApp> {
+    let locale = Locale::from_env();
+    let desktops = current_desktops();
+    let mut seen = HashSet::new();
+    let mut apps = Vec::new();
+    for dir in dirs {
+        let mut files = Vec::new();
+        collect(dir, "", &mut files);
+        for (id, path) in files {
+            // A higher-precedence entry with the same ID shadows this one,
+            // even if it is hidden.
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            let Some(entry) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| Group::desktop_entry(&text))
+            else {
+                continue;
+            };
+            apps.extend(DesktopApp::from_entry(id, path, &entry, &locale, &desktops));
+        }
+    }
+    log::info!("found {} applications", apps.len());
+    apps
+}
+
+struct Catalog {
+    apps: Arc<Vec<DesktopApp>>,
+    /// Modification times of the applications directories and their
+    /// subdirectories when `apps` was loaded.
+    stamps: Vec<Stamp>,
+    /// When `stamps` were last compared with the directories.
+    checked: Instant,
+}
+
+struct Inner {
+    dirs: Vec<PathBuf>,
+    catalog: RwLock<Catalog>,
+}
+
+/// Managed state holding the discovered applications. Cheap to clone.
+#[derive(Clone)]
+pub struct Apps(Arc<Inner>);
+
+type Stamp = (PathBuf, Option<SystemTime>);
+
+/// Modification times of `dirs` and every directory below them. Adding,
+/// removing or renaming an entry changes its own directory's time only, so
+/// vendor subdirectories like `applications/kde` are checked too.
+fn stamps(dirs: &[PathBuf]) -> Vec<Stamp> {
+    let mut out = Vec::new();
+    for dir in dirs {
+        stamp(dir, &mut out);
+    }
+    out
+}
+
+fn stamp(dir: &Path, out: &mut Vec<Stamp>) {
+    let modified = fs::metadata(dir).and_then(|m| m.modified()).ok();
+    out.push((dir.to_path_buf(), modified));
+    let Ok(read) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            stamp(&entry.path(), out);
+        }
+    }
+}
+
+impl Apps {
+    pub fn load() -> Self {
+        let dirs: Vec<PathBuf> = data_dirs()
+            .into_iter()
+            .map(|dir| dir.join("applications"))
+            .collect();
+        let catalog = Catalog {
+            stamps: stamps(&dirs),
+            apps: Arc::new(discover(&dirs)),
+            checked: Instant::now(),
+        };
+        Self(Arc::new(Inner {
+            dirs,
+            catalog: RwLock::new(catalog),
+        }))
+    }
+
+    /// The current applications, rediscovered first if an applications
+    /// directory or one below it changed since the last check.
+    pub fn all(&self) -> Arc<Vec<DesktopApp>> {
+        {
+            let catalog = self.0.catalog.read().unwrap();
+            if catalog.checked.elapsed() < RECHECK {
+                return catalog.apps.clone();
+            }
+        }
+        let current = stamps(&self.0.dirs);
+        let mut catalog = self.0.catalog.write().unwrap();
+        catalog.checked = Instant::now();
+        if catalog.stamps != current {
+            catalog.apps = Arc::new(discover(&self.0.dirs));
+            catalog.stamps = current;
+        }
+        catalog.apps.clone()
+    }
+
+    /// The application whose `.desktop` file is `path`.
+    pub fn find(&self, path: &Path) -> Option<DesktopApp> {
+        self.all().iter().find(|app| app.path == path).cloned()
+    }
+
+    /// The application with desktop file ID `id`.
+    pub fn by_id(&self, id: &str) -> Option<DesktopApp> {
+        self.all().iter().find(|app| app.id == id).cloned()
+    }
+
+    /// Applications that can open a file of the given types, listed most
+    /// specific type first. The user's default comes first. Only
+    /// applications shown in search are considered, so `NoDisplay` handlers
+    /// are never offered.
+    pub fn for_types(&self, types: &[String]) -> Vec<DesktopApp> {
+        mimeapps::applications(&self.all(), types, &current_desktops())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_cover_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let vendor = dir.path().join("kde");
+        fs::create_dir(&vendor).unwrap();
+        let dirs = [dir.path().to_path_buf()];
+        let before = stamps(&dirs);
+        assert_eq!(before.len(), 2);
+        assert!(before.iter().any(|(path, _)| *path == vendor));
+
+        // Only the subdirectory's time moves when a file is added there.
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        fs::File::open(&vendor)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+        let old = stamps(&dirs);
+        fs::write(vendor.join("okular.desktop"), "").unwrap();
+        assert_ne!(stamps(&dirs), old);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("editor.desktop"), "").unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+        let dirs = [dir.path().to_path_buf()];
+        assert_eq!(stamps(&dirs).len(), 1);
+        let mut files = Vec::new();
+        collect(dir.path(), "", &mut files);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "editor.desktop");
+    }
+}
@@ -0,0 +1,252 @@
+//! Starting the launcher at login through an XDG autostart entry in
+//! `~/.config/autostart`.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::apps::{self, Group};
+use crate::cli::HIDDEN_FLAG;
+use crate::error::{Error, Result};
+
+const ENTRY_NAME: &str = "quicklauncher.desktop";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AutostartStatus {
+    pub enabled: bool,
+    /// The executable the entry starts.
+    pub exec: Option<PathBuf>,
+    /// The entry starts some other executable than this one, usually because
+    /// the launcher was moved or reinstalled since it was written.
+    pub moved: bool,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn entry_path() -> Result<PathBuf> {
+    tauri::api::path::config_dir()
+        .map(|dir| dir.join("autostart").join(ENTRY_NAME))
+        .ok_or_else(|| Error::Autostart("could not resolve the config directory".into()))
+}
+
+/// macOS and Windows do not read XDG autostart entries.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn entry_path() -> Result<PathBuf> {
+    Err(Error::Autostart(
+        "only supported on freedesktop.org desktops".into(),
+    ))
+}
+
+/// The executable to start at login. An AppImage runs from a temporary
+/// mount, so the image itself is recorded instead.
+fn executable() -> Result<PathBuf> {
+    match env::var_os("APPIMAGE") {
+        Some(image) => Ok(PathBuf::from(image)),
+        None => Ok(env::current_exe()?),
+    }
+}
+
+pub fn status() -> Result<AutostartStatus> {
+    let text = match fs::read_to_string(entry_path()?) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(AutostartStatus::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(entry) = Group::desktop_entry(&text) else {
+        return Ok(AutostartStatus::default());
+    };
+    // Desktop settings panels switch entries off with either key rather
+    // than deleting them.
+    let enabled = !entry.boolean("Hidden")
+        && entry.string("X-GNOME-Autostart-enabled").as_deref() != Some("false");
+    let exec = entry
+        .string("Exec")
+        .and_then(|exec| apps::split_exec(&exec).ok())
+        .and_then(|args| args.into_iter().next())
+        .map(|program| PathBuf::from(program.replace("%%", "%")));
+    let moved = exec.as_ref() != Some(&executable()?);
+    Ok(AutostartStatus {
+        enabled,
+        exec,
+        moved,
+    })
+}
+
+/// Write the entry, pointing it at the running executable.
+pub fn enable() -> Result<AutostartStatus> {
+    let path = entry_path()?;
+    let exe = executable()?;
+    let exec = apps::join_exec(&[exe.to_string_lossy().as_ref(), HIDDEN_FLAG]);
+    let text = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Quick Launcher\n\
+         Comment=Start Quick Launcher in the system tray\n\
+         Exec={}\n\
+         Terminal=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        apps::escape(&exec)
+    );
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write to a sibling file first so a crash never leaves a truncated file.
+    let tmp = path.with_extension("desktop.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(tmp, &path)?;
+    status()
+}
+
+pub fn disable() -> Result<AutostartStatus> {
+    match fs::remove_file(entry_path()?) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    status()
+}
+
+/// Point an enabled entry at this executable if the one it names is gone.
+/// An entry naming another executable that still exists, such as an
+/// installed copy while a development build runs, is left alone.
+pub fn repair() {
+    let status = match status() {
+        Ok(status) => status,
+        Err(e) => {
+            log::debug!("autostart status unavailable: {e}");
+            return;
+        }
+    };
+    if !status.enabled || !status.moved {
+        return;
+    }
+    match &status.exec {
+        Some(exec) if exec.exists() => {
+            log::warn!(
+                "autostart entry starts {}, not this executable",
+                exec.display()
+            );
+        }
+        _ => {
+            log::info!("the launcher moved; updating the autostart entry");
+            if let Err(e) = enable() {
+                log::warn!("could not update the autostart entry: {e}");
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn autostart_status() -> Result<AutostartStatus> {
+    status()
+}
+
+#[tauri::command]
+pub fn set_autostart(app: AppHandle, enabled: bool) -> Result<AutostartStatus> {
+    let status = if enabled { enable() } else { disable() }?;
+    crate::tray::refresh(&app);
+    Ok(status)
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::sync::{Mutex, MutexGuard};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Held by tests that point `XDG_CONFIG_HOME` and `APPIMAGE` elsewhere.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// A temporary config directory, and this executable pretending to be an
+    /// AppImage whose name needs quoting.
+    fn setup() -> (TempDir, PathBuf, MutexGuard<'static, ()>) {
+        let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = TempDir::new().unwrap();
+        let image = dir.path().join("Quick $Launcher 100%.AppImage");
+        fs::write(&image, "").unwrap();
+        env::set_var("XDG_CONFIG_HOME", dir.path().join("config"));
+        env::set_var("APPIMAGE", &image);
+        (dir, image, guard)
+    }
+
+    fn write_entry(dir: &TempDir, exec: &str, enabled: bool) {
+        let autostart = dir.path().join("config/autostart");
+        fs::create_dir_all(&autostart).unwrap();
+        let text = format!(
+            "[Desktop Entry]\nType=Application\nName=Quick Launcher\nExec={exec}\n\
+             X-GNOME-Autostart-enabled={enabled}\n"
+        );
+        fs::write(autostart.join(ENTRY_NAME), text).unwrap();
+    }
+
+    fn entry(dir: &TempDir) -> String {
+        fs::read_to_string(dir.path().join("config/autostart").join(ENTRY_NAME)).unwrap()
+    }
+
+    fn status_now() -> AutostartStatus {
+        status().unwrap()
+    }
+
+    #[test]
+    fn entries_start_this_executable_hidden() {
+        let (dir, image, _guard) = setup();
+        let status = enable().unwrap();
+        let exec = format!(
+            "Exec=\"{}/Quick \\\\$Launcher 100%%.AppImage\" --hidden\n",
+            dir.path().display()
+        );
+        assert!(entry(&dir).contains(&exec), "{}", entry(&dir));
+        assert_eq!(
+            status,
+            AutostartStatus {
+                enabled: true,
+                exec: Some(image),
+                moved: false,
+            }
+        );
+
+        disable().unwrap();
+        assert_eq!(status_now(), AutostartStatus::default());
+    }
+
+    #[test]
+    fn moved_entries_are_repaired() {
+        let (dir, image, _guard) = setup();
+        write_entry(&dir, "/nonexistent/quicklauncher --hidden", true);
+        let moved = status_now();
+        assert!(moved.enabled && moved.moved);
+        assert_eq!(
+            moved.exec,
+            Some(PathBuf::from("/nonexistent/quicklauncher"))
+        );
+
+        repair();
+        let repaired = status_now();
+        assert!(!repaired.moved);
+        assert_eq!(repaired.exec, Some(image));
+    }
+
+    #[test]
+    fn other_or_disabled_entries_are_left_alone() {
+        let (dir, _image, _guard) = setup();
+        // Another copy that still exists, such as an installed one.
+        let other = dir.path().join("quicklauncher");
+        fs::write(&other, "").unwrap();
+        write_entry(&dir, &other.display().to_string(), true);
+        let before = entry(&dir);
+        repair();
+        assert!(status_now().moved);
+        assert_eq!(entry(&dir), before);
+
+        write_entry(&dir, "/nonexistent/quicklauncher", false);
+        let before = entry(&dir);
+        repair();
+        assert!(!status_now().enabled);
+        assert_eq!(entry(&dir), before);
+    }
+}
@@ -0,0 +1,293 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A run at least this long counts as healthy and resets the backoff.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+const TERM_GRACE: Duration = Duration::from_secs(3);
+
+/// How the FastAPI backend is started. Stored under `backend` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackendSettings {
+    pub enabled: bool,
+    /// Python interpreter used to run uvicorn.
+    pub python: String,
+    /// Directory containing the `backend` package; defaults to the repository
+    /// root in debug builds and the resource directory otherwise.
+    pub root: Option<PathBuf>,
+    /// Fixed loopback port; when unset a free one is picked at startup so
+    /// several launchers can run side by side.
+    pub port: Option<u16>,
+}
+
+impl Default for BackendSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            python: "python3".into(),
+            root: None,
+            port: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendStatus {
+    Disabled,
+    Starting,
+    Ready,
+    Restarting,
+    Stopped,
+}
+
+struct Inner {
+    /// Port of the current backend process; `None` while disabled.
+    port: Mutex<Option<u16>>,
+    child: Mutex<Option<Child>>,
+    status: Mutex<BackendStatus>,
+    shutdown: AtomicBool,
+}
+
+/// Managed handle to the supervised backend process.
+#[derive(Clone)]
+pub struct Backend(Arc<Inner>);
+
+impl Backend {
+    pub fn disabled() -> Self {
+        Self(Arc::new(Inner {
+            port: Mutex::new(None),
+            child: Mutex::new(None),
+            status: Mutex::new(BackendStatus::Disabled),
+            shutdown: AtomicBool::new(true),
+        }))
+    }
+
+    /// Start the supervisor thread. It spawns the backend, waits for it to
+    /// answer `/health`, and restarts it with exponential backoff when it exits.
+    /// Without a fixed port each start gets a fresh free one, so a port taken
+    /// in the meantime only costs a restart; `url` follows it.
+    pub fn spawn(settings: BackendSettings, root: PathBuf) -> io::Result<Self> {
+        let backend = Self(Arc::new(Inner {
+            port: Mutex::new(None),
+            child: Mutex::new(None),
+            status: Mutex::new(BackendStatus::Starting),
+            shutdown: AtomicBool::new(false),
+        }));
+        let supervisor = backend.clone();
+        thread::Builder::new()
+            .name("backend-supervisor".into())
+            .spawn(move || supervisor.supervise(&settings, &root))?;
+        Ok(backend)
+    }
+
+    /// Base URL of the current backend process, or `None` when it is
+    /// disabled. The port changes across restarts unless it is fixed.
+    pub fn url(&self) -> Option<String> {
+        self.0
+            .port
+            .lock()
+            .unwrap()
+            .map(|port| format!("http://127.0.0.1:{port}"))
+    }
+
+    pub fn status(&self) -> BackendStatus {
+        *self.0.status.lock().unwrap()
+    }
+
+    fn set_status(&self, status: BackendStatus) {
+        *self.0.status.lock().unwrap() = status;
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.0.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn supervise(&self, settings: &BackendSettings, root: &Path) {
+        let mut backoff = MIN_BACKOFF;
+        while !self.is_shutting_down() {
+            let started = Instant::now();
+            let port = match settings.port {
+                Some(port) => Ok(port),
+                None => free_port(),
+            };
+            match port.and_then(|port| self.start(settings, root, port).map(|()| port)) {
+                Ok(port) => {
+                    self.wait_ready(port);
+                    self.wait_exit();
+                }
+                Err(e) => log::error!(target: "backend", "failed to start backend: {e}"),
+            }
+            if self.is_shutting_down() {
+                break;
+            }
+
+            if started.elapsed() >= STABLE_RUN {
+                backoff = MIN_BACKOFF;
+            }
+            log::warn!(target: "backend", "backend exited; restarting in {backoff:?}");
+            self.set_status(BackendStatus::Restarting);
+            self.sleep_unless_shutdown(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        self.set_status(BackendStatus::Stopped);
+    }
+
+    fn start(&self, settings: &BackendSettings, root: &Path, port: u16) -> io::Result<()> {
+        let mut child = Command::new(&settings.python)
+            .args(["-m", "uvicorn", "backend.app:app", "--host", "127.0.0.1"])
+            .arg("--port")
+            .arg(port.to_string())
+            .current_dir(root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        log::info!(target: "backend", "started backend (pid {})", child.id());
+
+        if let Some(stdout) = child.stdout.take() {
+            forward_output(stdout, log::Level::Info);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            // uvicorn writes its access and startup logs to stderr as well.
+            forward_output(stderr, log::Level::Warn);
+        }
+        let mut slot = self.0.child.lock().unwrap();
+        if self.is_shutting_down() {
+            // Shutdown raced the spawn; don't leave an orphan behind.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(());
+        }
+        *slot = Some(child);
+        drop(slot);
+        *self.0.port.lock().unwrap() = Some(port);
+        self.set_status(BackendStatus::Starting);
+        Ok(())
+    }
+
+    fn wait_ready(&self, port: u16) {
+        let url = format!("http://127.0.0.1:{port}/health");
+        let deadline = Instant::now() + READY_TIMEOUT;
+        while Instant::now() < deadline && !self.is_shutting_down() && self.is_running() {
+            if ureq::get(&url).timeout(PROBE_INTERVAL).call().is_ok() {
+                log::info!(target: "backend", "backend ready on port {port}");
+                self.set_status(BackendStatus::Ready);
+                return;
+            }
+            thread::sleep(PROBE_INTERVAL);
+        }
+        if self.is_running() && !self.is_shutting_down() {
+            log::warn!(target: "backend", "backend did not become ready within {READY_TIMEOUT:?}");
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        match self.0.child.lock().unwrap().as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+
+    fn wait_exit(&self) {
+        loop {
+            if let Some(child) = self.0.child.lock().unwrap().as_mut() {
+                match child.try_wait() {
+                    Ok(None) => {}
+                    Ok(Some(status)) => {
+                        log::info!(target: "backend", "backend exited with {status}");
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!(target: "backend", "failed to poll backend: {e}");
+                        return;
+                    }
+                }
+            } else {
+                return;
+            }
+            thread::sleep(PROBE_INTERVAL);
+        }
+    }
+
+    fn sleep_unless_shutdown(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline && !self.is_shutting_down() {
+            thread::sleep(PROBE_INTERVAL);
+        }
+    }
+
+    /// Stop supervising and terminate the backend, escalating to a kill if it
+    /// does not exit within a short grace period.
+    pub fn shutdown(&self) {
+        if self.0.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Some(mut child) = self.0.child.lock().unwrap().take() else {
+            return;
+        };
+        terminate(&child);
+        let deadline = Instant::now() + TERM_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                log::info!(target: "backend", "backend stopped");
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        log::warn!(target: "backend", "backend ignored SIGTERM; killing it");
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Ask the kernel for an unused loopback port. The listener is dropped before
+/// the backend binds, which leaves a small window for another process to take
+/// it; the backend then fails to start and the supervisor picks another port
+/// for the next attempt.
+fn free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+#[cfg(unix)]
+fn terminate(child: &Child) {
+    // SAFETY: sending a signal to a pid we spawned and have not reaped yet.
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(_child: &Child) {}
+
+fn forward_output<R: Read + Send + 'static>(stream: R, level: log::Level) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            log::log!(target: "backend", level, "{line}");
+        }
+    });
+}
+
+#[tauri::command]
+pub fn backend_status(backend: State<'_, Backend>) -> BackendStatus {
+    backend.status()
+}
+
+#[tauri::command]
+pub fn backend_url(backend: State<'_, Backend>) -> Option<String> {
+    backend.url()
+}
@@ -0,0 +1,574 @@
+//! Decimal numbers of any size. Addition, subtraction and multiplication are
+//! exact; division, roots and the transcendental functions keep [`PLACES`]
+//! fractional digits, worked out with a few more so those are right.
+//! Exponentials and inexact powers carry about [`WORK`] significant digits,
+//! so large ones keep only the places that are right, and one whose integer
+//! part is not right is refused.
+//!
+//! Series are summed on fixed-point integers scaled by `10^WORK`.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use super::CalcError;
+
+/// Fractional digits kept by operations that cannot be exact.
+pub const PLACES: u32 = 50;
+/// Extra digits carried while summing series.
+const GUARD: u32 = 15;
+/// Scale of the fixed-point integers series are summed in.
+const WORK: u32 = PLACES + GUARD;
+/// Results with more integer digits than this are refused.
+const MAX_DIGITS: i64 = 1000;
+/// Largest `x` whose `e^x` stays below `10^MAX_DIGITS`.
+const MAX_EXP: u32 = 2302;
+/// Below `-MIN_EXP`, `e^x` rounds to zero at `WORK` places.
+const MIN_EXP: u32 = 150;
+/// Integer powers whose result has more bits than this are not worked out
+/// exactly.
+const MAX_EXACT_BITS: u64 = 3400;
+/// Nor are those with more fractional digits than this.
+const MAX_EXACT_SCALE: u64 = 1100;
+
+type Result<T> = std::result::Result<T, CalcError>;
+
+fn ten_pow(n: u32) -> BigInt {
+    BigInt::from(10u32).pow(n)
+}
+
+/// `n / d`, rounded half away from zero.
+fn div_round(n: &BigInt, d: &BigInt) -> BigInt {
+    let (q, r) = n.div_rem(d);
+    if r.abs() * 2u32 < d.abs() {
+        q
+    } else if n.is_negative() != d.is_negative() {
+        q - 1u32
+    } else {
+        q + 1u32
+    }
+}
+
+/// 1 in fixed point.
+fn unit() -> BigInt {
+    ten_pow(WORK)
+}
+
+fn fmul(a: &BigInt, b: &BigInt) -> BigInt {
+    div_round(&(a * b), &unit())
+}
+
+fn fdiv(a: &BigInt, b: &BigInt) -> BigInt {
+    div_round(&(a * unit()), b)
+}
+
+fn fsqrt(a: &BigInt) -> BigInt {
+    (a * unit()).sqrt()
+}
+
+/// `atanh z` for `|z| < 1`, by its Taylor series.
+fn atanh_series(z: &BigInt) -> BigInt {
+    let z2 = fmul(z, z);
+    let mut power = z.clone();
+    let mut sum = BigInt::zero();
+    let mut n = 1u32;
+    while !power.is_zero() {
+        sum += div_round(&power, &BigInt::from(n));
+        power = fmul(&power, &z2);
+        n += 2;
+    }
+    sum
+}
+
+/// `atan z` for `|z| < 1`, by its Taylor series.
+fn atan_series(z: &BigInt) -> BigInt {
+    let z2 = fmul(z, z);
+    let mut power = z.clone();
+    let mut sum = BigInt::zero();
+    let mut n = 1u32;
+    while !power.is_zero() {
+        let term = div_round(&power, &BigInt::from(n));
+        if n % 4 == 1 {
+            sum += term;
+        } else {
+            sum -= term;
+        }
+        power = fmul(&power, &z2);
+        n += 2;
+    }
+    sum
+}
+
+fn pi() -> &'static BigInt {
+    static PI: OnceLock<BigInt> = OnceLock::new();
+    // Machin's formula.
+    PI.get_or_init(|| {
+        let one = unit();
+        atan_series(&(&one / 5u32)) * 16u32 - atan_series(&(&one / 239u32)) * 4u32
+    })
+}
+
+fn ln2() -> &'static BigInt {
+    static LN2: OnceLock<BigInt> = OnceLock::new();
+    LN2.get_or_init(|| atanh_series(&(unit() / 3u32)) * 2u32)
+}
+
+fn ln10() -> &'static BigInt {
+    static LN10: OnceLock<BigInt> = OnceLock::new();
+    LN10.get_or_init(|| ln_reduced(unit() * 10u32))
+}
+
+/// `ln m` for a fixed-point `m` not far from 1: halve or double it into
+/// `[1/2, 1]`, then `ln m = 2 atanh((m - 1) / (m + 1))`.
+fn ln_reduced(mut m: BigInt) -> BigInt {
+    let one = unit();
+    let mut halvings = 0i64;
+    while m > one {
+        m = div_round(&m, &BigInt::from(2u32));
+        halvings += 1;
+    }
+    while &m * 2u32 < one {
+        m *= 2u32;
+        halvings -= 1;
+    }
+    let z = fdiv(&(&m - &one), &(&m + &one));
+    atanh_series(&z) * 2u32 + ln2() * halvings
+}
+
+/// `e^x` for a fixed-point `x`: halve `x` below 1/2, sum the series, then
+/// square the sum back.
+fn exp_fixed(x: &BigInt) -> Result<BigInt> {
+    let one = unit();
+    if *x > &one * MAX_EXP {
+        return Err(CalcError::TooLarge);
+    }
+    if *x < -(&one * MIN_EXP) {
+        return Ok(BigInt::zero());
+    }
+    let half = &one / 2u32;
+    let mut x = x.clone();
+    let mut halvings = 0;
+    while x.abs() > half {
+        x = div_round(&x, &BigInt::from(2u32));
+        halvings += 1;
+    }
+    let mut sum = one.clone();
+    let mut term = one.clone();
+    let mut n = 1u32;
+    loop {
+        term = div_round(&(&term * &x), &(&one * n));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+        n += 1;
+    }
+    for _ in 0..halvings {
+        sum = fmul(&sum, &sum);
+    }
+    Ok(sum)
+}
+
+/// `x` moved into `[-pi, pi]`.
+fn reduce_angle(x: &BigInt) -> BigInt {
+    let two_pi = pi() * 2u32;
+    x - div_round(x, &two_pi) * two_pi
+}
+
+fn sin_fixed(x: &BigInt) -> BigInt {
+    let x = reduce_angle(x);
+    let x2 = fmul(&x, &x);
+    let mut term = x.clone();
+    let mut sum = x;
+    let mut n = 1u32;
+    while !term.is_zero() {
+        term = -div_round(&(&term * &x2), &(unit() * ((2 * n) * (2 * n + 1))));
+        sum += &term;
+        n += 1;
+    }
+    sum
+}
+
+fn cos_fixed(x: &BigInt) -> BigInt {
+    let x = reduce_angle(x);
+    let x2 = fmul(&x, &x);
+    let mut term = unit();
+    let mut sum = term.clone();
+    let mut n = 1u32;
+    while !term.is_zero() {
+        term = -div_round(&(&term * &x2), &(unit() * ((2 * n - 1) * (2 * n))));
+        sum += &term;
+        n += 1;
+    }
+    sum
+}
+
+/// `atan x`: reflect `|x| > 1` through `pi/2 - atan(1/x)`, and halve the
+/// angle twice with `atan x = 2 atan(x / (1 + sqrt(1 + x^2)))` so the series
+/// converges quickly.
+fn atan_fixed(x: &BigInt) -> BigInt {
+    let one = unit();
+    if x.abs() > one {
+        let reflected = pi() / 2u32 - atan_fixed(&fdiv(&one, &x.abs()));
+        return if x.is_negative() {
+            -reflected
+        } else {
+            reflected
+        };
+    }
+    let mut x = x.clone();
+    for _ in 0..2 {
+        x = fdiv(&x, &(&one + fsqrt(&(&one + fmul(&x, &x)))));
+    }
+    atan_series(&x) * 4u32
+}
+
+fn asin_fixed(x: &BigInt) -> Result<BigInt> {
+    let one = unit();
+    match x.abs().cmp(&one) {
+        std::cmp::Ordering::Greater => Err(CalcError::Domain("asin")),
+        std::cmp::Ordering::Equal => Ok(pi() / 2u32 * x.signum()),
+        std::cmp::Ordering::Less => Ok(atan_fixed(&fdiv(x, &fsqrt(&(&one - fmul(x, x)))))),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    /// The value times `10^scale`.
+    digits: BigInt,
+    scale: u32,
+}
+
+impl Decimal {
+    /// `digits / 10^scale`, without trailing fractional zeros.
+    fn new(mut digits: BigInt, mut scale: u32) -> Self {
+        while scale > 0 && (&digits % 10u32).is_zero() {
+            digits /= 10u32;
+            scale -= 1;
+        }
+        if digits.is_zero() {
+            scale = 0;
+        }
+        Self { digits, scale }
+    }
+
+    pub fn integer(n: impl Into<BigInt>) -> Self {
+        Self::new(n.into(), 0)
+    }
+
+    /// `digits * 10^exponent`, rounded to [`PLACES`].
+    fn scaled(digits: BigInt, exponent: i64) -> Result<Self> {
+        if exponent >= 0 {
+            let exponent = u32::try_from(exponent)
+                .ok()
+                .filter(|e| i64::from(*e) <= MAX_DIGITS)
+                .ok_or(CalcError::TooLarge)?;
+            return Ok(Self::new(digits * ten_pow(exponent), 0));
+        }
+        let scale = exponent.unsigned_abs();
+        // Far below the last kept place; spare working it out.
+        if scale > u64::from(PLACES) + digits.bits() {
+            return Ok(Self::integer(0));
+        }
+        Ok(Self::new(digits, scale as u32).rounded(PLACES))
+    }
+
+    /// Parse a decimal literal such as `12`, `.5` or `1.5e-3`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let malformed = || CalcError::Syntax(format!("malformed number `{text}`"));
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(i) => (
+                &text[..i],
+                text[i + 1..].parse::<i64>().map_err(|_| malformed())?,
+            ),
+            None => (text, 0),
+        };
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let all_digits = int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit());
+        if !all_digits || int.len() + frac.len() == 0 {
+            return Err(malformed());
+        }
+        let digits: BigInt = format!("{int}{frac}").parse().map_err(|_| malformed())?;
+        Self::scaled(digits, exponent.saturating_sub(frac.len() as i64))
+    }
+
+    /// Parse an integer in base `radix`.
+    pub fn parse_radix(digits: &str, radix: u32) -> Option<Self> {
+        BigInt::parse_bytes(digits.as_bytes(), radix).map(Self::integer)
+    }
+
+    pub fn pi() -> Self {
+        Self::from_fixed(pi().clone())
+    }
+
+    pub fn e() -> Self {
+        Self::from_fixed(exp_fixed(&unit()).expect("e is small"))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.digits.is_negative()
+    }
+
+    fn to_integer(&self) -> Option<BigInt> {
+        (self.scale == 0).then(|| self.digits.clone())
+    }
+
+    /// Roughly `log10 |self|`, for range checks and argument reduction.
+    fn magnitude(&self) -> i64 {
+        (self.digits.bits() as f64 * std::f64::consts::LOG10_2) as i64 - i64::from(self.scale)
+    }
+
+    /// The value, refused when it has too many digits.
+    fn checked(self) -> Result<Self> {
+        if self.magnitude() > MAX_DIGITS {
+            return Err(CalcError::TooLarge);
+        }
+        Ok(self)
+    }
+
+    /// `self / 10^places`, exactly.
+    fn shifted(&self, places: i64) -> Self {
+        let shift = places.unsigned_abs() as u32;
+        if places >= 0 {
+            Self::new(self.digits.clone(), self.scale + shift)
+        } else if self.scale >= shift {
+            Self::new(self.digits.clone(), self.scale - shift)
+        } else {
+            Self::new(&self.digits * ten_pow(shift - self.scale), 0)
+        }
+    }
+
+    /// The digits at `scale`, rounded when that drops some.
+    fn at_scale(&self, scale: u32) -> BigInt {
+        if scale >= self.scale {
+            &self.digits * ten_pow(scale - self.scale)
+        } else {
+            div_round(&self.digits, &ten_pow(self.scale - scale))
+        }
+    }
+
+    fn fixed(&self) -> BigInt {
+        self.at_scale(WORK)
+    }
+
+    fn from_fixed(n: BigInt) -> Self {
+        Self::new(n, WORK).rounded(PLACES)
+    }
+
+    /// Round to `places` fractional digits, half away from zero.
+    pub fn rounded(&self, places: u32) -> Self {
+        if self.scale <= places {
+            return self.clone();
+        }
+        Self::new(self.at_scale(places), places)
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(-&self.digits, self.scale)
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let scale = self.scale.max(other.scale);
+        Self::new(self.at_scale(scale) + other.at_scale(scale), scale)
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Result<Self> {
+        Self::new(&self.digits * &other.digits, self.scale + other.scale)
+            .rounded(PLACES)
+            .checked()
+    }
+
+    pub fn div(&self, other: &Self) -> Result<Self> {
+        if other.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        let n = &self.digits * ten_pow(PLACES + other.scale);
+        let d = &other.digits * ten_pow(self.scale);
+        Self::new(div_round(&n, &d), PLACES).checked()
+    }
+
+    pub fn pow(&self, exponent: &Self) -> Result<Self> {
+        if let Some(n) = exponent.to_integer() {
+            return self.powi(&n);
+        }
+        if self.is_negative() {
+            return Err(CalcError::Domain("^"));
+        }
+        if self.is_zero() {
+            return if exponent.is_negative() {
+                Err(CalcError::DivisionByZero)
+            } else {
+                Ok(Self::integer(0))
+            };
+        }
+        let x = fmul(&exponent.fixed(), &ln_fixed(self));
+        Self::from_exp(&x, exponent.magnitude(), "^")
+    }
+
+    /// An integer power: exact while the result is of reasonable size,
+    /// through `e^(n ln x)` otherwise.
+    fn powi(&self, n: &BigInt) -> Result<Self> {
+        if n.is_zero() {
+            return Ok(Self::integer(1));
+        }
+        if self.is_zero() {
+            return if n.is_negative() {
+                Err(CalcError::DivisionByZero)
+            } else {
+                Ok(Self::integer(0))
+            };
+        }
+        let negative = self.is_negative() && n.is_odd();
+        // Both bounds matter: `0.1^n` has a one-bit mantissa but `n` digits.
+        let exact = n.abs().to_u32().filter(|e| {
+            let e = u64::from(*e);
+            (self.digits.bits() - 1).saturating_mul(e) <= MAX_EXACT_BITS
+                && u64::from(self.scale) * e <= MAX_EXACT_SCALE
+        });
+        let Some(e) = exact else {
+            let x = fmul(&(unit() * n), &ln_fixed(&self.abs()));
+            let power = Self::from_exp(&x, Self::integer(n.clone()).magnitude(), "^")?;
+            return Ok(if negative { power.neg() } else { power });
+        };
+        let power = Self::new(self.digits.pow(e), self.scale * e);
+        if n.is_negative() {
+            Self::integer(1).div(&power)
+        } else {
+            power.rounded(PLACES).checked()
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(self.digits.abs(), self.scale)
+    }
+
+    pub fn floor(&self) -> Self {
+        Self::integer(self.digits.div_floor(&ten_pow(self.scale)))
+    }
+
+    pub fn ceil(&self) -> Self {
+        self.neg().floor().neg()
+    }
+
+    pub fn round(&self) -> Self {
+        self.rounded(0)
+    }
+
+    pub fn sqrt(&self) -> Result<Self> {
+        if self.is_negative() {
+            return Err(CalcError::Domain("sqrt"));
+        }
+        Ok(Self::new(self.at_scale(2 * WORK).sqrt(), WORK).rounded(PLACES))
+    }
+
+    pub fn exp(&self) -> Result<Self> {
+        Self::from_exp(&self.fixed(), 0, "exp")
+    }
+
+    /// `e^x` for a fixed-point `x`, rounded to the places that are right.
+    /// Its relative error grows with `|x|`, and with `10^spread` when `x` is
+    /// a product carrying a factor that large. Refused when even the integer
+    /// part would be wrong.
+    fn from_exp(x: &BigInt, spread: i64, function: &'static str) -> Result<Self> {
+        let value = Self::new(exp_fixed(x)?, WORK).checked()?;
+        let lost = Self::new(x.clone(), WORK).magnitude().max(spread).max(0) + 1;
+        let places = i64::from(WORK) - (value.magnitude() + 1) - lost - 1;
+        if places < 0 {
+            return Err(CalcError::Imprecise(function));
+        }
+        Ok(value.rounded(places.min(i64::from(PLACES)) as u32))
+    }
+
+    fn positive(&self, function: &'static str) -> Result<()> {
+        if self.is_negative() || self.is_zero() {
+            return Err(CalcError::Domain(function));
+        }
+        Ok(())
+    }
+
+    pub fn ln(&self) -> Result<Self> {
+        self.positive("ln")?;
+        Ok(Self::from_fixed(ln_fixed(self)))
+    }
+
+    pub fn log10(&self) -> Result<Self> {
+        self.positive("log")?;
+        Ok(Self::from_fixed(fdiv(&ln_fixed(self), ln10())))
+    }
+
+    pub fn log2(&self) -> Result<Self> {
+        self.positive("log2")?;
+        Ok(Self::from_fixed(fdiv(&ln_fixed(self), ln2())))
+    }
+
+    /// The angle in fixed point. Reducing it modulo 2 pi loses as many digits
+    /// as it has before the point, so larger ones are refused.
+    fn angle(&self, function: &'static str) -> Result<BigInt> {
+        if self.magnitude() > i64::from(GUARD) {
+            return Err(CalcError::Imprecise(function));
+        }
+        Ok(self.fixed())
+    }
+
+    pub fn sin(&self) -> Result<Self> {
+        Ok(Self::from_fixed(sin_fixed(&self.angle("sin")?)))
+    }
+
+    pub fn cos(&self) -> Result<Self> {
+        Ok(Self::from_fixed(cos_fixed(&self.angle("cos")?)))
+    }
+
+    pub fn tan(&self) -> Result<Self> {
+        let x = self.angle("tan")?;
+        let cos = cos_fixed(&x);
+        if cos.is_zero() {
+            return Err(CalcError::Domain("tan"));
+        }
+        Self::from_fixed(fdiv(&sin_fixed(&x), &cos)).checked()
+    }
+
+    pub fn asin(&self) -> Result<Self> {
+        asin_fixed(&self.fixed()).map(Self::from_fixed)
+    }
+
+    pub fn acos(&self) -> Result<Self> {
+        let asin = asin_fixed(&self.fixed()).map_err(|_| CalcError::Domain("acos"))?;
+        Ok(Self::from_fixed(pi() / 2u32 - asin))
+    }
+
+    pub fn atan(&self) -> Self {
+        Self::from_fixed(atan_fixed(&self.fixed()))
+    }
+}
+
+/// `ln x` in fixed point for a positive `x`: split off a power of ten so the
+/// series only sees a number near 1.
+fn ln_fixed(x: &Decimal) -> BigInt {
+    let places = x.magnitude();
+    ln_reduced(x.shifted(places).fixed()) + ln10() * places
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.digits.magnitude().to_string();
+        if self.scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let scale = self.scale as usize;
+        let padded = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = padded.split_at(padded.len() - scale);
+        write!(f, "{sign}{int}.{frac}")
+    }
+}
@@ -0,0 +1,227 @@
+//! The calculator: a query that is an arithmetic expression gets its value as
+//! the top result, and choosing that copies the value.
+//!
+//! Numbers are decimals of any size, so `0.1 + 0.2` is 0.3 and `2^100` is
+//! exact; see [`decimal`] for what is rounded and where.
+
+mod decimal;
+mod parse;
+
+use crate::actions::{Action, ActionItem};
+use crate::fuzzy::{Pattern, Ranges};
+use crate::icons;
+use crate::providers::{blocking, Provider, Query, QueryFuture};
+use crate::search::{ResultKind, SearchResult};
+
+/// Fractional digits an answer shows.
+const SHOWN_PLACES: u32 = 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalcError {
+    #[error("{0}")]
+    Syntax(String),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("`{0}` is undefined there")]
+    Domain(&'static str),
+    #[error("`{0}` cannot be worked out precisely for a number this large")]
+    Imprecise(&'static str),
+    #[error("the result is too large")]
+    TooLarge,
+}
+
+/// Work out `text`, if it is an expression worth answering. A number on its
+/// own is left to the other sources.
+pub fn evaluate(text: &str) -> Option<String> {
+    let expr = parse::parse(text).ok()?;
+    if expr.is_plain() {
+        return None;
+    }
+    match expr.eval() {
+        Ok(value) => Some(value.rounded(SHOWN_PLACES).to_string()),
+        Err(e) => {
+            log::debug!("cannot evaluate {text:?}: {e}");
+            None
+        }
+    }
+}
+
+/// The answer to `text` as a search result.
+pub fn answer(text: &str) -> Option<SearchResult> {
+    let value = evaluate(text)?;
+    Some(SearchResult {
+        title: value.clone(),
+        path: format!("calculator:{}", text.trim()),
+        kind: ResultKind::Answer,
+        icon: Some(icons::name_url("accessories-calculator")),
+        ranges: Ranges::new(),
+        // Answers are listed first whatever their score; this only orders
+        // them against each other.
+        score: Pattern::parse(text).max_score() as f64,
+        source: String::new(),
+        action: Some(Action::Copy { text: value }),
+    })
+}
+
+pub struct Calculator;
+
+impl Provider for Calculator {
+    fn id(&self) -> &str {
+        "calculator"
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        blocking(move || Ok(answer(&query.text).into_iter().collect()))
+    }
+
+    fn actions(&self, result: &SearchResult) -> Option<Vec<ActionItem>> {
+        let action = result.action.clone()?;
+        Some(vec![ActionItem::new(action, "Copy Result")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The value of `text` to every kept place.
+    fn eval(text: &str) -> Result<String, CalcError> {
+        Ok(parse::parse(text)?.eval()?.to_string())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("-2^2").unwrap(), "-4");
+        assert_eq!(eval("2^3^2").unwrap(), "512");
+        assert_eq!(eval("2^10 * 3.5").unwrap(), "3584");
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2").unwrap(), "5");
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), "9");
+        assert_eq!(eval("3 × 4 ÷ 2 − 1").unwrap(), "5");
+    }
+
+    #[test]
+    fn percentages() {
+        assert_eq!(eval("50 + 10%").unwrap(), "55");
+        assert_eq!(eval("200 - 25%").unwrap(), "150");
+        assert_eq!(eval("5 * 10%").unwrap(), "0.5");
+        assert_eq!(eval("10%").unwrap(), "0.1");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(eval("0xff").unwrap(), "255");
+        assert_eq!(eval("0b1010 + 0o17").unwrap(), "25");
+        assert_eq!(eval("0.1 + 0.2").unwrap(), "0.3");
+        assert_eq!(eval(".5 + 1.5e3").unwrap(), "1500.5");
+        assert_eq!(eval("2^100").unwrap(), "1267650600228229401496703205376");
+        assert!(matches!(eval("0x"), Err(CalcError::Syntax(_))));
+        assert!(matches!(eval("2e"), Err(CalcError::Syntax(_))));
+    }
+
+    #[test]
+    fn functions_to_fifty_places() {
+        let cases = [
+            ("pi", "3.14159265358979323846264338327950288419716939937511"),
+            ("e", "2.71828182845904523536028747135266249775724709369996"),
+            (
+                "ln(2)",
+                "0.69314718055994530941723212145817656807550013436026",
+            ),
+            (
+                "ln(10)",
+                "2.30258509299404568401799145468436420760110148862877",
+            ),
+            (
+                "sin(1)",
+                "0.84147098480789650665250232163029899962256306079837",
+            ),
+            (
+                "sqrt(2)",
+                "1.41421356237309504880168872420969807856967187537695",
+            ),
+            (
+                "atan(1)",
+                "0.78539816339744830961566084581987572104929234984378",
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(eval(text).unwrap(), expected, "{text}");
+        }
+        assert_eq!(eval("sin(pi)").unwrap(), "0");
+        assert_eq!(eval("log(1000)").unwrap(), "3");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(eval("1/0"), Err(CalcError::DivisionByZero)));
+        assert!(matches!(eval("0^-1"), Err(CalcError::DivisionByZero)));
+        assert!(matches!(eval("sqrt(-1)"), Err(CalcError::Domain("sqrt"))));
+        assert!(matches!(eval("ln(0)"), Err(CalcError::Domain("ln"))));
+        assert!(matches!(eval("asin(2)"), Err(CalcError::Domain("asin"))));
+        assert!(matches!(eval("(-8)^(1/3)"), Err(CalcError::Domain("^"))));
+        assert!(matches!(
+            eval("sin(1e999)"),
+            Err(CalcError::Imprecise("sin"))
+        ));
+    }
+
+    #[test]
+    fn huge_exponents() {
+        assert_eq!(eval("0.1^4000000000").unwrap(), "0");
+        assert_eq!(eval("0.01^3000000000").unwrap(), "0");
+        assert_eq!(eval("0.5^2000").unwrap(), "0");
+        assert!(matches!(eval("0.1^-4000000000"), Err(CalcError::TooLarge)));
+        assert!(matches!(eval("2^4000"), Err(CalcError::TooLarge)));
+        assert!(matches!(eval("2^2^2^2^2"), Err(CalcError::TooLarge)));
+        assert!(matches!(eval("1e1001 * 1"), Err(CalcError::TooLarge)));
+        assert_eq!(eval("2^3000").unwrap().len(), 904);
+    }
+
+    #[test]
+    fn large_inexact_powers_keep_only_right_places() {
+        assert_eq!(
+            eval("10^15.5").unwrap(),
+            "3162277660168379.3319988935444327185337195551393252168268575048"
+        );
+        assert_eq!(
+            eval("exp(40)").unwrap(),
+            "235385266837019985.40789991074903480450887161725455546723665125"
+        );
+        assert!(matches!(
+            eval("exp(2000)"),
+            Err(CalcError::Imprecise("exp"))
+        ));
+        assert!(matches!(eval("1.1^20000"), Err(CalcError::Imprecise("^"))));
+        assert!(matches!(eval("2^4000"), Err(CalcError::TooLarge)));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let deep = ["(".repeat(10_000), "-".repeat(10_000), "2^".repeat(10_000)];
+        for prefix in deep {
+            assert!(matches!(
+                eval(&format!("{prefix}1")),
+                Err(CalcError::Syntax(_))
+            ));
+        }
+        let long = format!("{}1", "1+".repeat(10_000));
+        assert!(matches!(eval(&long), Err(CalcError::Syntax(_))));
+        assert_eq!(eval(&format!("{}1)", "(".repeat(50))).unwrap(), "1");
+    }
+
+    #[test]
+    fn answers() {
+        assert!(answer("42").is_none());
+        assert!(answer("pi").is_none());
+        assert!(answer("downloads").is_none());
+        let result = answer("1/3").unwrap();
+        assert_eq!(result.title, "0.33333333333333333333");
+        assert_eq!(result.kind, ResultKind::Answer);
+        assert_eq!(
+            result.action,
+            Some(Action::Copy {
+                text: "0.33333333333333333333".into()
+            })
+        );
+    }
+}
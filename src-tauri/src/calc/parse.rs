@@ -0,0 +1,365 @@
+//! Expressions: numbers, `+ - * / ^`, percentages, parentheses, constants and
+//! one-argument functions, parsed by precedence climbing.
+//!
+//! `^` binds tighter than unary minus and groups to the right, so `-2^2` is
+//! -4 and `2^3^2` is 512. A percentage added to or subtracted from something
+//! is a percentage of it: `50 + 10%` is 55. Anywhere else `x%` is `x / 100`.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::decimal::Decimal;
+use super::CalcError;
+
+type Result<T> = std::result::Result<T, CalcError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    /// Digits of a number and their base; base 10 may have a fraction and
+    /// an exponent.
+    Number(&'a str, u32),
+    Name(&'a str),
+    Op(char),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Function {
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Exp,
+    Ln,
+    Log,
+    Log2,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+}
+
+const FUNCTIONS: &[(&str, Function)] = &[
+    ("sqrt", Function::Sqrt),
+    ("abs", Function::Abs),
+    ("floor", Function::Floor),
+    ("ceil", Function::Ceil),
+    ("round", Function::Round),
+    ("exp", Function::Exp),
+    ("ln", Function::Ln),
+    ("log", Function::Log),
+    ("log2", Function::Log2),
+    ("sin", Function::Sin),
+    ("cos", Function::Cos),
+    ("tan", Function::Tan),
+    ("asin", Function::Asin),
+    ("acos", Function::Acos),
+    ("atan", Function::Atan),
+];
+
+impl Function {
+    fn apply(self, x: &Decimal) -> Result<Decimal> {
+        match self {
+            Self::Sqrt => x.sqrt(),
+            Self::Abs => Ok(x.abs()),
+            Self::Floor => Ok(x.floor()),
+            Self::Ceil => Ok(x.ceil()),
+            Self::Round => Ok(x.round()),
+            Self::Exp => x.exp(),
+            Self::Ln => x.ln(),
+            Self::Log => x.log10(),
+            Self::Log2 => x.log2(),
+            Self::Sin => x.sin(),
+            Self::Cos => x.cos(),
+            Self::Tan => x.tan(),
+            Self::Asin => x.asin(),
+            Self::Acos => x.acos(),
+            Self::Atan => Ok(x.atan()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// `plain` for a decimal literal or a named constant, which on its own is
+    /// not worth an answer.
+    Number {
+        value: Decimal,
+        plain: bool,
+    },
+    Neg(Box<Expr>),
+    Percent(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    /// Whether the expression is just a number as typed.
+    pub fn is_plain(&self) -> bool {
+        matches!(self, Self::Number { plain: true, .. })
+    }
+
+    pub fn eval(&self) -> Result<Decimal> {
+        match self {
+            Self::Number { value, .. } => Ok(value.clone()),
+            Self::Neg(x) => Ok(x.eval()?.neg()),
+            Self::Percent(x) => x.eval()?.div(&Decimal::integer(100)),
+            Self::Binary(op, a, b) => {
+                let a = a.eval()?;
+                if let (op @ ('+' | '-'), Self::Percent(p)) = (*op, b.as_ref()) {
+                    let change = a.mul(&p.eval()?)?.div(&Decimal::integer(100))?;
+                    return Ok(if op == '+' {
+                        a.add(&change)
+                    } else {
+                        a.sub(&change)
+                    });
+                }
+                let b = b.eval()?;
+                match op {
+                    '+' => Ok(a.add(&b)),
+                    '-' => Ok(a.sub(&b)),
+                    '*' => a.mul(&b),
+                    '/' => a.div(&b),
+                    _ => a.pow(&b),
+                }
+            }
+            Self::Call(function, x) => function.apply(&x.eval()?),
+        }
+    }
+}
+
+/// Deepest nesting of parentheses, signs, function calls and `^` accepted.
+const MAX_DEPTH: usize = 100;
+/// Longest expression accepted, in tokens. Evaluating and dropping an
+/// expression recurse once per operator, so this bounds their depth too.
+const MAX_TOKENS: usize = 500;
+
+/// Binding power of unary minus, between `*` and `^`.
+const PREFIX: u8 = 5;
+/// Binding power of a trailing `%`.
+const POSTFIX: u8 = 9;
+
+/// Left and right binding power of an infix operator.
+fn infix(op: char) -> Option<(u8, u8)> {
+    match op {
+        '+' | '-' => Some((1, 2)),
+        '*' | '/' => Some((3, 4)),
+        '^' => Some((8, 7)),
+        _ => None,
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expr> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err(CalcError::Syntax("nothing to calculate".into()));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.expr(0)?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(token) => Err(unexpected(token)),
+    }
+}
+
+fn unexpected(token: Token) -> CalcError {
+    CalcError::Syntax(match token {
+        Token::Number(digits, _) => format!("unexpected number `{digits}`"),
+        Token::Name(name) => format!("unexpected `{name}`"),
+        Token::Op(op) => format!("unexpected `{op}`"),
+        Token::Open => "unexpected `(`".into(),
+        Token::Close => "unexpected `)`".into(),
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    /// Calls to `expr` in progress.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn expr(&mut self, min: u8) -> Result<Expr> {
+        if self.depth == MAX_DEPTH {
+            return Err(CalcError::Syntax("nested too deeply".into()));
+        }
+        self.depth += 1;
+        let expr = self.climb(min);
+        self.depth -= 1;
+        expr
+    }
+
+    fn climb(&mut self, min: u8) -> Result<Expr> {
+        let mut lhs = self.operand()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            if op == '%' {
+                if POSTFIX < min {
+                    break;
+                }
+                self.pos += 1;
+                lhs = Expr::Percent(Box::new(lhs));
+                continue;
+            }
+            let Some((left, right)) = infix(op) else {
+                break;
+            };
+            if left < min {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(right)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn operand(&mut self) -> Result<Expr> {
+        let Some(token) = self.next() else {
+            return Err(CalcError::Syntax("unexpected end".into()));
+        };
+        match token {
+            Token::Number(digits, 10) => Ok(Expr::Number {
+                value: Decimal::parse(digits)?,
+                plain: true,
+            }),
+            Token::Number(digits, radix) => Ok(Expr::Number {
+                value: Decimal::parse_radix(digits, radix)
+                    .ok_or_else(|| CalcError::Syntax(format!("malformed number `{digits}`")))?,
+                plain: false,
+            }),
+            Token::Name(name) => self.name(name),
+            Token::Open => self.group(),
+            Token::Op('-') => Ok(Expr::Neg(Box::new(self.expr(PREFIX)?))),
+            Token::Op('+') => self.expr(PREFIX),
+            token => Err(unexpected(token)),
+        }
+    }
+
+    fn name(&mut self, name: &str) -> Result<Expr> {
+        let lower = name.to_lowercase();
+        let constant = match lower.as_str() {
+            "pi" | "π" => Some(Decimal::pi()),
+            "e" => Some(Decimal::e()),
+            _ => None,
+        };
+        if let Some(value) = constant {
+            return Ok(Expr::Number { value, plain: true });
+        }
+        let function = FUNCTIONS
+            .iter()
+            .find(|(known, _)| *known == lower)
+            .map(|(_, function)| *function)
+            .ok_or_else(|| CalcError::Syntax(format!("unknown name `{name}`")))?;
+        match self.next() {
+            Some(Token::Open) => Ok(Expr::Call(function, Box::new(self.group()?))),
+            _ => Err(CalcError::Syntax(format!("`{name}` needs parentheses"))),
+        }
+    }
+
+    /// The rest of a parenthesized expression. A missing `)` at the end is
+    /// fine, so answers show up while the expression is still being typed.
+    fn group(&mut self) -> Result<Expr> {
+        let inner = self.expr(0)?;
+        match self.next() {
+            Some(Token::Close) | None => Ok(inner),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => number(text, start, &mut chars),
+            c if c.is_alphabetic() => {
+                let end = take_while(text, &mut chars, char::is_alphanumeric);
+                Token::Name(&text[start..end])
+            }
+            '*' => {
+                chars.next();
+                if chars.next_if(|&(_, c)| c == '*').is_some() {
+                    Token::Op('^')
+                } else {
+                    Token::Op('*')
+                }
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    '+' | '-' | '/' | '^' | '%' => Token::Op(c),
+                    '−' => Token::Op('-'),
+                    '×' | '·' => Token::Op('*'),
+                    '÷' => Token::Op('/'),
+                    _ => return Err(CalcError::Syntax(format!("unexpected `{c}`"))),
+                }
+            }
+        };
+        if tokens.len() == MAX_TOKENS {
+            return Err(CalcError::Syntax("too long to calculate".into()));
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Consume characters while `f` holds; returns the byte offset after them.
+fn take_while(text: &str, chars: &mut Peekable<CharIndices>, f: impl Fn(char) -> bool) -> usize {
+    while chars.next_if(|&(_, c)| f(c)).is_some() {}
+    chars.peek().map_or(text.len(), |&(i, _)| i)
+}
+
+/// A number starting at `start`: `0x`, `0b` or `0o` and digits in that base,
+/// or decimal digits with an optional fraction and exponent.
+fn number<'a>(text: &'a str, start: usize, chars: &mut Peekable<CharIndices>) -> Token<'a> {
+    let rest = &text[start..];
+    let radix = match rest.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => Some(16),
+        Some("0b") => Some(2),
+        Some("0o") => Some(8),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        chars.nth(1);
+        let end = take_while(text, chars, |c| c.is_digit(radix));
+        return Token::Number(&text[start + 2..end], radix);
+    }
+    let mut end = take_while(text, chars, |c| c.is_ascii_digit() || c == '.');
+    // An exponent only if digits follow, so `2e` stays `2` and then `e`.
+    let exponent = text[end..]
+        .strip_prefix(['e', 'E'])
+        .map(|after| after.strip_prefix(['+', '-']).unwrap_or(after))
+        .is_some_and(|digits| digits.starts_with(|c: char| c.is_ascii_digit()));
+    if exponent {
+        chars.next();
+        chars.next_if(|&(_, c)| c == '+' || c == '-');
+        end = take_while(text, chars, |c| c.is_ascii_digit());
+    }
+    Token::Number(&text[start..end], 10)
+}
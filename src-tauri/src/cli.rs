@@ -0,0 +1,409 @@
+//! The command line. Subcommands are sent to the running launcher when there
+//! is one; otherwise `search`, `reindex` and `status` run without a window,
+//! and the rest start the launcher.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Config, Manager, State};
+
+use crate::apps::{self, Apps};
+use crate::error::Result;
+use crate::history::History;
+use crate::icons::Icons;
+use crate::index::{IndexStatus, Indexer};
+use crate::mime::MimeDb;
+use crate::providers::Providers;
+use crate::search::{self, SearchResult};
+use crate::settings::SettingsStore;
+use crate::window;
+
+/// Passed by the autostart entry: start in the tray, and leave an already
+/// running launcher alone instead of showing it.
+pub const HIDDEN_FLAG: &str = "--hidden";
+
+/// Event carrying a query for the search box.
+pub const QUERY_EVENT: &str = "query";
+
+#[derive(Debug, Parser)]
+#[command(name = "quicklauncher", version, about = "Quick Launcher")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Show the launcher window; same as `show`.
+    #[arg(long)]
+    pub show: bool,
+    /// Rebuild the file index; same as `reindex`.
+    #[arg(long)]
+    pub reindex: bool,
+    /// Start in the tray without showing the launcher, and do nothing if it
+    /// is already running.
+    #[arg(long, conflicts_with = "show")]
+    pub hidden: bool,
+}
+
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Show the launcher window.
+    Show,
+    /// Hide the launcher window.
+    Hide,
+    /// Show the launcher window if it is hidden, hide it otherwise.
+    Toggle,
+    /// Show the launcher with the search box filled in.
+    Query {
+        #[arg(required = true)]
+        text: Vec<String>,
+    },
+    /// Print search results without showing the launcher.
+    Search {
+        #[arg(required = true)]
+        text: Vec<String>,
+        /// Print the results as a JSON array.
+        #[arg(long)]
+        json: bool,
+        /// Maximum number of results.
+        #[arg(long, default_value_t = search::DEFAULT_LIMIT)]
+        limit: usize,
+    },
+    /// Rebuild the file index, or only the part at and below `path`.
+    Reindex { path: Option<PathBuf> },
+    /// Print whether the launcher is running and the state of the index.
+    Status {
+        /// Print the status as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl Cli {
+    /// The commands to run, in order; empty for a plain start.
+    pub fn commands(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        if self.reindex {
+            commands.push(Command::Reindex { path: None });
+        }
+        if self.show {
+            commands.push(Command::Show);
+        }
+        commands.extend(self.command.clone().map(|command| match command {
+            // The running launcher has its own working directory.
+            Command::Reindex { path: Some(path) } => Command::Reindex {
+                path: Some(std::path::absolute(&path).unwrap_or(path)),
+            },
+            command => command,
+        }));
+        commands
+    }
+}
+
+impl Command {
+    /// Whether the command needs the launcher window, and so starts the
+    /// launcher when it is not running.
+    pub fn needs_window(&self) -> bool {
+        matches!(self, Self::Show | Self::Toggle | Self::Query { .. })
+    }
+}
+
+/// What a command produced, sent back to the invoking process for printing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum Reply {
+    Done,
+    Results { results: Vec<SearchResult> },
+    Status(Status),
+    Error { message: String },
+}
+
+impl From<Result<Reply>> for Reply {
+    fn from(result: Result<Reply>) -> Self {
+        result.unwrap_or_else(|e| Self::Error {
+            message: e.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Status {
+    pub running: bool,
+    /// `None` when nothing has been indexed yet.
+    pub index: Option<IndexStatus>,
+}
+
+/// Managed state holding a query passed on the command line at startup. The
+/// page is not loaded yet when startup commands run, so it asks for the query
+/// once it is listening instead of being sent an event.
+#[derive(Default)]
+pub struct PendingQuery(Mutex<Option<String>>);
+
+/// Run `command` in the running launcher.
+pub fn execute(app: &AppHandle, command: &Command) -> Reply {
+    match command {
+        Command::Show => window::show(app),
+        Command::Hide => window::dismiss(app),
+        Command::Toggle => window::toggle(app),
+        Command::Query { text } => {
+            let query = text.join(" ");
+            *app.state::<PendingQuery>().0.lock().unwrap() = Some(query.clone());
+            if let Err(e) = app.emit_to(window::MAIN_WINDOW, QUERY_EVENT, query) {
+                log::warn!("could not forward the query: {e}");
+            }
+            window::show(app);
+        }
+        Command::Search { text, limit, .. } => {
+            // The same providers as the search box, plugins included.
+            let results = tauri::async_runtime::block_on(search::search_providers(
+                &app.state::<Providers>(),
+                &app.state::<SettingsStore>(),
+                text.join(" "),
+                *limit,
+            ));
+            return Reply::Results { results };
+        }
+        Command::Reindex { path } => {
+            let indexer = app.state::<Indexer>();
+            match path {
+                // A subtree is quick, so the reply waits for it; a full
+                // build runs in the background.
+                Some(path) if !indexer.roots().contains(path) => {
+                    return indexer.refresh(path).map(|()| Reply::Done).into()
+                }
+                _ => indexer.rebuild(),
+            }
+        }
+        Command::Status { .. } => {
+            return Reply::Status(Status {
+                running: true,
+                index: app.try_state::<Indexer>().map(|i| i.status()),
+            })
+        }
+    }
+    Reply::Done
+}
+
+/// Run `command` without a running launcher. Commands that need the window
+/// are not handled here; `Hide` has nothing to do.
+pub fn execute_headless(config: &Config, command: &Command) -> Reply {
+    headless(config, command).into()
+}
+
+fn headless(config: &Config, command: &Command) -> Result<Reply> {
+    let dirs = Dirs::resolve(config)?;
+    let settings = SettingsStore::load(&dirs.config);
+    let index_settings = settings.get().index.clone();
+    let indexer = Indexer::load(&dirs.data, index_settings.roots, index_settings.ignore);
+    match command {
+        Command::Search { text, limit, .. } => {
+            if !indexer.is_built() {
+                log::warn!("no file index yet; run `reindex` to build one");
+            }
+            let history_settings = settings.get().history.clone();
+            let icon_settings = settings.get().icons.clone();
+            let mime = MimeDb::load(&apps::data_dirs());
+            let results = search::search_blocking(
+                &indexer.read(),
+                &Apps::load(),
+                &History::open(&dirs.data, history_settings),
+                &Icons::new(dirs.cache, &icon_settings, mime),
+                &text.join(" "),
+                *limit,
+            );
+            Ok(Reply::Results { results })
+        }
+        Command::Reindex { path } => {
+            match path {
+                // Without an index to update, build the whole thing.
+                Some(path) if indexer.is_built() => {
+                    indexer.refresh(path)?;
+                    indexer.persist();
+                }
+                _ => indexer.rebuild_blocking(),
+            }
+            Ok(Reply::Done)
+        }
+        Command::Status { .. } => Ok(Reply::Status(Status {
+            running: false,
+            index: indexer.is_built().then(|| indexer.status()),
+        })),
+        Command::Show | Command::Hide | Command::Toggle | Command::Query { .. } => Ok(Reply::Done),
+    }
+}
+
+/// The app's directories, resolved the way `PathResolver` does.
+struct Dirs {
+    config: PathBuf,
+    data: PathBuf,
+    cache: PathBuf,
+}
+
+impl Dirs {
+    fn resolve(config: &Config) -> Result<Self> {
+        use tauri::api::path;
+        let missing = || std::io::Error::other("could not resolve the app directories");
+        Ok(Self {
+            config: path::app_config_dir(config).ok_or_else(missing)?,
+            data: path::app_data_dir(config).ok_or_else(missing)?,
+            cache: path::app_cache_dir(config).ok_or_else(missing)?,
+        })
+    }
+}
+
+/// Run `commands` in the running launcher, or here when it is not running,
+/// and print the replies. Returns the process exit code.
+pub fn dispatch(config: &Config, commands: &[Command]) -> i32 {
+    let mut code = 0;
+    for command in commands {
+        let reply = match forward(command) {
+            Ok(Some(reply)) => reply,
+            Ok(None) => execute_headless(config, command),
+            Err(e) => Reply::Error {
+                message: format!("could not reach the launcher: {e}"),
+            },
+        };
+        if !print(command, &reply) {
+            code = 1;
+        }
+    }
+    code
+}
+
+#[cfg(unix)]
+fn forward(command: &Command) -> std::io::Result<Option<Reply>> {
+    crate::instance::request(command)
+}
+
+/// Without the single-instance socket every command runs in its own process.
+#[cfg(not(unix))]
+fn forward(_command: &Command) -> std::io::Result<Option<Reply>> {
+    Ok(None)
+}
+
+/// Print a reply for `command`. Returns `false` for an error.
+pub fn print(command: &Command, reply: &Reply) -> bool {
+    let json = matches!(
+        command,
+        Command::Search { json: true, .. } | Command::Status { json: true }
+    );
+    match reply {
+        Reply::Done => {}
+        Reply::Results { results } if json => print_json(results),
+        Reply::Results { results } => {
+            for result in results {
+                println!("{}\t{}", result.title, result.path);
+            }
+        }
+        Reply::Status(status) if json => print_json(status),
+        Reply::Status(status) => print_status(status),
+        Reply::Error { message } => {
+            eprintln!("quicklauncher: {message}");
+            return false;
+        }
+    }
+    true
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{text}"),
+        Err(e) => eprintln!("quicklauncher: {e}"),
+    }
+}
+
+fn print_status(status: &Status) {
+    println!(
+        "launcher: {}",
+        if status.running {
+            "running"
+        } else {
+            "not running"
+        }
+    );
+    let Some(index) = &status.index else {
+        println!("index: not built");
+        return;
+    };
+    println!("indexed entries: {}", index.entries);
+    for root in &index.roots {
+        println!("root: {}", root.display());
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("last built: {}s ago", now.saturating_sub(index.built_at));
+    if index.building {
+        println!("rebuilding now");
+    }
+    if status.running {
+        println!("watching: {}", if index.watching { "yes" } else { "no" });
+    }
+}
+
+/// The query passed on the command line that the page has not picked up yet.
+#[tauri::command]
+pub fn take_query(pending: State<'_, PendingQuery>) -> Option<String> {
+    pending.0.lock().unwrap().take()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Vec<Command> {
+        Cli::try_parse_from([&["quicklauncher"], args].concat())
+            .unwrap()
+            .commands()
+    }
+
+    #[test]
+    fn definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn flags_run_before_the_subcommand() {
+        let commands = parse(&["--reindex", "--show", "status"]);
+        assert!(matches!(
+            commands[..],
+            [
+                Command::Reindex { path: None },
+                Command::Show,
+                Command::Status { json: false }
+            ]
+        ));
+        assert!(parse(&[]).is_empty());
+        assert!(Cli::try_parse_from(["quicklauncher", "--show", "--hidden"]).is_err());
+    }
+
+    #[test]
+    fn reindex_paths_are_made_absolute() {
+        let [Command::Reindex { path: Some(path) }] = &parse(&["reindex", "docs"])[..] else {
+            panic!("expected one reindex");
+        };
+        assert!(path.is_absolute());
+        assert!(path.ends_with("docs"));
+    }
+
+    #[test]
+    fn commands_survive_the_socket() {
+        let [search] = &parse(&["search", "--json", "--limit", "5", "tax", "2024"])[..] else {
+            panic!("expected one search");
+        };
+        let line = serde_json::to_string(search).unwrap();
+        let Command::Search { text, json, limit } = serde_json::from_str(&line).unwrap() else {
+            panic!("expected a search in {line}");
+        };
+        assert_eq!(
+            (text.join(" "), json, limit),
+            ("tax 2024".to_string(), true, 5)
+        );
+        assert!(!search.needs_window());
+        assert!(parse(&["query", "tax"])[0].needs_window());
+    }
+}
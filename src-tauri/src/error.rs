@@ -0,0 +1,46 @@
+use serde::{Serialize, Serializer};
+
+/// Errors surfaced by the Tauri shell. Commands return these directly; they
+/// serialize to their display string so the webview receives a readable message.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Tauri(#[from] tauri::Error),
+    #[error("invalid accelerator `{0}`: {1}")]
+    InvalidAccelerator(String, String),
+    #[error("failed to register shortcut `{0}`: {1}")]
+    Shortcut(String, String),
+    #[error("refusing to launch {}: {reason}", path.display())]
+    LaunchDenied {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    #[error("cannot launch application `{0}`: {1}")]
+    App(String, String),
+    #[error("action failed: {0}")]
+    Action(String),
+    #[error("trash: {0}")]
+    Trash(String),
+    #[error("autostart: {0}")]
+    Autostart(String),
+    #[error("plugin `{0}`: {1}")]
+    Plugin(String, String),
+    #[error("{} is not inside an indexed root", .0.display())]
+    NotIndexed(std::path::PathBuf),
+    #[error("background task failed: {0}")]
+    Task(String),
+    #[error("search provider failed: {0}")]
+    Provider(String),
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
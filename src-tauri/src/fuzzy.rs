@@ -0,0 +1,425 @@
+//! Fuzzy subsequence matching in the spirit of fzf's v2 algorithm.
+//!
+//! Every pattern character must appear in the candidate in order. Among all
+//! such alignments the scorer picks the best one, rewarding matches at word
+//! boundaries, on camelCase humps and in contiguous runs, and penalizing gaps.
+//! Matching is smart-case: a pattern containing an uppercase letter is matched
+//! case-sensitively.
+
+use std::cell::RefCell;
+
+use memchr::memchr;
+use serde::Serialize;
+
+const SCORE_MATCH: i32 = 16;
+const GAP_START: i32 = -3;
+const GAP_EXTENSION: i32 = -1;
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+const BONUS_CAMEL: i32 = BONUS_BOUNDARY - 1;
+const BONUS_CONSECUTIVE: i32 = -(GAP_START + GAP_EXTENSION);
+/// The first pattern character's bonus counts this many times, so anchoring
+/// the start of a query to a word start dominates.
+const BONUS_FIRST_MULTIPLIER: i32 = 2;
+const NONE: i32 = i32::MIN / 2;
+
+/// Matched characters as `[start, end)` ranges of `char` indices, ready for
+/// the webview to wrap in highlight markup.
+pub type Ranges = Vec<[usize; 2]>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    pub score: i64,
+    pub ranges: Ranges,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Delimiter,
+    Lower,
+    Upper,
+    Digit,
+    Other,
+}
+
+fn class(c: char) -> Class {
+    if c.is_ascii() {
+        return match c as u8 {
+            b'a'..=b'z' => Class::Lower,
+            b'A'..=b'Z' => Class::Upper,
+            b'0'..=b'9' => Class::Digit,
+            _ => Class::Delimiter,
+        };
+    }
+    if c.is_lowercase() {
+        Class::Lower
+    } else if c.is_uppercase() {
+        Class::Upper
+    } else if c.is_numeric() {
+        Class::Digit
+    } else if c.is_alphabetic() {
+        Class::Other
+    } else {
+        Class::Delimiter
+    }
+}
+
+fn bonus(prev: Class, cur: Class) -> i32 {
+    match (prev, cur) {
+        (_, Class::Delimiter) => 0,
+        (Class::Delimiter, _) => BONUS_BOUNDARY,
+        (Class::Lower, Class::Upper) => BONUS_CAMEL,
+        (Class::Lower | Class::Upper | Class::Other, Class::Digit) => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+#[derive(Default)]
+struct Scratch {
+    text: Vec<char>,
+    bonus: Vec<i32>,
+    /// Row-major `terms x text` score matrix; only two rows are needed for
+    /// scoring, the full matrix for recovering positions.
+    h: Vec<i32>,
+    /// Whether the best score at a cell continued a contiguous run.
+    consecutive: Vec<bool>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// A parsed query: whitespace-separated terms, each matched independently.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    terms: Vec<Vec<char>>,
+    /// The ASCII characters of each term, lowercased, for the cheap
+    /// subsequence prefilter.
+    folded: Vec<Vec<u8>>,
+    case_sensitive: bool,
+}
+
+impl Pattern {
+    pub fn parse(query: &str) -> Self {
+        let case_sensitive = query.chars().any(char::is_uppercase);
+        let terms: Vec<Vec<char>> = query
+            .split_whitespace()
+            .map(|term| term.chars().map(|c| fold(c, case_sensitive)).collect())
+            .collect();
+        let folded = query
+            .split_whitespace()
+            .map(|term| {
+                term.bytes()
+                    .filter(u8::is_ascii)
+                    .map(|b| b.to_ascii_lowercase())
+                    .collect()
+            })
+            .collect();
+        Self {
+            terms,
+            folded,
+            case_sensitive,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Upper bound on [`score`](Self::score) for any candidate: every character
+    /// matched contiguously and on a word boundary.
+    pub fn max_score(&self) -> i64 {
+        self.terms
+            .iter()
+            .map(|term| {
+                let rest = term.len() as i32 - 1;
+                let first = SCORE_MATCH + BONUS_BOUNDARY * BONUS_FIRST_MULTIPLIER;
+                (first + rest * (SCORE_MATCH + BONUS_BOUNDARY + BONUS_CONSECUTIVE)) as i64
+            })
+            .sum()
+    }
+
+    /// Quick rejection: does every term occur as a byte subsequence of
+    /// `candidate`, which must already be ASCII-lowercased? Never rejects a
+    /// candidate that [`score`](Self::score) would accept.
+    pub fn prefilter(&self, candidate: &[u8]) -> bool {
+        self.folded.iter().all(|term| {
+            let mut rest = candidate;
+            term.iter().all(|&b| match memchr(b, rest) {
+                Some(i) => {
+                    rest = &rest[i + 1..];
+                    true
+                }
+                None => false,
+            })
+        })
+    }
+
+    /// Best alignment score summed over all terms, or `None` if any term does
+    /// not match.
+    pub fn score(&self, candidate: &str) -> Option<i64> {
+        if self.is_empty() {
+            return None;
+        }
+        SCRATCH.with(|scratch| {
+            let scratch = &mut scratch.borrow_mut();
+            self.load(scratch, candidate);
+            self.terms
+                .iter()
+                .map(|term| align(scratch, term, false).map(|(score, _)| score as i64))
+                .sum()
+        })
+    }
+
+    /// Like [`score`](Self::score), but also recovers which characters matched.
+    pub fn positions(&self, candidate: &str) -> Option<Match> {
+        if self.is_empty() {
+            return None;
+        }
+        SCRATCH.with(|scratch| {
+            let scratch = &mut scratch.borrow_mut();
+            self.load(scratch, candidate);
+            let mut total = 0;
+            let mut positions = Vec::new();
+            for term in &self.terms {
+                let (score, matched) = align(scratch, term, true)?;
+                total += score as i64;
+                positions.extend(matched);
+            }
+            positions.sort_unstable();
+            positions.dedup();
+            Some(Match {
+                score: total,
+                ranges: to_ranges(&positions),
+            })
+        })
+    }
+
+    fn load(&self, scratch: &mut Scratch, candidate: &str) {
+        scratch.text.clear();
+        scratch.bonus.clear();
+        let mut prev = Class::Delimiter;
+        for c in candidate.chars() {
+            let cur = class(c);
+            scratch.bonus.push(bonus(prev, cur));
+            scratch.text.push(fold(c, self.case_sensitive));
+            prev = cur;
+        }
+    }
+}
+
+fn fold(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        // Never onto ASCII, as KELVIN SIGN would to `k` and `İ` to `i`: the
+        // prefilter only sees ASCII lowercasing and would reject the match.
+        match c.to_lowercase().next() {
+            Some(lower) if !lower.is_ascii() => lower,
+            _ => c,
+        }
+    }
+}
+
+/// Align `term` against the loaded text. Returns the best score and, when
+/// `trace` is set, the matched text indices.
+fn align(scratch: &mut Scratch, term: &[char], trace: bool) -> Option<(i32, Vec<usize>)> {
+    let n = scratch.text.len();
+    let m = term.len();
+    if m == 0 || m > n {
+        return if m == 0 { Some((0, Vec::new())) } else { None };
+    }
+    // Every alignment lies between the first occurrence of the term's first
+    // character and the last occurrence of its last one.
+    let first = scratch.text.iter().position(|&c| c == term[0])?;
+    let end = scratch.text.iter().rposition(|&c| c == term[m - 1])? + 1;
+    if end - first < m {
+        return None;
+    }
+    if m == 1 && !trace {
+        let best = (first..end)
+            .filter(|&j| scratch.text[j] == term[0])
+            .map(|j| scratch.bonus[j])
+            .max()?;
+        return Some((SCORE_MATCH + best * BONUS_FIRST_MULTIPLIER, Vec::new()));
+    }
+
+    let rows = if trace { m } else { 2 };
+    scratch.h.clear();
+    scratch.h.resize(rows * n, NONE);
+    if trace {
+        scratch.consecutive.clear();
+        scratch.consecutive.resize(rows * n, false);
+    }
+
+    let Scratch {
+        text,
+        bonus,
+        h,
+        consecutive,
+    } = scratch;
+
+    for (i, &pc) in term.iter().enumerate() {
+        let row = if trace { i } else { i % 2 } * n;
+        let prev_row = if trace {
+            i.saturating_sub(1)
+        } else {
+            (i + 1) % 2
+        } * n;
+        // Best predecessor score reaching column j across a gap of one or more
+        // characters, already carrying the gap penalty.
+        let mut gapped = NONE;
+        for j in first..end {
+            if j >= first + 2 && i > 0 {
+                gapped = gapped
+                    .saturating_add(GAP_EXTENSION)
+                    .max(h[prev_row + j - 2].saturating_add(GAP_START));
+            }
+            if text[j] != pc {
+                h[row + j] = NONE;
+                continue;
+            }
+            if i == 0 {
+                h[row + j] = SCORE_MATCH + bonus[j] * BONUS_FIRST_MULTIPLIER;
+                continue;
+            }
+            let contiguous = if j > first {
+                h[prev_row + j - 1].saturating_add(BONUS_CONSECUTIVE)
+            } else {
+                NONE
+            };
+            let best = contiguous.max(gapped);
+            if best <= NONE / 2 {
+                h[row + j] = NONE;
+                continue;
+            }
+            h[row + j] = best + SCORE_MATCH + bonus[j];
+            if trace {
+                consecutive[row + j] = contiguous >= gapped;
+            }
+        }
+    }
+
+    let last = if trace { m - 1 } else { (m - 1) % 2 } * n;
+    let (end, score) = (first..end)
+        .map(|j| (j, h[last + j]))
+        .filter(|&(_, s)| s > NONE / 2)
+        .max_by_key(|&(j, s)| (s, std::cmp::Reverse(j)))?;
+    if !trace {
+        return Some((score, Vec::new()));
+    }
+
+    // Walk back through the matrix to recover one optimal alignment.
+    let mut positions = vec![0; m];
+    let mut j = end;
+    for i in (0..m).rev() {
+        positions[i] = j;
+        if i == 0 {
+            break;
+        }
+        let prev_row = (i - 1) * n;
+        j = if consecutive[i * n + j] {
+            j - 1
+        } else {
+            (0..j.saturating_sub(1))
+                .filter(|&k| h[prev_row + k] > NONE / 2)
+                .max_by_key(|&k| h[prev_row + k] + GAP_START + GAP_EXTENSION * (j - k - 2) as i32)?
+        };
+    }
+    Some((score, positions))
+}
+
+fn to_ranges(positions: &[usize]) -> Ranges {
+    let mut ranges: Ranges = Vec::new();
+    for &p in positions {
+        match ranges.last_mut() {
+            Some(range) if range[1] == p => range[1] = p + 1,
+            _ => ranges.push([p, p + 1]),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefilter_passes_whatever_score_accepts() {
+        let cases = [
+            ("kelvin", "\u{212A}elvin"),
+            ("istanbul", "İstanbul"),
+            ("ärger", "Ärger"),
+            ("straße", "STRASSE"),
+            ("report", "Report.PDF"),
+        ];
+        for (query, candidate) in cases {
+            let pattern = Pattern::parse(query);
+            if pattern.score(candidate).is_some() {
+                let lower = candidate.to_ascii_lowercase();
+                assert!(pattern.prefilter(lower.as_bytes()), "{query} {candidate}");
+            }
+        }
+        assert!(Pattern::parse("ärger").score("Ärger").is_some());
+        assert!(Pattern::parse("report").score("Report.PDF").is_some());
+    }
+
+    fn score(query: &str, candidate: &str) -> i64 {
+        Pattern::parse(query).score(candidate).unwrap()
+    }
+
+    #[test]
+    fn word_boundaries_beat_mid_word_matches() {
+        assert!(score("bar", "foo-bar") > score("bar", "foobar"));
+        assert!(score("bar", "foo bar") > score("bar", "foobarx"));
+    }
+
+    #[test]
+    fn camel_humps_earn_a_bonus() {
+        assert!(score("b", "fooBar") > score("b", "foobar"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+    }
+
+    #[test]
+    fn contiguous_runs_beat_gapped_ones() {
+        assert!(score("abc", "xabcx") > score("abc", "xaxbxcx"));
+        assert!(score("abc", "xaxbxcx") > score("abc", "xaxxxbxxxcx"));
+    }
+
+    #[test]
+    fn uppercase_queries_are_case_sensitive() {
+        assert!(Pattern::parse("readme").score("README.md").is_some());
+        assert!(Pattern::parse("README").score("readme.md").is_none());
+        assert!(Pattern::parse("ReadMe").score("ReadMe.md").is_some());
+        assert!(Pattern::parse("ReadMe").score("readme.md").is_none());
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        let pattern = Pattern::parse("rep pdf");
+        assert_eq!(
+            pattern.score("report.pdf"),
+            Some(score("rep", "report.pdf") + score("pdf", "report.pdf"))
+        );
+        assert!(pattern.score("report.txt").is_none());
+        assert!(Pattern::parse("  ").score("report.pdf").is_none());
+        // Terms may match in any order.
+        assert!(Pattern::parse("pdf rep").score("report.pdf").is_some());
+    }
+
+    #[test]
+    fn positions_are_exact() {
+        let found = Pattern::parse("qlnch").positions("QuickLauncher").unwrap();
+        assert_eq!(found.ranges, [[0, 1], [5, 6], [8, 11]]);
+        assert_eq!(
+            Some(found.score),
+            Pattern::parse("qlnch").score("QuickLauncher")
+        );
+
+        let found = Pattern::parse("rep pdf").positions("report.pdf").unwrap();
+        assert_eq!(found.ranges, [[0, 3], [7, 10]]);
+        let found = Pattern::parse("bar").positions("foobar-bar").unwrap();
+        assert_eq!(found.ranges, [[7, 10]]);
+    }
+}
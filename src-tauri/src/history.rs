@@ -0,0 +1,421 @@
+//! Launch history and frecency.
+//!
+//! Every launch is appended as one JSON line to `history.jsonl` in the app
+//! data directory, so recording never rewrites the file. On startup the log is
+//! replayed into one [`HistoryItem`] per path and compacted once dead lines
+//! outnumber live ones. Forgetting a path rewrites the log at once and
+//! clearing deletes it, so nothing forgotten stays on disk.
+//!
+//! Frecency is a launch count that decays exponentially: each launch adds 1,
+//! and the total halves every `half_life_days`.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::error::Result;
+
+const HISTORY_FILE: &str = "history.jsonl";
+const SECS_PER_DAY: f64 = 86_400.0;
+/// Compaction is skipped for small logs, where it would save nothing.
+const COMPACT_MIN_LINES: usize = 256;
+
+/// How launches feed into ranking. Stored under `history` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Record launches and boost frequently used results.
+    pub enabled: bool,
+    /// Days after which a launch counts half as much.
+    pub half_life_days: f64,
+    /// Score added per e-fold of frecency. One matched character is worth 16.
+    pub weight: f64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            half_life_days: 14.0,
+            weight: 24.0,
+        }
+    }
+}
+
+/// Aggregated launches of one path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub path: PathBuf,
+    pub count: u64,
+    /// Unix seconds of the most recent launch.
+    pub last_used: u64,
+    /// Decayed launch count as of `last_used`.
+    pub weight: f64,
+}
+
+impl HistoryItem {
+    fn frecency(&self, now: u64, half_life_days: f64) -> f64 {
+        self.weight * decay(now.saturating_sub(self.last_used), half_life_days)
+    }
+}
+
+/// One line of the history log.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Event {
+    Launch {
+        path: PathBuf,
+        at: u64,
+    },
+    /// Logs no longer get these two, but older ones may still hold them.
+    Forget {
+        path: PathBuf,
+    },
+    Clear,
+    /// Aggregated state written by compaction.
+    Item(HistoryItem),
+}
+
+fn decay(elapsed_secs: u64, half_life_days: f64) -> f64 {
+    if half_life_days <= 0.0 {
+        return 1.0;
+    }
+    0.5f64.powf(elapsed_secs as f64 / SECS_PER_DAY / half_life_days)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A history entry as reported to the webview, with its current frecency.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub item: HistoryItem,
+    pub frecency: f64,
+}
+
+/// Managed state owning the history log and its aggregated view.
+pub struct History {
+    path: PathBuf,
+    settings: HistorySettings,
+    items: Mutex<HashMap<PathBuf, HistoryItem>>,
+}
+
+impl History {
+    /// Replay the log in `data_dir`. Unparseable lines, such as one cut short
+    /// by a crash, are skipped.
+    pub fn open(data_dir: &Path, settings: HistorySettings) -> Self {
+        let path = data_dir.join(HISTORY_FILE);
+        let history = Self {
+            path,
+            settings,
+            items: Mutex::new(HashMap::new()),
+        };
+        let mut lines = 0;
+        match fs::File::open(&history.path) {
+            Ok(file) => {
+                let mut items = history.items.lock().unwrap();
+                for line in BufReader::new(file).lines().map_while(|l| l.ok()) {
+                    lines += 1;
+                    match serde_json::from_str(&line) {
+                        Ok(event) => history.apply(&mut items, event),
+                        Err(e) => log::debug!("skipping history line: {e}"),
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("could not read {}: {e}", history.path.display()),
+        }
+
+        let live = history.items.lock().unwrap().len();
+        if lines > COMPACT_MIN_LINES && lines > 2 * live {
+            if let Err(e) = history.compact() {
+                log::warn!("could not compact {}: {e}", history.path.display());
+            }
+        }
+        history
+    }
+
+    fn apply(&self, items: &mut HashMap<PathBuf, HistoryItem>, event: Event) {
+        match event {
+            Event::Launch { path, at } => {
+                let half_life = self.settings.half_life_days;
+                let item = items.entry(path.clone()).or_insert(HistoryItem {
+                    path,
+                    count: 0,
+                    last_used: at,
+                    weight: 0.0,
+                });
+                let elapsed = at.saturating_sub(item.last_used);
+                item.weight = item.weight * decay(elapsed, half_life) + 1.0;
+                item.count += 1;
+                item.last_used = item.last_used.max(at);
+            }
+            Event::Forget { path } => {
+                items.remove(&path);
+            }
+            Event::Clear => items.clear(),
+            Event::Item(item) => {
+                items.insert(item.path.clone(), item);
+            }
+        }
+    }
+
+    fn append(&self, event: &Event) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    fn compact(&self) -> Result<()> {
+        self.rewrite(&self.items.lock().unwrap())
+    }
+
+    /// Replace the log with one line per item in `items`.
+    fn rewrite(&self, items: &HashMap<PathBuf, HistoryItem>) -> Result<()> {
+        let mut out = Vec::new();
+        for item in items.values() {
+            serde_json::to_writer(&mut out, &Event::Item(item.clone()))?;
+            out.push(b'\n');
+        }
+        // Write to a sibling file first so a crash never leaves a truncated file.
+        let tmp = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Record a launch of `path`. Does nothing when history is disabled.
+    pub fn record(&self, path: &Path) -> Result<()> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+        let event = Event::Launch {
+            path: path.to_path_buf(),
+            at: now(),
+        };
+        // Held across the append so a concurrent forget or clear cannot
+        // rewrite the log between the line and the item.
+        let mut items = self.items.lock().unwrap();
+        self.append(&event)?;
+        self.apply(&mut items, event);
+        Ok(())
+    }
+
+    /// Ranking boost for `path`: `weight` per e-fold of its frecency, so the
+    /// tenth launch matters less than the second.
+    pub fn boost(&self, path: &Path) -> f64 {
+        if !self.settings.enabled {
+            return 0.0;
+        }
+        self.items
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|item| {
+                let frecency = item.frecency(now(), self.settings.half_life_days);
+                self.settings.weight * frecency.ln_1p()
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Paths that search should consider regardless of how the index ranks
+    /// them. Empty when history is disabled.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        if !self.settings.enabled {
+            return Vec::new();
+        }
+        self.items.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Every recorded item, most frecent first.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let now = now();
+        let mut entries: Vec<HistoryEntry> = self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .map(|item| HistoryEntry {
+                frecency: item.frecency(now, self.settings.half_life_days),
+                item: item.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| b.frecency.total_cmp(&a.frecency));
+        entries
+    }
+
+    /// Drop `path` and rewrite the log without it. Returns whether it was
+    /// recorded.
+    pub fn forget(&self, path: &Path) -> Result<bool> {
+        let mut items = self.items.lock().unwrap();
+        let Some(item) = items.remove(path) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.rewrite(&items) {
+            items.insert(path.to_path_buf(), item);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Drop every item and delete the log.
+    pub fn clear(&self) -> Result<()> {
+        let mut items = self.items.lock().unwrap();
+        match fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        items.clear();
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn history_list(history: State<'_, History>, limit: Option<usize>) -> Vec<HistoryEntry> {
+    let mut entries = history.entries();
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    entries
+}
+
+/// Drop one path from the history. Returns whether it was recorded.
+#[tauri::command]
+pub fn history_forget(history: State<'_, History>, path: PathBuf) -> Result<bool> {
+    history.forget(&path)
+}
+
+#[tauri::command]
+pub fn history_clear(history: State<'_, History>) -> Result<()> {
+    history.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400;
+
+    fn launch(path: &str, at: u64) -> String {
+        serde_json::to_string(&Event::Launch {
+            path: path.into(),
+            at,
+        })
+        .unwrap()
+    }
+
+    fn open(dir: &Path, lines: &[String]) -> History {
+        fs::write(dir.join(HISTORY_FILE), lines.join("\n")).unwrap();
+        History::open(dir, HistorySettings::default())
+    }
+
+    fn count(history: &History, path: &str) -> Option<u64> {
+        let items = history.items.lock().unwrap();
+        items.get(Path::new(path)).map(|item| item.count)
+    }
+
+    #[test]
+    fn truncated_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cut = launch("/notes.txt", 20);
+        cut.truncate(cut.len() / 2);
+        let history = open(
+            dir.path(),
+            &[launch("/notes.txt", 0), launch("/todo.txt", 10), cut],
+        );
+        assert_eq!(count(&history, "/notes.txt"), Some(1));
+        assert_eq!(count(&history, "/todo.txt"), Some(1));
+    }
+
+    #[test]
+    fn frecency_halves_every_half_life() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = open(
+            dir.path(),
+            &[launch("/notes.txt", 0), launch("/notes.txt", 0)],
+        );
+        let items = history.items.lock().unwrap();
+        let item = &items[Path::new("/notes.txt")];
+        let half_life = history.settings.half_life_days;
+        let after = |days: f64| item.frecency((days * DAY as f64) as u64, half_life);
+        assert_eq!(after(0.0), 2.0);
+        assert!((after(half_life) - 1.0).abs() < 1e-9);
+        assert!((after(2.0 * half_life) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compaction_keeps_every_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines: Vec<String> = (0..300)
+            .map(|i| {
+                launch(
+                    if i % 3 == 0 {
+                        "/todo.txt"
+                    } else {
+                        "/notes.txt"
+                    },
+                    i,
+                )
+            })
+            .collect();
+        let history = open(dir.path(), &lines);
+        assert_eq!(count(&history, "/notes.txt"), Some(200));
+        assert_eq!(count(&history, "/todo.txt"), Some(100));
+
+        let log = fs::read_to_string(dir.path().join(HISTORY_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        let reopened = History::open(dir.path(), HistorySettings::default());
+        assert_eq!(count(&reopened, "/notes.txt"), Some(200));
+        assert_eq!(count(&reopened, "/todo.txt"), Some(100));
+    }
+
+    #[test]
+    fn forgetting_rewrites_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open(dir.path(), HistorySettings::default());
+        history.record(Path::new("/secret.txt")).unwrap();
+        history.record(Path::new("/notes.txt")).unwrap();
+
+        assert!(history.forget(Path::new("/secret.txt")).unwrap());
+        assert!(!history.forget(Path::new("/secret.txt")).unwrap());
+        let log = fs::read_to_string(dir.path().join(HISTORY_FILE)).unwrap();
+        assert!(!log.contains("secret"));
+        assert!(log.contains("/notes.txt"));
+        let reopened = History::open(dir.path(), HistorySettings::default());
+        assert_eq!(count(&reopened, "/secret.txt"), None);
+        assert_eq!(count(&reopened, "/notes.txt"), Some(1));
+    }
+
+    #[test]
+    fn clearing_deletes_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open(dir.path(), HistorySettings::default());
+        history.record(Path::new("/notes.txt")).unwrap();
+        assert!(dir.path().join(HISTORY_FILE).exists());
+
+        history.clear().unwrap();
+        assert!(!dir.path().join(HISTORY_FILE).exists());
+        assert!(history.entries().is_empty());
+        history.clear().unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, GlobalShortcutManager, Manager, State};
+
+use crate::error::{Error, Result};
+use crate::settings::SettingsStore;
+use crate::window;
+
+/// Accelerator written to a fresh settings file.
+pub const DEFAULT_HOTKEY: &str = "CmdOrCtrl+Space";
+
+/// Accelerator tried when the configured one cannot be registered. It avoids
+/// the `Ctrl+Space` chord that input-method frameworks commonly grab.
+pub const FALLBACK_HOTKEY: &str = "CmdOrCtrl+Shift+Space";
+
+const MODIFIERS: &[&str] = &[
+    "OPTION",
+    "ALT",
+    "CONTROL",
+    "CTRL",
+    "COMMAND",
+    "CMD",
+    "SUPER",
+    "SHIFT",
+    "COMMANDORCONTROL",
+    "COMMANDORCTRL",
+    "CMDORCTRL",
+    "CMDORCONTROL",
+];
+
+#[rustfmt::skip]
+const NAMED_KEYS: &[&str] = &[
+    "SPACE", "ENTER", "TAB", "BACKSPACE", "ESC", "ESCAPE", "INSERT", "DELETE", "HOME", "END",
+    "PAGEUP", "PAGEDOWN", "UP", "DOWN", "LEFT", "RIGHT", "ARROWUP", "ARROWDOWN", "ARROWLEFT",
+    "ARROWRIGHT", "BACKQUOTE", "BACKSLASH", "BRACKETLEFT", "BRACKETRIGHT", "COMMA", "PERIOD",
+    "QUOTE", "SEMICOLON", "SLASH", "PLUS", "PRINTSCREEN", "SCROLLLOCK", "PAUSE", "`", "[", "]",
+    ",", ".", "'", ";", "/", "=", "-",
+];
+
+/// Which accelerator is currently active, and why the configured one is not
+/// if registration had to fall back.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HotkeyStatus {
+    pub configured: String,
+    pub active: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct HotkeyState(Mutex<HotkeyStatus>);
+
+/// Check that `accelerator` is one or more distinct modifiers followed by
+/// exactly one key the shortcut manager understands. The platform parser
+/// silently accepts modifier-only strings, so this catches those before they
+/// reach it.
+pub fn validate(accelerator: &str) -> Result<()> {
+    let invalid = |reason: &str| Error::InvalidAccelerator(accelerator.to_string(), reason.into());
+
+    let tokens: Vec<String> = accelerator
+        .split('+')
+        .map(|t| t.trim().to_uppercase())
+        .collect();
+    if tokens.iter().any(String::is_empty) {
+        return Err(invalid("empty token"));
+    }
+    let (key, mods) = tokens.split_last().ok_or_else(|| invalid("empty"))?;
+    if mods.is_empty() {
+        return Err(invalid("at least one modifier is required"));
+    }
+    if let Some(m) = mods.iter().find(|m| !MODIFIERS.contains(&m.as_str())) {
+        return Err(invalid(&format!("unknown modifier `{m}`")));
+    }
+    if let Some((_, m)) = mods
+        .iter()
+        .enumerate()
+        .find(|(i, m)| mods[..*i].contains(m))
+    {
+        return Err(invalid(&format!("duplicate modifier `{m}`")));
+    }
+    if MODIFIERS.contains(&key.as_str()) {
+        return Err(invalid("missing a non-modifier key"));
+    }
+    if !is_key(key) {
+        return Err(invalid(&format!("unknown key `{key}`")));
+    }
+    Ok(())
+}
+
+fn is_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            return true;
+        }
+    }
+    if let Some(n) = key.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+        return (1..=24).contains(&n);
+    }
+    NAMED_KEYS.contains(&key)
+}
+
+fn register(app: &AppHandle, accelerator: &str) -> Result<()> {
+    validate(accelerator)?;
+    let handle = app.clone();
+    app.global_shortcut_manager()
+        .register(accelerator, move || window::toggle(&handle))
+        .map_err(|e| Error::Shortcut(accelerator.to_string(), e.to_string()))
+}
+
+/// Register the configured accelerator at startup. If it is invalid or taken,
+/// fall back to [`FALLBACK_HOTKEY`] and record the reason instead of failing.
+pub fn init(app: &AppHandle) {
+    let configured = app.state::<SettingsStore>().get().hotkey.clone();
+    let mut status = HotkeyStatus {
+        configured: configured.clone(),
+        ..Default::default()
+    };
+
+    match register(app, &configured) {
+        Ok(()) => status.active = Some(configured),
+        Err(e) => {
+            log::warn!("{e}; falling back to {FALLBACK_HOTKEY}");
+            status.error = Some(e.to_string());
+            match register(app, FALLBACK_HOTKEY) {
+                Ok(()) => status.active = Some(FALLBACK_HOTKEY.to_string()),
+                Err(e) => log::error!("{e}; no global shortcut is active"),
+            }
+        }
+    }
+
+    *app.state::<HotkeyState>().0.lock().unwrap() = status;
+}
+
+#[tauri::command]
+pub fn get_hotkey(state: State<'_, HotkeyState>) -> HotkeyStatus {
+    state.0.lock().unwrap().clone()
+}
+
+/// The global shortcut registrations a hotkey change works with.
+trait Shortcuts {
+    fn register(&mut self, accelerator: &str) -> Result<()>;
+    fn unregister(&mut self, accelerator: &str);
+}
+
+impl Shortcuts for AppHandle {
+    fn register(&mut self, accelerator: &str) -> Result<()> {
+        register(self, accelerator)
+    }
+
+    fn unregister(&mut self, accelerator: &str) {
+        let _ = self.global_shortcut_manager().unregister(accelerator);
+    }
+}
+
+/// Register `previous` again after a failed change, and mark it active if
+/// that worked.
+fn reinstate(shortcuts: &mut impl Shortcuts, status: &mut HotkeyStatus, previous: Option<String>) {
+    if let Some(previous) = previous {
+        if shortcuts.register(&previous).is_ok() {
+            status.active = Some(previous);
+        }
+    }
+}
+
+/// Make `accelerator` the active shortcut and `save` it. On failure the
+/// previous accelerator stays registered.
+fn change(
+    shortcuts: &mut impl Shortcuts,
+    status: &mut HotkeyStatus,
+    accelerator: String,
+    save: impl FnOnce(&str) -> Result<()>,
+) -> Result<HotkeyStatus> {
+    validate(&accelerator)?;
+    let previous = status.active.take();
+    if let Some(previous) = &previous {
+        shortcuts.unregister(previous);
+    }
+    if let Err(e) = shortcuts.register(&accelerator) {
+        reinstate(shortcuts, status, previous);
+        return Err(e);
+    }
+    // Unsaved, the new shortcut would be gone after a restart; keep the one
+    // that is saved.
+    if let Err(e) = save(&accelerator) {
+        shortcuts.unregister(&accelerator);
+        reinstate(shortcuts, status, previous);
+        return Err(e);
+    }
+    *status = HotkeyStatus {
+        configured: accelerator.clone(),
+        active: Some(accelerator),
+        error: None,
+    };
+    Ok(status.clone())
+}
+
+/// Swap the active accelerator at runtime and persist it. On failure the
+/// previous accelerator stays registered.
+#[tauri::command]
+pub fn set_hotkey(
+    mut app: AppHandle,
+    state: State<'_, HotkeyState>,
+    settings: State<'_, SettingsStore>,
+    accelerator: String,
+) -> Result<HotkeyStatus> {
+    let mut status = state.0.lock().unwrap();
+    change(&mut app, &mut status, accelerator, |accelerator| {
+        settings.update(|s| s.hotkey = accelerator.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shortcuts where the accelerators in `taken` belong to someone else.
+    #[derive(Default)]
+    struct Fake {
+        registered: Vec<String>,
+        taken: Vec<&'static str>,
+    }
+
+    impl Shortcuts for Fake {
+        fn register(&mut self, accelerator: &str) -> Result<()> {
+            if self.taken.contains(&accelerator) {
+                return Err(Error::Shortcut(accelerator.into(), "taken".into()));
+            }
+            self.registered.push(accelerator.into());
+            Ok(())
+        }
+
+        fn unregister(&mut self, accelerator: &str) {
+            self.registered.retain(|a| a != accelerator);
+        }
+    }
+
+    fn active(accelerator: &str) -> HotkeyStatus {
+        HotkeyStatus {
+            configured: accelerator.into(),
+            active: Some(accelerator.into()),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn accelerators_need_modifiers_and_one_key() {
+        for accelerator in ["Ctrl+Space", "CmdOrCtrl+Shift+K", "alt+f12", "Super+7"] {
+            assert!(validate(accelerator).is_ok(), "{accelerator}");
+        }
+        for accelerator in ["Space", "Ctrl", "Ctrl+Shift", "", "Ctrl+", "Ctrl++"] {
+            assert!(validate(accelerator).is_err(), "{accelerator}");
+        }
+    }
+
+    #[test]
+    fn unknown_and_duplicate_parts_are_refused() {
+        for accelerator in [
+            "Ctrl+Foo",
+            "Ctrl+F25",
+            "Ctrl+F0",
+            "Hyper+A",
+            "Ctrl+Ctrl+A",
+            "Ctrl+Shift+ctrl+A",
+        ] {
+            assert!(validate(accelerator).is_err(), "{accelerator}");
+        }
+    }
+
+    #[test]
+    fn named_keys_are_accepted() {
+        for key in NAMED_KEYS {
+            let accelerator = format!("Ctrl+{}", key.to_lowercase());
+            assert!(validate(&accelerator).is_ok(), "{accelerator}");
+        }
+    }
+
+    #[test]
+    fn failed_changes_keep_the_previous_shortcut() {
+        let mut shortcuts = Fake {
+            registered: vec!["Ctrl+Space".into()],
+            taken: vec!["Alt+Space"],
+        };
+        let mut status = active("Ctrl+Space");
+        assert!(change(&mut shortcuts, &mut status, "Alt+Space".into(), |_| Ok(())).is_err());
+        assert_eq!(shortcuts.registered, ["Ctrl+Space"]);
+        assert_eq!(status.active.as_deref(), Some("Ctrl+Space"));
+
+        let unsaved = |_: &str| Err(Error::Task("disk full".into()));
+        assert!(change(&mut shortcuts, &mut status, "Alt+K".into(), unsaved).is_err());
+        assert_eq!(shortcuts.registered, ["Ctrl+Space"]);
+        assert_eq!(status.active.as_deref(), Some("Ctrl+Space"));
+
+        let status = change(&mut shortcuts, &mut status, "Alt+K".into(), |_| Ok(())).unwrap();
+        assert_eq!(shortcuts.registered, ["Alt+K"]);
+        assert_eq!(status.configured, "Alt+K");
+        assert_eq!(status.active.as_deref(), Some("Alt+K"));
+    }
+}
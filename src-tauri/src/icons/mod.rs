@@ -0,0 +1,234 @@
+//! Result icons, resolved through the freedesktop icon theme and served to
+//! the webview over the `icon://` protocol.
+//!
+//! URLs name what to show rather than a file: `icon://localhost/name/firefox`
+//! for a theme icon (or an absolute image path) and
+//! `icon://localhost/mime/text/x-python` for a file type, with optional
+//! `size` and `scale` query parameters. Resolved icons are copied into the app
+//! cache directory, so once warm a request never has to load a theme.
+
+mod theme;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use tauri::http::{Request, Response, ResponseBuilder};
+use tauri::{AppHandle, Manager, Url};
+
+use crate::apps::{self, KeyFile};
+use crate::mime::MimeDb;
+
+use theme::{is_icon_name, Themes, EXTENSIONS};
+
+pub const SCHEME: &str = "icon";
+const DEFAULT_SIZE: u32 = 32;
+const FALLBACK_THEME: &str = "hicolor";
+/// Cached icons older than this are resolved again, picking up theme updates.
+const CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Icon settings, stored under `icons` in settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconSettings {
+    /// Icon theme to use. Detected from the GTK or KDE configuration when
+    /// unset.
+    pub theme: Option<String>,
+}
+
+/// Base of every icon URL. Windows webviews only allow custom protocols
+/// under `https://<scheme>.localhost`.
+fn base_url() -> String {
+    if cfg!(target_os = "windows") {
+        format!("https://{SCHEME}.localhost/")
+    } else {
+        format!("{SCHEME}://localhost/")
+    }
+}
+
+/// URL of the theme icon `name`, as given by a desktop entry's `Icon` key.
+pub fn name_url(name: &str) -> String {
+    format!(
+        "{}name/{}",
+        base_url(),
+        utf8_percent_encode(name, NON_ALPHANUMERIC)
+    )
+}
+
+/// Read the icon theme name from the desktop's configuration files.
+fn detect_theme() -> Option<String> {
+    let config = tauri::api::path::config_dir()?;
+    let gtk = ["gtk-4.0", "gtk-3.0"].iter().find_map(|dir| {
+        let text = fs::read_to_string(config.join(dir).join("settings.ini")).ok()?;
+        KeyFile::parse(&text)
+            .group("Settings")?
+            .string("gtk-icon-theme-name")
+    });
+    gtk.or_else(|| {
+        let text = fs::read_to_string(config.join("kdeglobals")).ok()?;
+        KeyFile::parse(&text).group("Icons")?.string("Theme")
+    })
+}
+
+/// A 64-bit FNV-1a hash, stable across builds so cache file names are too.
+fn fnv1a(data: &str) -> u64 {
+    data.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => "image/svg+xml",
+        _ => "image/png",
+    }
+}
+
+/// Managed state resolving and caching icons.
+pub struct Icons {
+    theme: String,
+    cache_dir: PathBuf,
+    themes: Arc<Themes>,
+    mime: MimeDb,
+    /// Keys known to have no icon, so repeated misses skip the theme walk.
+    misses: Mutex<HashSet<String>>,
+}
+
+impl Icons {
+    pub fn new(cache_dir: PathBuf, settings: &IconSettings, mime: MimeDb) -> Self {
+        let theme = settings
+            .theme
+            .clone()
+            .or_else(detect_theme)
+            .unwrap_or_else(|| FALLBACK_THEME.to_string());
+        log::info!("using icon theme `{theme}`");
+
+        let data_dirs = apps::data_dirs();
+        let mut base_dirs: Vec<PathBuf> = tauri::api::path::home_dir()
+            .map(|home| home.join(".icons"))
+            .into_iter()
+            .collect();
+        base_dirs.extend(data_dirs.iter().map(|dir| dir.join("icons")));
+        let pixmaps = data_dirs.iter().map(|dir| dir.join("pixmaps")).collect();
+
+        // Loading a theme lists all of its directories; do it before the
+        // first request needs it rather than while that request waits.
+        let themes = Arc::new(Themes::new(theme.clone(), base_dirs, pixmaps));
+        let preloaded = themes.clone();
+        if let Err(e) = thread::Builder::new()
+            .name("icon-themes".into())
+            .spawn(move || preloaded.preload())
+        {
+            log::warn!("could not preload icon themes: {e}");
+        }
+
+        Self {
+            theme,
+            cache_dir: cache_dir.join("icons"),
+            themes,
+            mime,
+            misses: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// URL of the icon for a file or directory, chosen by MIME type.
+    pub fn file_url(&self, path: &Path, is_dir: bool) -> String {
+        let mime = self.mime.guess(path, is_dir);
+        format!("{}mime/{}", base_url(), mime)
+    }
+
+    /// Resolve a request path such as `name/firefox` to image bytes and their
+    /// content type.
+    fn load(&self, spec: &str, size: u32, scale: u32) -> Option<(Vec<u8>, &'static str)> {
+        let key = format!("{}|{spec}|{size}@{scale}", self.theme);
+        let stem = format!("{:016x}", fnv1a(&key));
+        for ext in EXTENSIONS {
+            let cached = self.cache_dir.join(format!("{stem}.{ext}"));
+            let fresh = fs::metadata(&cached)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| SystemTime::now().duration_since(t).ok())
+                .is_some_and(|age| age < CACHE_TTL);
+            if fresh {
+                if let Ok(bytes) = fs::read(&cached) {
+                    return Some((bytes, content_type(&cached)));
+                }
+            }
+        }
+        if self.misses.lock().unwrap().contains(&key) {
+            return None;
+        }
+
+        let Some(path) = self.resolve(spec, size, scale) else {
+            self.misses.lock().unwrap().insert(key);
+            return None;
+        };
+        let bytes = fs::read(&path).ok()?;
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+        if let Err(e) = self.store(&format!("{stem}.{ext}"), &bytes) {
+            log::debug!("could not cache icon {}: {e}", path.display());
+        }
+        Some((bytes, content_type(&path)))
+    }
+
+    fn resolve(&self, spec: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let names = match spec.split_once('/')? {
+            ("name", name) => {
+                let path = Path::new(name);
+                if path.is_absolute() {
+                    // Only serve images, so the scheme cannot read arbitrary files.
+                    let ext = path.extension().and_then(|e| e.to_str())?;
+                    let plain = path.components().all(|c| c != Component::ParentDir);
+                    return (plain && EXTENSIONS.contains(&ext) && path.is_file())
+                        .then(|| path.to_path_buf());
+                }
+                if !is_icon_name(name) {
+                    return None;
+                }
+                vec![name.to_string()]
+            }
+            ("mime", mime) => self.mime.icon_names(mime),
+            _ => return None,
+        };
+        self.themes.find(&names, size, scale)
+    }
+
+    fn store(&self, file: &str, bytes: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.cache_dir)?;
+        let path = self.cache_dir.join(file);
+        // Write to a sibling file first so a crash never leaves a truncated file.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// Handler for the `icon://` protocol.
+pub fn handle(app: &AppHandle, request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+    let not_found = || ResponseBuilder::new().status(404).body(Vec::new());
+    let Some(icons) = app.try_state::<Icons>() else {
+        return not_found();
+    };
+    let url = Url::parse(request.uri())?;
+    let spec = percent_decode_str(url.path().trim_start_matches('/')).decode_utf8()?;
+    let param = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.parse::<u32>().ok())
+    };
+    let size = param("size").unwrap_or(DEFAULT_SIZE).clamp(8, 512);
+    let scale = param("scale").unwrap_or(1).clamp(1, 4);
+
+    match icons.load(&spec, size, scale) {
+        Some((bytes, content_type)) => ResponseBuilder::new()
+            .mimetype(content_type)
+            .header("Cache-Control", "max-age=86400")
+            .body(bytes),
+        None => not_found(),
+    }
+}
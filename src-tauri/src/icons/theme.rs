@@ -0,0 +1,293 @@
+//! Icon lookup following the freedesktop Icon Theme Specification.
+//!
+//! Each theme's directories are listed once and kept as a name → files map,
+//! so a lookup costs a few hash probes instead of a stat per directory.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::apps::{Group, KeyFile};
+
+const FALLBACK_THEME: &str = "hicolor";
+/// Formats the webview can display, in order of preference.
+pub const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// One `Directories` entry of an `index.theme`.
+#[derive(Debug)]
+struct Directory {
+    size: u32,
+    scale: u32,
+    kind: Kind,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl Directory {
+    fn parse(group: &Group) -> Option<Self> {
+        let int = |key: &str| group.string(key).and_then(|v| v.parse::<u32>().ok());
+        let size = int("Size")?;
+        let kind = match group.string("Type").as_deref() {
+            Some("Fixed") => Kind::Fixed,
+            Some("Scalable") => Kind::Scalable,
+            _ => Kind::Threshold,
+        };
+        Some(Self {
+            size,
+            scale: int("Scale").unwrap_or(1),
+            kind,
+            min_size: int("MinSize").unwrap_or(size),
+            max_size: int("MaxSize").unwrap_or(size),
+            threshold: int("Threshold").unwrap_or(2),
+        })
+    }
+
+    fn matches(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            Kind::Fixed => self.size == size,
+            Kind::Scalable => (self.min_size..=self.max_size).contains(&size),
+            Kind::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    fn distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (min, max) = match self.kind {
+            Kind::Fixed => (self.size, self.size),
+            Kind::Scalable => (self.min_size, self.max_size),
+            Kind::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        if wanted < min * self.scale {
+            min * self.scale - wanted
+        } else {
+            wanted.saturating_sub(max * self.scale)
+        }
+    }
+}
+
+/// A loaded theme: its parents and every icon file it contains.
+#[derive(Debug)]
+pub struct Theme {
+    inherits: Vec<String>,
+    directories: Vec<Directory>,
+    /// Icon name → (index into `directories`, file).
+    files: HashMap<String, Vec<(usize, PathBuf)>>,
+}
+
+impl Theme {
+    /// Load `name` from the first base directory that has an `index.theme`
+    /// for it. Icons are collected from the theme directory in every base
+    /// directory, as the spec merges them.
+    pub fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        let index = base_dirs
+            .iter()
+            .map(|base| base.join(name).join("index.theme"))
+            .find(|index| index.is_file())?;
+        let mut file = KeyFile::parse(&fs::read_to_string(index).ok()?);
+        let entry = file.take("Icon Theme")?;
+
+        let mut names = entry.comma_list("Directories");
+        names.extend(entry.comma_list("ScaledDirectories"));
+        let mut directories = Vec::new();
+        let mut files: HashMap<String, Vec<(usize, PathBuf)>> = HashMap::new();
+        for dir_name in names {
+            let Some(directory) = file.group(&dir_name).and_then(Directory::parse) else {
+                continue;
+            };
+            let id = directories.len();
+            directories.push(directory);
+            for base in base_dirs {
+                let Ok(read) = fs::read_dir(base.join(name).join(&dir_name)) else {
+                    continue;
+                };
+                for file in read.flatten() {
+                    let path = file.path();
+                    let (Some(stem), Some(ext)) = (
+                        path.file_stem().and_then(|s| s.to_str()),
+                        path.extension().and_then(|e| e.to_str()),
+                    ) else {
+                        continue;
+                    };
+                    if EXTENSIONS.contains(&ext) {
+                        files.entry(stem.to_string()).or_default().push((id, path));
+                    }
+                }
+            }
+        }
+
+        Some(Self {
+            inherits: entry.comma_list("Inherits"),
+            directories,
+            files,
+        })
+    }
+
+    /// The spec's `LookupIcon`: an exact size match if there is one, the
+    /// closest size otherwise.
+    fn lookup(&self, name: &str, size: u32, scale: u32) -> Option<&Path> {
+        let candidates = self.files.get(name)?;
+        let preference = |path: &Path| {
+            let ext = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            EXTENSIONS
+                .iter()
+                .position(|e| *e == ext)
+                .unwrap_or(usize::MAX)
+        };
+        if let Some((_, path)) = candidates
+            .iter()
+            .filter(|(dir, _)| self.directories[*dir].matches(size, scale))
+            .min_by_key(|(_, path)| preference(path))
+        {
+            return Some(path);
+        }
+        candidates
+            .iter()
+            .min_by_key(|(dir, path)| {
+                (
+                    self.directories[*dir].distance(size, scale),
+                    preference(path),
+                )
+            })
+            .map(|(_, path)| path.as_path())
+    }
+}
+
+/// Whether `name` can be an icon name rather than a path: looking it up
+/// must not leave the directory it is looked up in.
+pub fn is_icon_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// The icon theme in use and the base directories it comes from. The theme
+/// and its ancestors are loaded once, on first use or by `preload`.
+pub struct Themes {
+    theme: String,
+    base_dirs: Vec<PathBuf>,
+    pixmaps: Vec<PathBuf>,
+    /// Inheritance chain of `theme`, depth first, ending with `hicolor`.
+    /// Themes that were not found are left out.
+    chain: OnceLock<Vec<Theme>>,
+}
+
+impl Themes {
+    pub fn new(theme: String, base_dirs: Vec<PathBuf>, pixmaps: Vec<PathBuf>) -> Self {
+        Self {
+            theme,
+            base_dirs,
+            pixmaps,
+            chain: OnceLock::new(),
+        }
+    }
+
+    /// Load the theme chain now, if it is not loaded yet. Concurrent lookups
+    /// wait for it rather than loading it again.
+    pub fn preload(&self) {
+        self.chain();
+    }
+
+    fn chain(&self) -> &[Theme] {
+        self.chain.get_or_init(|| {
+            let mut chain = Vec::new();
+            let mut seen = HashSet::new();
+            let mut stack = vec![self.theme.clone()];
+            while let Some(name) = stack.pop() {
+                if name == FALLBACK_THEME || !seen.insert(name.clone()) {
+                    continue;
+                }
+                match Theme::load(&name, &self.base_dirs) {
+                    Some(theme) => {
+                        stack.extend(theme.inherits.iter().rev().cloned());
+                        chain.push(theme);
+                    }
+                    None => log::debug!("icon theme `{name}` not found"),
+                }
+            }
+            chain.extend(Theme::load(FALLBACK_THEME, &self.base_dirs));
+            chain
+        })
+    }
+
+    /// The spec's `FindIcon`: look every name in `names` up in each theme of
+    /// the chain in turn, then as an unthemed icon in the base and pixmap
+    /// directories. Names that are really paths are skipped.
+    pub fn find(&self, names: &[String], size: u32, scale: u32) -> Option<PathBuf> {
+        let names: Vec<&String> = names.iter().filter(|name| is_icon_name(name)).collect();
+        for theme in self.chain() {
+            for name in &names {
+                if let Some(path) = theme.lookup(name, size, scale) {
+                    return Some(path.to_path_buf());
+                }
+            }
+        }
+        names.iter().find_map(|name| {
+            self.base_dirs
+                .iter()
+                .chain(&self.pixmaps)
+                .flat_map(|dir| {
+                    EXTENSIONS
+                        .iter()
+                        .map(move |ext| dir.join(format!("{name}.{ext}")))
+                })
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn theme(base: &Path, name: &str, inherits: &str, icons: &[&str]) {
+        let dir = base.join(name);
+        fs::create_dir_all(dir.join("48x48/apps")).unwrap();
+        let index = format!(
+            "[Icon Theme]\nName={name}\nInherits={inherits}\nDirectories=48x48/apps\n\n\
+             [48x48/apps]\nSize=48\nType=Fixed\n"
+        );
+        fs::write(dir.join("index.theme"), index).unwrap();
+        for icon in icons {
+            fs::write(dir.join("48x48/apps").join(format!("{icon}.png")), b"").unwrap();
+        }
+    }
+
+    #[test]
+    fn find_walks_the_chain_before_the_names() {
+        let base = TempDir::new().unwrap();
+        theme(base.path(), "child", "parent", &["generic"]);
+        theme(base.path(), "parent", "", &["specific"]);
+        fs::write(base.path().join("secret.png"), b"").unwrap();
+        let themes = Themes::new("child".into(), vec![base.path().to_path_buf()], Vec::new());
+
+        let names = ["specific".to_string(), "generic".to_string()];
+        let found = themes.find(&names, 48, 1).unwrap();
+        assert!(found.ends_with("child/48x48/apps/generic.png"));
+        let found = themes.find(&names[..1], 48, 1).unwrap();
+        assert!(found.ends_with("parent/48x48/apps/specific.png"));
+
+        assert!(themes.find(&["../secret".into()], 48, 1).is_none());
+        assert!(themes.find(&["child/../secret".into()], 48, 1).is_none());
+        assert!(themes.find(&["secret".into()], 48, 1).is_some());
+    }
+}
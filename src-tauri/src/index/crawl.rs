@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::rules::{IgnoreSettings, Rules, Scope, SkipReason};
+
+/// A filesystem entry found while crawling, before it is packed into an
+/// [`Index`](super::Index).
+#[derive(Debug, Clone)]
+pub struct RawEntry {
+    pub dir: PathBuf,
+    pub name: String,
+    pub size: u64,
+    pub mtime: i64,
+    pub is_dir: bool,
+}
+
+/// Stat a single path without following symlinks. Returns `None` if it no
+/// longer exists or cannot be represented (non-UTF-8 name, filesystem root).
+pub fn stat(path: &Path) -> Option<RawEntry> {
+    let meta = fs::symlink_metadata(path).ok()?;
+    Some(RawEntry {
+        dir: path.parent()?.to_path_buf(),
+        name: path.file_name()?.to_str()?.to_string(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        mtime: mtime(&meta),
+        is_dir: meta.is_dir(),
+    })
+}
+
+fn mtime(meta: &fs::Metadata) -> i64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// A path the crawler left out, reported by a dry run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Skipped {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Entries found under one directory, plus what was skipped when requested.
+#[derive(Default)]
+pub struct Crawl {
+    pub entries: Vec<RawEntry>,
+    pub skipped: Vec<Skipped>,
+}
+
+impl Crawl {
+    fn extend(&mut self, other: Crawl) {
+        self.entries.extend(other.entries);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+/// Crawl every root in parallel. Subdirectories are fanned out across the
+/// rayon pool as they are discovered, so one deep root does not serialize the
+/// whole crawl. Symlinks are recorded but never followed, and ignored
+/// directories are not descended into.
+pub fn crawl(roots: &[PathBuf], ignore: &IgnoreSettings) -> Vec<RawEntry> {
+    roots
+        .par_iter()
+        .filter(|root| root.is_dir())
+        .flat_map(|root| crawl_root(root, ignore, false).entries)
+        .collect()
+}
+
+/// Crawl a single root, optionally recording every skipped path and why.
+pub fn crawl_root(root: &Path, ignore: &IgnoreSettings, report: bool) -> Crawl {
+    let rules = Rules::new(root, ignore);
+    let scope = Scope::enter(&Arc::new(Scope::default()), root, ignore);
+    crawl_dir(root, &rules, &scope, report)
+}
+
+/// Crawl `dir`, a directory below `root` that appeared after the initial
+/// crawl, applying the ignore files of every ancestor in between. `rules`
+/// are those of `root`.
+pub fn crawl_subtree(rules: &Rules, root: &Path, dir: &Path) -> Vec<RawEntry> {
+    let scope = rules.scope_for(root, dir);
+    crawl_dir(dir, rules, &scope, false).entries
+}
+
+/// `dir` and every directory below it that a crawl would descend into, or
+/// nothing if `dir` itself is ignored. Only these need watching.
+pub fn watched_dirs(rules: &Rules, root: &Path, dir: &Path) -> Vec<PathBuf> {
+    if rules.check_path(root, dir).is_some() {
+        return Vec::new();
+    }
+    let scope = rules.scope_for(root, dir);
+    let mut dirs = subdirs(dir, rules, &scope);
+    dirs.push(dir.to_path_buf());
+    dirs
+}
+
+fn subdirs(dir: &Path, rules: &Rules, scope: &Arc<Scope>) -> Vec<PathBuf> {
+    let Ok(read) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = read
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let path = entry.path();
+            rules
+                .check(scope, &path, &name, true)
+                .is_none()
+                .then_some(path)
+        })
+        .collect();
+    let nested: Vec<PathBuf> = dirs
+        .par_iter()
+        .flat_map(|sub| subdirs(sub, rules, &Scope::enter(scope, sub, rules.settings())))
+        .collect();
+    dirs.extend(nested);
+    dirs
+}
+
+fn crawl_dir(dir: &Path, rules: &Rules, scope: &Arc<Scope>, report: bool) -> Crawl {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) => {
+            log::debug!(target: "index", "skipping {}: {e}", dir.display());
+            return Crawl::default();
+        }
+    };
+
+    let mut crawl = Crawl::default();
+    let mut subdirs = Vec::new();
+    for entry in read.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let Ok(name) = entry.file_name().into_string() else {
+            log::debug!(target: "index", "skipping non UTF-8 name in {}", dir.display());
+            continue;
+        };
+        let path = entry.path();
+        if let Some(reason) = rules.check(scope, &path, &name, file_type.is_dir()) {
+            if report {
+                crawl.skipped.push(Skipped { path, reason });
+            }
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            subdirs.push(path);
+        }
+        crawl.entries.push(RawEntry {
+            dir: dir.to_path_buf(),
+            name,
+            size: if file_type.is_dir() { 0 } else { meta.len() },
+            mtime: mtime(&meta),
+            is_dir: file_type.is_dir(),
+        });
+    }
+
+    let nested = subdirs
+        .par_iter()
+        .map(|sub| {
+            let scope = Scope::enter(scope, sub, rules.settings());
+            crawl_dir(sub, rules, &scope, report)
+        })
+        .reduce(Crawl::default, |mut a, b| {
+            a.extend(b);
+            a
+        });
+    crawl.extend(nested);
+    crawl
+}
@@ -0,0 +1,921 @@
+//! In-process filesystem index.
+//!
+//! Entries are packed into a few flat arrays rather than one allocation per
+//! file: directory paths are interned once, and every filename lives in a
+//! single `\0`-separated arena with an ASCII-lowercased twin used for
+//! matching. A query runs a cheap subsequence prefilter over that arena and
+//! fuzzy-scores the survivors (see [`crate::fuzzy`]), split across the rayon
+//! pool.
+//!
+//! Incremental updates append to the arenas and tombstone removed records;
+//! the dead space is reclaimed when the index is next persisted.
+
+mod crawl;
+mod rules;
+mod store;
+mod watch;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::error::{Error, Result};
+use crate::fuzzy::Pattern;
+
+pub use crawl::{crawl, RawEntry, Skipped};
+pub use rules::IgnoreSettings;
+pub use watch::Watcher;
+
+const SEPARATOR: char = '\0';
+const INDEX_FILE: &str = "index.bin";
+/// Records scanned per rayon task when querying.
+const QUERY_CHUNK: usize = 16 * 1024;
+
+/// Which directories are indexed. Stored under `index` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexSettings {
+    pub roots: Vec<PathBuf>,
+    /// Apply filesystem changes as they happen instead of waiting for a rescan.
+    pub watch: bool,
+    /// Seconds between full rescans when watching is off or unavailable.
+    pub rescan_interval_secs: u64,
+    pub ignore: IgnoreSettings,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        let home = tauri::api::path::home_dir();
+        let roots = ["Desktop", "Documents", "Downloads"]
+            .iter()
+            .filter_map(|dir| home.as_ref().map(|home| home.join(dir)))
+            .collect();
+        Self {
+            roots,
+            watch: true,
+            rescan_interval_secs: 15 * 60,
+            ignore: IgnoreSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    dir: u32,
+    /// Byte offset of the name in the arenas.
+    name: u32,
+    name_len: u16,
+    /// Byte offset of the extension within the name, or `name_len` if none.
+    ext: u16,
+    is_dir: bool,
+    /// Tombstone left by an incremental removal.
+    removed: bool,
+    size: u64,
+    mtime: i64,
+}
+
+/// Reverse maps needed to apply incremental changes. Built on the first
+/// change rather than at load so a read-only session never pays for them.
+#[derive(Debug, Default)]
+struct Lookup {
+    dir_ids: HashMap<String, u32>,
+    children: HashMap<u32, Vec<u32>>,
+}
+
+/// The index itself. Replaced wholesale when a rebuild finishes and patched in
+/// place by the watcher in between.
+#[derive(Debug, Default)]
+pub struct Index {
+    roots: Vec<PathBuf>,
+    /// Fingerprint of the ignore rules the index was built with.
+    rules: String,
+    built_at: u64,
+    dirs: Vec<String>,
+    names: String,
+    lower: String,
+    records: Vec<Record>,
+    removed: usize,
+    lookup: Option<Lookup>,
+}
+
+/// Borrowed view of one indexed entry.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    pub dir: &'a str,
+    pub name: &'a str,
+}
+
+impl Entry<'_> {
+    pub fn path(&self) -> PathBuf {
+        Path::new(self.dir).join(self.name)
+    }
+}
+
+/// A query match: the entry id and its rank (higher is better).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hit {
+    pub score: i64,
+    pub id: u32,
+}
+
+fn extension_offset(name: &str) -> usize {
+    match name.rfind('.') {
+        // A leading dot marks a hidden file, not an extension.
+        Some(0) | None => name.len(),
+        Some(i) => i + 1,
+    }
+}
+
+impl Index {
+    /// Pack crawled entries. Names longer than `u16::MAX` bytes cannot occur
+    /// on supported filesystems and are dropped defensively.
+    pub fn build(roots: Vec<PathBuf>, rules: String, entries: Vec<RawEntry>) -> Self {
+        let mut index = Index {
+            roots,
+            rules,
+            built_at: now(),
+            ..Default::default()
+        };
+        let mut dir_ids: HashMap<PathBuf, u32> = HashMap::new();
+        for entry in entries {
+            if entry.name.len() > u16::MAX as usize || entry.name.contains(SEPARATOR) {
+                continue;
+            }
+            let Some(dir_str) = entry.dir.to_str() else {
+                continue;
+            };
+            let dir = *dir_ids.entry(entry.dir.clone()).or_insert_with(|| {
+                index.dirs.push(dir_str.to_string());
+                (index.dirs.len() - 1) as u32
+            });
+            let name = index.names.len() as u32;
+            index.names.push_str(&entry.name);
+            index.names.push(SEPARATOR);
+            index.records.push(Record {
+                dir,
+                name,
+                name_len: entry.name.len() as u16,
+                ext: extension_offset(&entry.name) as u16,
+                is_dir: entry.is_dir,
+                removed: false,
+                size: entry.size,
+                mtime: entry.mtime,
+            });
+        }
+        index.lower = index.names.to_ascii_lowercase();
+        index
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn rules(&self) -> &str {
+        &self.rules
+    }
+
+    pub fn built_at(&self) -> u64 {
+        self.built_at
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len() - self.removed
+    }
+
+    pub fn entry(&self, id: u32) -> Entry<'_> {
+        let r = &self.records[id as usize];
+        let start = r.name as usize;
+        Entry {
+            dir: &self.dirs[r.dir as usize],
+            name: &self.names[start..start + r.name_len as usize],
+        }
+    }
+
+    fn name(&self, r: &Record) -> &str {
+        let start = r.name as usize;
+        &self.names[start..start + r.name_len as usize]
+    }
+
+    fn lower_name(&self, r: &Record) -> &str {
+        let start = r.name as usize;
+        &self.lower[start..start + r.name_len as usize]
+    }
+
+    fn ensure_lookup(&mut self) {
+        if self.lookup.is_some() {
+            return;
+        }
+        let mut lookup = Lookup::default();
+        for (id, dir) in self.dirs.iter().enumerate() {
+            lookup.dir_ids.insert(dir.clone(), id as u32);
+        }
+        for (id, r) in self.records.iter().enumerate() {
+            if !r.removed {
+                lookup.children.entry(r.dir).or_default().push(id as u32);
+            }
+        }
+        self.lookup = Some(lookup);
+    }
+
+    fn find(&self, dir: u32, name: &str) -> Option<u32> {
+        let children = self.lookup.as_ref()?.children.get(&dir)?;
+        children.iter().copied().find(|&id| {
+            let r = &self.records[id as usize];
+            !r.removed && self.name(r) == name
+        })
+    }
+
+    /// Insert an entry, or refresh its metadata if it is already indexed.
+    pub fn upsert(&mut self, entry: &RawEntry) {
+        if entry.name.len() > u16::MAX as usize || entry.name.contains(SEPARATOR) {
+            return;
+        }
+        let Some(dir_str) = entry.dir.to_str() else {
+            return;
+        };
+        self.ensure_lookup();
+        let lookup = self.lookup.as_mut().unwrap();
+        let dir = match lookup.dir_ids.get(dir_str) {
+            Some(&dir) => dir,
+            None => {
+                self.dirs.push(dir_str.to_string());
+                let dir = (self.dirs.len() - 1) as u32;
+                lookup.dir_ids.insert(dir_str.to_string(), dir);
+                dir
+            }
+        };
+
+        if let Some(id) = self.find(dir, &entry.name) {
+            let r = &mut self.records[id as usize];
+            r.is_dir = entry.is_dir;
+            r.size = entry.size;
+            r.mtime = entry.mtime;
+            return;
+        }
+
+        let name = self.names.len() as u32;
+        self.names.push_str(&entry.name);
+        self.names.push(SEPARATOR);
+        self.lower.push_str(&entry.name.to_ascii_lowercase());
+        self.lower.push(SEPARATOR);
+        let id = self.records.len() as u32;
+        self.records.push(Record {
+            dir,
+            name,
+            name_len: entry.name.len() as u16,
+            ext: extension_offset(&entry.name) as u16,
+            is_dir: entry.is_dir,
+            removed: false,
+            size: entry.size,
+            mtime: entry.mtime,
+        });
+        let lookup = self.lookup.as_mut().unwrap();
+        lookup.children.entry(dir).or_default().push(id);
+    }
+
+    /// Remove `path` and, if it was a directory, everything indexed below it.
+    /// Returns the number of entries removed.
+    pub fn remove(&mut self, path: &Path) -> usize {
+        let Some(path_str) = path.to_str() else {
+            return 0;
+        };
+        self.ensure_lookup();
+        let mut ids = Vec::new();
+
+        let parent = path.parent().and_then(Path::to_str);
+        let name = path.file_name().and_then(|n| n.to_str());
+        if let (Some(parent), Some(name)) = (parent, name) {
+            let lookup = self.lookup.as_ref().unwrap();
+            if let Some(id) = lookup.dir_ids.get(parent).and_then(|&d| self.find(d, name)) {
+                ids.push(id);
+            }
+        }
+
+        let lookup = self.lookup.as_mut().unwrap();
+        for (dir, dir_path) in self.dirs.iter().enumerate() {
+            let below = dir_path
+                .strip_prefix(path_str)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(MAIN_SEPARATOR));
+            if below {
+                if let Some(children) = lookup.children.remove(&(dir as u32)) {
+                    ids.extend(children);
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for id in ids {
+            let r = &mut self.records[id as usize];
+            if !r.removed {
+                r.removed = true;
+                removed += 1;
+            }
+        }
+        self.removed += removed;
+        removed
+    }
+
+    /// Drop tombstoned records and the arena space their names occupied.
+    pub fn compact(&mut self) {
+        if self.removed == 0 {
+            return;
+        }
+        let mut names = String::with_capacity(self.names.len());
+        let mut records = Vec::with_capacity(self.len());
+        for r in self.records.iter().filter(|r| !r.removed) {
+            let mut r = *r;
+            let old = r.name as usize;
+            r.name = names.len() as u32;
+            names.push_str(&self.names[old..old + r.name_len as usize]);
+            names.push(SEPARATOR);
+            records.push(r);
+        }
+        self.lower = names.to_ascii_lowercase();
+        self.names = names;
+        self.records = records;
+        self.removed = 0;
+        self.lookup = None;
+    }
+
+    /// Return the `limit` best entries whose name fuzzily matches every term
+    /// of `pattern`.
+    pub fn search(&self, pattern: &Pattern, limit: usize) -> Vec<Hit> {
+        self.search_until(pattern, limit, || false)
+            .unwrap_or_default()
+    }
+
+    /// Like `search`, but gives up once `cancelled` returns true, which is
+    /// checked before each chunk of records. `None` when cancelled.
+    pub fn search_until<F>(&self, pattern: &Pattern, limit: usize, cancelled: F) -> Option<Vec<Hit>>
+    where
+        F: Fn() -> bool + Sync,
+    {
+        if pattern.is_empty() || limit == 0 {
+            return Some(Vec::new());
+        }
+        let mut hits: Vec<Hit> = self
+            .records
+            .par_chunks(QUERY_CHUNK)
+            .enumerate()
+            .map(|(chunk, records)| {
+                if cancelled() {
+                    return None;
+                }
+                let base = chunk * QUERY_CHUNK;
+                Some(self.scan_chunk(pattern, records, base, limit))
+            })
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        hits.sort_unstable_by(|a, b| b.cmp(a));
+        hits.truncate(limit);
+        Some(hits)
+    }
+
+    fn scan_chunk(
+        &self,
+        pattern: &Pattern,
+        records: &[Record],
+        base: usize,
+        limit: usize,
+    ) -> Vec<Hit> {
+        let ceiling = pattern.max_score();
+        let mut top: BinaryHeap<Reverse<Hit>> = BinaryHeap::with_capacity(limit + 1);
+        for (i, r) in records.iter().enumerate() {
+            if r.removed {
+                continue;
+            }
+            // Once the heap is full, names too long to beat its minimum even
+            // with a perfect match are skipped without scoring.
+            if let Some(Reverse(min)) = top.peek() {
+                if top.len() == limit && rank(ceiling, r.name_len) <= min.score {
+                    continue;
+                }
+            }
+            // The byte-level subsequence check rejects most names before the
+            // alignment runs.
+            if !pattern.prefilter(self.lower_name(r).as_bytes()) {
+                continue;
+            }
+            let Some(score) = pattern.score(self.name(r)) else {
+                continue;
+            };
+            top.push(Reverse(Hit {
+                score: rank(score, r.name_len),
+                id: (base + i) as u32,
+            }));
+            if top.len() > limit {
+                top.pop();
+            }
+        }
+        top.into_iter().map(|Reverse(hit)| hit).collect()
+    }
+}
+
+/// Fold the match score and name length into one sortable key, so shorter
+/// names break ties between equally good matches.
+fn rank(score: i64, name_len: u16) -> i64 {
+    const LENGTH_SLOTS: i64 = 1 << 10;
+    score * LENGTH_SLOTS - (name_len as i64).min(LENGTH_SLOTS - 1)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub entries: usize,
+    pub roots: Vec<PathBuf>,
+    pub built_at: u64,
+    pub building: bool,
+    pub watching: bool,
+}
+
+/// A filesystem change reported by the watcher.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// `path` was created or modified; a new directory is crawled.
+    Upsert(PathBuf),
+    Remove(PathBuf),
+    /// The watcher lost events; only a full rescan is reliable.
+    Rescan,
+}
+
+struct Inner {
+    path: PathBuf,
+    roots: Mutex<Vec<PathBuf>>,
+    ignore: IgnoreSettings,
+    index: RwLock<Index>,
+    building: AtomicBool,
+    /// A rebuild was requested while one was running.
+    queued: AtomicBool,
+    /// Changes applied while a build crawls, which the crawl may have missed.
+    /// Replayed onto the new index once it is swapped in.
+    missed: Mutex<Vec<Change>>,
+    watching: AtomicBool,
+    rescanning: AtomicBool,
+    /// Set when incremental changes have not been persisted yet.
+    dirty: AtomicBool,
+    /// Told when a build starts or finishes and when watching stops or
+    /// starts.
+    listener: OnceLock<Box<dyn Fn() + Send + Sync>>,
+}
+
+/// Managed owner of the live index and its on-disk copy.
+#[derive(Clone)]
+pub struct Indexer(Arc<Inner>);
+
+impl Indexer {
+    /// Load the persisted index from `data_dir` if it was built with the same
+    /// roots and ignore rules; otherwise start a background build.
+    pub fn open(data_dir: &Path, roots: Vec<PathBuf>, ignore: IgnoreSettings) -> Self {
+        let indexer = Self::load(data_dir, roots, ignore);
+        if indexer.is_built() {
+            log::info!(target: "index", "loaded {} entries", indexer.read().len());
+        } else {
+            indexer.rebuild();
+        }
+        indexer
+    }
+
+    /// Like `open`, but never starts a build: without a usable persisted
+    /// index the indexer starts out empty. For one-off commands.
+    pub fn load(data_dir: &Path, roots: Vec<PathBuf>, ignore: IgnoreSettings) -> Self {
+        let path = data_dir.join(INDEX_FILE);
+        let fingerprint = ignore.fingerprint();
+        let loaded = match store::load(&path) {
+            Ok(index) if index.roots() == roots.as_slice() && index.rules() == fingerprint => {
+                Some(index)
+            }
+            Ok(_) => {
+                log::info!(target: "index", "indexed roots or ignore rules changed; rebuilding");
+                None
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!(target: "index", "discarding unreadable {}: {e}", path.display());
+                None
+            }
+        };
+        Self(Arc::new(Inner {
+            path,
+            roots: Mutex::new(roots),
+            ignore,
+            index: RwLock::new(loaded.unwrap_or_default()),
+            building: AtomicBool::new(false),
+            queued: AtomicBool::new(false),
+            missed: Mutex::new(Vec::new()),
+            watching: AtomicBool::new(false),
+            rescanning: AtomicBool::new(false),
+            dirty: AtomicBool::new(false),
+            listener: OnceLock::new(),
+        }))
+    }
+
+    /// Whether the index has been built or loaded, as opposed to empty.
+    pub fn is_built(&self) -> bool {
+        self.read().built_at() != 0
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Index> {
+        self.0.index.read().unwrap()
+    }
+
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.0.roots.lock().unwrap().clone()
+    }
+
+    pub fn status(&self) -> IndexStatus {
+        let index = self.read();
+        IndexStatus {
+            entries: index.len(),
+            roots: self.roots(),
+            built_at: index.built_at(),
+            building: self.0.building.load(Ordering::SeqCst),
+            watching: self.0.watching.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Call `listener` whenever `building` or `watching` in the status
+    /// changes. It may run with the indexer's or the watcher's locks held,
+    /// so it should hand the work off rather than read them back. Only the
+    /// first listener is kept.
+    pub fn on_status_change(&self, listener: impl Fn() + Send + Sync + 'static) {
+        let _ = self.0.listener.set(Box::new(listener));
+    }
+
+    fn status_changed(&self) {
+        if let Some(listener) = self.0.listener.get() {
+            listener();
+        }
+    }
+
+    pub(crate) fn set_watching(&self, watching: bool) {
+        if self.0.watching.swap(watching, Ordering::SeqCst) != watching {
+            self.status_changed();
+        }
+    }
+
+    /// Crawl the roots on a background thread, then persist and swap in the
+    /// new index. A request while a build is running queues another build
+    /// after it.
+    pub fn rebuild(&self) {
+        if self.0.building.swap(true, Ordering::SeqCst) {
+            self.0.queued.store(true, Ordering::SeqCst);
+            return;
+        }
+        self.status_changed();
+        let indexer = self.clone();
+        thread::spawn(move || indexer.build());
+    }
+
+    /// Like `rebuild`, but on the calling thread, returning once the new
+    /// index is in place.
+    pub fn rebuild_blocking(&self) {
+        if self.0.building.swap(true, Ordering::SeqCst) {
+            self.0.queued.store(true, Ordering::SeqCst);
+            return;
+        }
+        self.status_changed();
+        self.build();
+    }
+
+    /// The body of a rebuild, run again for each request queued meanwhile;
+    /// the caller has set `building`.
+    fn build(&self) {
+        loop {
+            self.0.queued.store(false, Ordering::SeqCst);
+            self.build_once();
+            self.0.building.store(false, Ordering::SeqCst);
+            // A request queued after the check above starts its own build.
+            if !self.0.queued.load(Ordering::SeqCst) || self.0.building.swap(true, Ordering::SeqCst)
+            {
+                break;
+            }
+        }
+        self.status_changed();
+    }
+
+    fn build_once(&self) {
+        // Changes from before the crawl starts are in it anyway.
+        self.0.missed.lock().unwrap().clear();
+        let roots = self.roots();
+        let ignore = &self.0.ignore;
+        let entries = crawl(&roots, ignore);
+        let index = Index::build(roots, ignore.fingerprint(), entries);
+        log::info!(target: "index", "indexed {} entries", index.len());
+        if let Err(e) = store::save(&index, &self.0.path) {
+            log::warn!(target: "index", "could not persist index: {e}");
+        }
+        let missed = {
+            // Taken under the write lock, so every change lands either in the
+            // list or directly in the new index.
+            let mut live = self.0.index.write().unwrap();
+            *live = index;
+            std::mem::take(&mut *self.0.missed.lock().unwrap())
+        };
+        self.0.dirty.store(false, Ordering::SeqCst);
+        if !missed.is_empty() {
+            self.apply(&missed);
+        }
+    }
+
+    /// Crawl `path` again, replacing whatever the index held at and below
+    /// it. A root is rebuilt like the whole index, but on the calling thread.
+    pub fn refresh(&self, path: &Path) -> Result<()> {
+        let roots = self.roots();
+        if roots.iter().any(|root| root == path) {
+            self.rebuild_blocking();
+            return Ok(());
+        }
+        if !roots.iter().any(|root| path.starts_with(root)) {
+            return Err(Error::NotIndexed(path.to_path_buf()));
+        }
+        self.apply(&[
+            Change::Remove(path.to_path_buf()),
+            Change::Upsert(path.to_path_buf()),
+        ]);
+        Ok(())
+    }
+
+    /// Apply a batch of watcher changes under a single write lock.
+    pub fn apply(&self, changes: &[Change]) {
+        if changes.iter().any(|c| matches!(c, Change::Rescan)) {
+            self.rebuild();
+            return;
+        }
+        // Crawl new directories before taking the lock so searches keep running.
+        let roots = self.roots();
+        let mut rules = HashMap::new();
+        let mut upserts = Vec::new();
+        for change in changes {
+            let Change::Upsert(path) = change else {
+                continue;
+            };
+            let Some(root) = roots.iter().find(|root| path.starts_with(root)) else {
+                continue;
+            };
+            let rules = rules
+                .entry(root)
+                .or_insert_with(|| rules::Rules::new(root, &self.0.ignore));
+            if rules.check_path(root, path).is_some() {
+                continue;
+            }
+            match crawl::stat(path) {
+                Some(entry) if entry.is_dir => {
+                    upserts.extend(crawl::crawl_subtree(rules, root, path));
+                    upserts.push(entry);
+                }
+                Some(entry) => upserts.push(entry),
+                None => {}
+            }
+        }
+
+        // Removals go first: upserts were stat'ed just now, so they reflect
+        // the final state even when a path was deleted and recreated.
+        let mut index = self.0.index.write().unwrap();
+        for change in changes {
+            if let Change::Remove(path) = change {
+                index.remove(path);
+            }
+        }
+        for entry in &upserts {
+            index.upsert(entry);
+        }
+        if self.0.building.load(Ordering::SeqCst) {
+            self.0.missed.lock().unwrap().extend_from_slice(changes);
+        }
+        self.0.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Compact and write the index if incremental changes are pending.
+    pub fn persist(&self) {
+        if !self.0.dirty.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let mut index = self.0.index.write().unwrap();
+        index.compact();
+        if let Err(e) = store::save(&index, &self.0.path) {
+            log::warn!(target: "index", "could not persist index: {e}");
+        }
+    }
+
+    /// Rebuild from scratch every `interval`. Used when watching is off or the
+    /// kernel refused to watch every directory. Only the first call starts
+    /// the loop.
+    pub fn spawn_rescan(&self, interval: Duration) {
+        if self.0.rescanning.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let indexer = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            indexer.rebuild();
+        });
+    }
+}
+
+#[tauri::command]
+pub fn index_status(indexer: State<'_, Indexer>) -> IndexStatus {
+    indexer.status()
+}
+
+/// What a crawl of one root would index, and what it would skip and why.
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub root: PathBuf,
+    pub included_total: usize,
+    /// The first `limit` included paths.
+    pub included: Vec<PathBuf>,
+    pub excluded: Vec<Skipped>,
+}
+
+/// Crawl every configured root without touching the index. Skipped
+/// directories are reported once; nothing below them is visited.
+#[tauri::command]
+pub async fn index_dry_run(
+    indexer: State<'_, Indexer>,
+    limit: Option<usize>,
+) -> Result<Vec<DryRunReport>> {
+    let indexer = indexer.inner().clone();
+    let limit = limit.unwrap_or(1000);
+    tauri::async_runtime::spawn_blocking(move || {
+        indexer
+            .roots()
+            .into_iter()
+            .map(|root| {
+                let crawl = crawl::crawl_root(&root, &indexer.0.ignore, true);
+                DryRunReport {
+                    included_total: crawl.entries.len(),
+                    included: crawl
+                        .entries
+                        .iter()
+                        .take(limit)
+                        .map(|e| e.dir.join(&e.name))
+                        .collect(),
+                    excluded: crawl.skipped,
+                    root,
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| Error::Task(e.to_string()))
+}
+
+#[tauri::command]
+pub fn rebuild_index(indexer: State<'_, Indexer>) -> Result<()> {
+    indexer.rebuild();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// An indexer over a fresh root, built once, and the root.
+    fn indexer(data: &TempDir, root: &TempDir) -> Indexer {
+        let roots = vec![root.path().to_path_buf()];
+        let indexer = Indexer::load(data.path(), roots, IgnoreSettings::default());
+        indexer.rebuild_blocking();
+        indexer
+    }
+
+    fn paths(indexer: &Indexer) -> Vec<PathBuf> {
+        let index = indexer.read();
+        let mut paths: Vec<PathBuf> = (0..index.records.len())
+            .filter(|&id| !index.records[id].removed)
+            .map(|id| index.entry(id as u32).path())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// The target of under 10 ms per query on a million entries, on a machine
+    /// with a few cores for rayon to split the scan across. Timing depends on
+    /// the machine and the build, so run it on demand:
+    /// `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn searches_a_million_entries_quickly() {
+        let kinds = ["main", "lib", "util", "notes"];
+        let entries = (0..1_000_000)
+            .map(|i| RawEntry {
+                dir: PathBuf::from(format!("/home/user/projects/p{}/src", i % 1000)),
+                name: format!("{}_{i:07}.rs", kinds[i % kinds.len()]),
+                size: 0,
+                mtime: 0,
+                is_dir: false,
+            })
+            .collect();
+        let index = Index::build(vec![PathBuf::from("/home/user")], String::new(), entries);
+        let pattern = Pattern::parse("util_04201");
+        // The first query also starts the rayon pool.
+        index.search(&pattern, 50);
+
+        let started = std::time::Instant::now();
+        let hits = index.search(&pattern, 50);
+        let elapsed = started.elapsed();
+        assert!(!hits.is_empty());
+        assert!(elapsed < Duration::from_millis(10), "took {elapsed:?}");
+    }
+
+    #[test]
+    fn create() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let indexer = indexer(&data, &root);
+        assert!(paths(&indexer).is_empty());
+
+        let file = root.path().join("notes.txt");
+        fs::write(&file, "x").unwrap();
+        let dir = root.path().join("project");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        indexer.apply(&[Change::Upsert(file.clone()), Change::Upsert(dir.clone())]);
+
+        let mut expected = vec![file, dir.clone(), dir.join("src"), dir.join("src/main.rs")];
+        expected.sort();
+        assert_eq!(paths(&indexer), expected);
+        let hits = indexer.read().search(&Pattern::parse("main"), 10);
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn rename() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let old = root.path().join("draft.txt");
+        fs::write(&old, "x").unwrap();
+        let indexer = indexer(&data, &root);
+        assert_eq!(paths(&indexer), vec![old.clone()]);
+
+        let new = root.path().join("final.txt");
+        fs::rename(&old, &new).unwrap();
+        indexer.apply(&[Change::Remove(old), Change::Upsert(new.clone())]);
+        assert_eq!(paths(&indexer), vec![new]);
+    }
+
+    #[test]
+    fn delete() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let dir = root.path().join("old");
+        fs::create_dir_all(dir.join("deep")).unwrap();
+        fs::write(dir.join("deep/a.txt"), "").unwrap();
+        let kept = root.path().join("kept.txt");
+        fs::write(&kept, "").unwrap();
+        let indexer = indexer(&data, &root);
+        assert_eq!(paths(&indexer).len(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+        indexer.apply(&[Change::Remove(dir)]);
+        assert_eq!(paths(&indexer), vec![kept]);
+        assert_eq!(indexer.read().len(), 1);
+    }
+
+    #[test]
+    fn ignored_changes() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let indexer = indexer(&data, &root);
+        let modules = root.path().join("node_modules");
+        fs::create_dir_all(&modules).unwrap();
+        fs::write(modules.join("lib.js"), "").unwrap();
+        indexer.apply(&[
+            Change::Upsert(modules.clone()),
+            Change::Upsert(modules.join("lib.js")),
+        ]);
+        assert!(paths(&indexer).is_empty());
+    }
+
+    #[test]
+    fn rebuilds_requested_during_a_build_are_queued() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let indexer = indexer(&data, &root);
+        // As if a build were crawling when the request came in.
+        indexer.0.building.store(true, Ordering::SeqCst);
+        indexer.rebuild();
+        assert!(indexer.0.queued.load(Ordering::SeqCst));
+
+        let file = root.path().join("late.txt");
+        fs::write(&file, "").unwrap();
+        indexer.build();
+        assert_eq!(paths(&indexer), vec![file]);
+        assert!(!indexer.0.queued.load(Ordering::SeqCst));
+        assert!(!indexer.0.building.load(Ordering::SeqCst));
+    }
+}
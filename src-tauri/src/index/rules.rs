@@ -0,0 +1,192 @@
+//! Decides which paths the crawler skips: hidden entries, the user's global
+//! exclude globs, and `.gitignore` / `.ignore` files found along the way.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::{Deserialize, Serialize};
+
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// Why a path was left out of the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SkipReason {
+    Hidden,
+    Exclude { pattern: String },
+    IgnoreFile { file: PathBuf, pattern: String },
+}
+
+/// Ignore configuration, stored under `index.ignore` in settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IgnoreSettings {
+    pub include_hidden: bool,
+    pub respect_ignore_files: bool,
+    /// Gitignore-style globs applied under every root, e.g. `node_modules/`.
+    pub excludes: Vec<String>,
+}
+
+impl Default for IgnoreSettings {
+    fn default() -> Self {
+        Self {
+            include_hidden: false,
+            respect_ignore_files: true,
+            excludes: vec!["node_modules/".into(), "__pycache__/".into()],
+        }
+    }
+}
+
+impl IgnoreSettings {
+    /// Stable string stored with the persisted index so a change in rules
+    /// forces a rebuild.
+    pub fn fingerprint(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Ignore files found in one directory, linked to those of its ancestors.
+/// Nodes are shared between sibling subtrees while crawling in parallel.
+#[derive(Default)]
+pub struct Scope {
+    /// `.ignore` before `.gitignore`, so the former takes precedence.
+    matchers: Vec<Gitignore>,
+    parent: Option<Arc<Scope>>,
+}
+
+impl Scope {
+    /// Extend `parent` with the ignore files in `dir`, if any.
+    pub fn enter(parent: &Arc<Scope>, dir: &Path, settings: &IgnoreSettings) -> Arc<Scope> {
+        if !settings.respect_ignore_files {
+            return parent.clone();
+        }
+        let matchers: Vec<Gitignore> = IGNORE_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .filter(|file| file.is_file())
+            .filter_map(|file| {
+                let (gitignore, err) = Gitignore::new(&file);
+                if let Some(e) = err {
+                    log::debug!(target: "index", "{}: {e}", file.display());
+                }
+                (!gitignore.is_empty()).then_some(gitignore)
+            })
+            .collect();
+        if matchers.is_empty() {
+            return parent.clone();
+        }
+        Arc::new(Scope {
+            matchers,
+            parent: Some(parent.clone()),
+        })
+    }
+
+    /// The deepest matching rule wins; a whitelist (`!pattern`) match keeps
+    /// the path even if a shallower file ignores it.
+    fn check(&self, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        let mut scope = Some(self);
+        while let Some(s) = scope {
+            for matcher in &s.matchers {
+                match matcher.matched(path, is_dir) {
+                    Match::Ignore(glob) => {
+                        return Some(SkipReason::IgnoreFile {
+                            file: glob
+                                .from()
+                                .map(Path::to_path_buf)
+                                .unwrap_or_else(|| matcher.path().to_path_buf()),
+                            pattern: glob.original().to_string(),
+                        })
+                    }
+                    Match::Whitelist(_) => return None,
+                    Match::None => {}
+                }
+            }
+            scope = s.parent.as_deref();
+        }
+        None
+    }
+}
+
+/// Compiled rules for one crawl root.
+pub struct Rules {
+    settings: IgnoreSettings,
+    excludes: Gitignore,
+}
+
+impl Rules {
+    pub fn new(root: &Path, settings: &IgnoreSettings) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &settings.excludes {
+            if let Err(e) = builder.add_line(None, pattern) {
+                log::warn!(target: "index", "ignoring invalid exclude `{pattern}`: {e}");
+            }
+        }
+        let excludes = builder.build().unwrap_or_else(|e| {
+            log::warn!(target: "index", "could not compile excludes: {e}");
+            Gitignore::empty()
+        });
+        Self {
+            settings: settings.clone(),
+            excludes,
+        }
+    }
+
+    pub fn settings(&self) -> &IgnoreSettings {
+        &self.settings
+    }
+
+    /// Check a directory entry found while crawling under `scope`.
+    pub fn check(
+        &self,
+        scope: &Scope,
+        path: &Path,
+        name: &str,
+        is_dir: bool,
+    ) -> Option<SkipReason> {
+        if !self.settings.include_hidden && name.starts_with('.') {
+            return Some(SkipReason::Hidden);
+        }
+        if let Match::Ignore(glob) = self.excludes.matched(path, is_dir) {
+            return Some(SkipReason::Exclude {
+                pattern: glob.original().to_string(),
+            });
+        }
+        scope.check(path, is_dir)
+    }
+
+    /// Build the scope for `dir`, a directory below `root`, by loading the
+    /// ignore files of every ancestor in between.
+    pub fn scope_for(&self, root: &Path, dir: &Path) -> Arc<Scope> {
+        let mut scope = Scope::enter(&Arc::new(Scope::default()), root, &self.settings);
+        if let Ok(relative) = dir.strip_prefix(root) {
+            let mut current = root.to_path_buf();
+            for component in relative.components() {
+                current.push(component);
+                scope = Scope::enter(&scope, &current, &self.settings);
+            }
+        }
+        scope
+    }
+
+    /// Check an arbitrary path below `root`, as reported by the watcher. Every
+    /// ancestor between the root and the path is evaluated, so a file inside
+    /// an ignored directory is skipped too.
+    pub fn check_path(&self, root: &Path, path: &Path) -> Option<SkipReason> {
+        let relative = path.strip_prefix(root).ok()?;
+        let mut scope = Arc::new(Scope::default());
+        let mut current = root.to_path_buf();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            scope = Scope::enter(&scope, &current, &self.settings);
+            current.push(component);
+            let is_dir = components.peek().is_some() || current.is_dir();
+            let name = component.as_os_str().to_string_lossy();
+            if let Some(reason) = self.check(&scope, &current, &name, is_dir) {
+                return Some(reason);
+            }
+        }
+        None
+    }
+}
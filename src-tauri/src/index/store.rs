@@ -0,0 +1,239 @@
+//! Binary on-disk format for [`Index`].
+//!
+//! Layout, all integers little-endian:
+//!
+//! ```text
+//! magic "QLIX" | version u32 | built_at u64
+//! rules:   len u32, utf-8 ignore-rule fingerprint
+//! roots:   count u32, then (len u32, utf-8 bytes) each
+//! dirs:    count u32, then (len u32, utf-8 bytes) each
+//! names:   len u32, `\0`-separated utf-8 arena
+//! records: count u32, then 29 bytes each:
+//!          dir u32 | name u32 | name_len u16 | ext u16 | is_dir u8 | size u64 | mtime i64
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Index, Record};
+
+const MAGIC: &[u8; 4] = b"QLIX";
+const VERSION: u32 = 2;
+/// Bytes per record on disk.
+const RECORD_SIZE: usize = 29;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn save(index: &Index, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut buf = Vec::with_capacity(index.names.len() + index.records.len() * RECORD_SIZE + 1024);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&index.built_at.to_le_bytes());
+    write_str(&mut buf, &index.rules);
+
+    let roots: Vec<&str> = index.roots.iter().filter_map(|r| r.to_str()).collect();
+    write_strings(&mut buf, roots.iter().copied());
+    write_strings(&mut buf, index.dirs.iter().map(String::as_str));
+    write_str(&mut buf, &index.names);
+
+    // Tombstones are skipped; their names stay in the arena as dead space
+    // until the index is compacted.
+    buf.extend_from_slice(&(index.len() as u32).to_le_bytes());
+    for r in index.records.iter().filter(|r| !r.removed) {
+        buf.extend_from_slice(&r.dir.to_le_bytes());
+        buf.extend_from_slice(&r.name.to_le_bytes());
+        buf.extend_from_slice(&r.name_len.to_le_bytes());
+        buf.extend_from_slice(&r.ext.to_le_bytes());
+        buf.push(r.is_dir as u8);
+        buf.extend_from_slice(&r.size.to_le_bytes());
+        buf.extend_from_slice(&r.mtime.to_le_bytes());
+    }
+
+    let tmp = path.with_extension("bin.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&buf)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_strings<'a>(buf: &mut Vec<u8>, strings: impl ExactSizeIterator<Item = &'a str>) {
+    buf.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    for s in strings {
+        write_str(buf, s);
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<Index> {
+    let data = fs::read(path)?;
+    let mut r = Reader {
+        data: &data,
+        pos: 0,
+    };
+    if r.take(4)? != MAGIC {
+        return Err(invalid("not an index file"));
+    }
+    if r.u32()? != VERSION {
+        return Err(invalid("unsupported index version"));
+    }
+    let built_at = r.u64()?;
+    let rules = r.string()?;
+    let roots = r.strings()?.into_iter().map(PathBuf::from).collect();
+    let dirs = r.strings()?;
+    let names = r.string()?;
+
+    let count = r.u32()? as usize;
+    // Checked before allocating, so a corrupt count cannot ask for more
+    // memory than the file could describe.
+    if count > r.remaining() / RECORD_SIZE {
+        return Err(invalid("truncated index file"));
+    }
+    let mut records = Vec::with_capacity(count);
+    for _ in 0..count {
+        let record = Record {
+            dir: r.u32()?,
+            name: r.u32()?,
+            name_len: r.u16()?,
+            ext: r.u16()?,
+            is_dir: r.take(1)?[0] != 0,
+            removed: false,
+            size: r.u64()?,
+            mtime: r.u64()? as i64,
+        };
+        let start = record.name as usize;
+        let end = start + record.name_len as usize;
+        let ext = start + record.ext as usize;
+        // `get` checks that both ends fall on character boundaries.
+        let out_of_bounds = record.dir as usize >= dirs.len()
+            || record.ext > record.name_len
+            || names.get(start..end).is_none()
+            || !names.is_char_boundary(ext);
+        if out_of_bounds {
+            return Err(invalid("record out of bounds"));
+        }
+        records.push(record);
+    }
+
+    let lower = names.to_ascii_lowercase();
+    Ok(Index {
+        roots,
+        rules,
+        built_at,
+        dirs,
+        names,
+        lower,
+        records,
+        ..Default::default()
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| invalid("truncated index file"))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid utf-8"))
+    }
+
+    fn strings(&mut self) -> io::Result<Vec<String>> {
+        let count = self.u32()? as usize;
+        (0..count).map(|_| self.string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::RawEntry;
+    use super::*;
+
+    fn index() -> Index {
+        let entries = ["notes.txt", "report.pdf"]
+            .into_iter()
+            .map(|name| RawEntry {
+                dir: PathBuf::from("/home/user"),
+                name: name.into(),
+                size: 1,
+                mtime: 2,
+                is_dir: false,
+            })
+            .collect();
+        Index::build(vec![PathBuf::from("/home/user")], "rules".into(), entries)
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.bin");
+        save(&index(), &path).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.rules(), "rules");
+        assert_eq!(loaded.entry(1).path(), Path::new("/home/user/report.pdf"));
+    }
+
+    #[test]
+    fn corrupt_files_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.bin");
+        save(&index(), &path).unwrap();
+        let data = fs::read(&path).unwrap();
+        let records = data.len() - 2 * RECORD_SIZE;
+
+        // A huge record count with nothing behind it.
+        let mut huge = data[..records].to_vec();
+        huge[records - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &huge).unwrap();
+        assert_eq!(load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Cut off in the middle of a record.
+        fs::write(&path, &data[..data.len() - 3]).unwrap();
+        assert!(load(&path).is_err());
+
+        // A name offset past the end of the arena.
+        let mut bad = data.clone();
+        bad[records + 4..records + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bad).unwrap();
+        assert!(load(&path).is_err());
+    }
+}
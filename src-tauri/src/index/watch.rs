@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{ErrorKind, Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use super::crawl;
+use super::rules::Rules;
+use super::{Change, Indexer};
+
+/// Events arriving within this window are applied as one batch.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+/// Incremental changes are persisted once the tree has been quiet this long.
+const PERSIST_AFTER: Duration = Duration::from_secs(30);
+
+/// Keeps the index in sync with the filesystem. Dropping it stops watching.
+pub struct Watcher {
+    indexer: Indexer,
+    roots: Vec<PathBuf>,
+    rescan: Duration,
+    inner: Arc<Mutex<Watches>>,
+}
+
+impl Watcher {
+    /// Watch every directory the crawler would index, one at a time, and feed
+    /// changes into `indexer`; ignored trees cost no watches. Directories
+    /// created later are watched as they appear. Fails if the kernel runs out
+    /// of watches (`fs.inotify.max_user_watches` on Linux); callers fall back
+    /// to periodic rescans. Running out later makes the watcher fall back to
+    /// rescanning every `rescan` itself.
+    pub fn start(indexer: Indexer, roots: &[PathBuf], rescan: Duration) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+        let notify = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        let mut watches = Watches {
+            notify: Some(notify),
+            dirs: HashSet::new(),
+            paused: false,
+        };
+        watches.add_roots(&indexer, roots)?;
+
+        // The thread only holds a weak reference, so dropping this drops the
+        // notify watcher and with it the sender, which ends the thread.
+        let inner = Arc::new(Mutex::new(watches));
+        let (watched, shared) = (indexer.clone(), Arc::downgrade(&inner));
+        let thread_roots = roots.to_vec();
+        thread::Builder::new()
+            .name("index-watcher".into())
+            .spawn(move || run(watched, rx, shared, thread_roots, rescan))?;
+        Ok(Self {
+            indexer,
+            roots: roots.to_vec(),
+            rescan,
+            inner,
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.lock().unwrap().paused
+    }
+
+    /// Whether the watcher still runs, rather than having given way to
+    /// periodic rescans.
+    pub fn is_available(&self) -> bool {
+        self.inner.lock().unwrap().notify.is_some()
+    }
+
+    /// Stop watching the roots until `resume`.
+    pub fn pause(&self) -> notify::Result<()> {
+        let mut watches = self.inner.lock().unwrap();
+        if watches.notify.is_none() {
+            return Err(unavailable());
+        }
+        if watches.paused {
+            return Ok(());
+        }
+        watches.clear();
+        watches.paused = true;
+        self.indexer.set_watching(false);
+        log::info!(target: "index", "file watching paused");
+        Ok(())
+    }
+
+    /// Watch the roots again. Changes made while paused were never seen, so
+    /// the index is rebuilt.
+    pub fn resume(&self) -> notify::Result<()> {
+        let mut watches = self.inner.lock().unwrap();
+        if watches.notify.is_none() {
+            return Err(unavailable());
+        }
+        if !watches.paused {
+            return Ok(());
+        }
+        watches.paused = false;
+        if let Err(e) = watches.add_roots(&self.indexer, &self.roots) {
+            watches.give_up(&self.indexer, self.rescan);
+            return Err(e);
+        }
+        self.indexer.set_watching(true);
+        self.indexer.rebuild();
+        log::info!(target: "index", "file watching resumed");
+        Ok(())
+    }
+}
+
+fn unavailable() -> notify::Error {
+    notify::Error::generic("file watching stopped at the watch limit")
+}
+
+/// The directories being watched, each on its own.
+struct Watches {
+    /// `None` once dropped for hitting the watch limit.
+    notify: Option<RecommendedWatcher>,
+    dirs: HashSet<PathBuf>,
+    paused: bool,
+}
+
+impl Watches {
+    fn add_roots(&mut self, indexer: &Indexer, roots: &[PathBuf]) -> notify::Result<()> {
+        for root in roots.iter().filter(|root| root.is_dir()) {
+            self.add(&Rules::new(root, &indexer.0.ignore), root, root)?;
+        }
+        Ok(())
+    }
+
+    /// Watch `dir` and the directories below it that are not ignored. Only
+    /// running out of watches is an error; a directory that cannot be watched
+    /// otherwise, say because it is already gone again, is skipped.
+    fn add(&mut self, rules: &Rules, root: &Path, dir: &Path) -> notify::Result<()> {
+        let Some(notify) = self.notify.as_mut() else {
+            return Ok(());
+        };
+        for dir in crawl::watched_dirs(rules, root, dir) {
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            match notify.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir);
+                }
+                Err(e) if matches!(e.kind, ErrorKind::MaxFilesWatch) => return Err(e),
+                Err(e) => log::debug!(target: "index", "not watching {}: {e}", dir.display()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching `dir` and everything below it.
+    fn remove(&mut self, dir: &Path) {
+        let Some(notify) = self.notify.as_mut() else {
+            return;
+        };
+        if !self.dirs.contains(dir) {
+            return;
+        }
+        self.dirs.retain(|watched| {
+            let below = watched.starts_with(dir);
+            if below {
+                let _ = notify.unwatch(watched);
+            }
+            !below
+        });
+    }
+
+    fn clear(&mut self) {
+        if let Some(notify) = self.notify.as_mut() {
+            for dir in self.dirs.drain() {
+                let _ = notify.unwatch(&dir);
+            }
+        }
+    }
+
+    /// Keep the watches in step with a batch of changes: watch directories
+    /// that appeared and forget those that went away.
+    fn follow(
+        &mut self,
+        indexer: &Indexer,
+        roots: &[PathBuf],
+        changes: &[Change],
+    ) -> notify::Result<()> {
+        if self.paused {
+            return Ok(());
+        }
+        let mut rules = HashMap::new();
+        for change in changes {
+            match change {
+                Change::Upsert(path) => {
+                    let is_dir = path.symlink_metadata().is_ok_and(|meta| meta.is_dir());
+                    if !is_dir || self.dirs.contains(path) {
+                        continue;
+                    }
+                    let Some(root) = roots.iter().find(|root| path.starts_with(root)) else {
+                        continue;
+                    };
+                    let rules = rules
+                        .entry(root)
+                        .or_insert_with(|| Rules::new(root, &indexer.0.ignore));
+                    self.add(rules, root, path)?;
+                }
+                Change::Remove(path) => self.remove(path),
+                // Picks up directories an edited ignore file no longer hides.
+                Change::Rescan => self.add_roots(indexer, roots)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the watcher after the kernel ran out of watches: some directories
+    /// went unwatched and more would, so rescan periodically instead.
+    fn give_up(&mut self, indexer: &Indexer, rescan: Duration) {
+        log::warn!(
+            target: "index",
+            "inotify watch limit reached; rescanning every {rescan:?} instead"
+        );
+        self.notify = None;
+        self.dirs.clear();
+        indexer.spawn_rescan(rescan);
+        indexer.rebuild();
+    }
+}
+
+fn run(
+    indexer: Indexer,
+    rx: mpsc::Receiver<notify::Result<Event>>,
+    inner: Weak<Mutex<Watches>>,
+    roots: Vec<PathBuf>,
+    rescan: Duration,
+) {
+    indexer.set_watching(true);
+    let mut pending = Vec::new();
+    let mut last_change = None;
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(Ok(event)) => {
+                pending.extend(changes(event));
+                continue;
+            }
+            Ok(Err(e)) if matches!(e.kind, ErrorKind::MaxFilesWatch) => {
+                if let Some(inner) = inner.upgrade() {
+                    inner.lock().unwrap().give_up(&indexer, rescan);
+                }
+                break;
+            }
+            Ok(Err(e)) => {
+                log::warn!(target: "index", "watch error: {e}");
+                pending.push(Change::Rescan);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if !pending.is_empty() {
+            let Some(inner) = inner.upgrade() else {
+                break;
+            };
+            let mut watches = inner.lock().unwrap();
+            if watches.follow(&indexer, &roots, &pending).is_err() {
+                watches.give_up(&indexer, rescan);
+                break;
+            }
+            drop(watches);
+            indexer.apply(&pending);
+            pending.clear();
+            last_change = Some(Instant::now());
+        }
+        if last_change.is_some_and(|at| at.elapsed() >= PERSIST_AFTER) {
+            indexer.persist();
+            last_change = None;
+        }
+    }
+    indexer.set_watching(false);
+    indexer.persist();
+}
+
+/// Translate a notify event into index changes.
+fn changes(event: Event) -> Vec<Change> {
+    let touches_ignore_file = event.paths.iter().any(|path| {
+        path.file_name()
+            .is_some_and(|name| name == ".gitignore" || name == ".ignore")
+    });
+    if event.need_rescan() || touches_ignore_file {
+        return vec![Change::Rescan];
+    }
+    let mut paths = event.paths.into_iter();
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Metadata(_)) => {
+            paths.map(Change::Upsert).collect()
+        }
+        EventKind::Remove(_) => paths.map(Change::Remove).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(Change::Remove).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => paths.map(Change::Upsert).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let (Some(from), Some(to)) = (paths.next(), paths.next()) else {
+                return Vec::new();
+            };
+            vec![Change::Remove(from), Change::Upsert(to)]
+        }
+        // Ambiguous events: let the current state of the path decide.
+        EventKind::Modify(_) | EventKind::Any => paths
+            .map(|path| {
+                if path.symlink_metadata().is_ok() {
+                    Change::Upsert(path)
+                } else {
+                    Change::Remove(path)
+                }
+            })
+            .collect(),
+        EventKind::Access(_) | EventKind::Other => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::Ordering;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::index::IgnoreSettings;
+
+    fn watched(watcher: &Watcher, root: &Path) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = watcher.inner.lock().unwrap().dirs.iter().cloned().collect();
+        dirs.sort();
+        dirs.iter()
+            .map(|dir| dir.strip_prefix(root).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn ignored_dirs_are_not_watched() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let root = root.path();
+        for dir in ["src/deep", "node_modules/pkg", ".git/objects"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let roots = vec![root.to_path_buf()];
+        let indexer = Indexer::load(data.path(), roots.clone(), IgnoreSettings::default());
+        let watcher = Watcher::start(indexer.clone(), &roots, Duration::from_secs(3600)).unwrap();
+        let paths = |dirs: &[&str]| dirs.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(watched(&watcher, root), paths(&["", "src", "src/deep"]));
+
+        fs::create_dir_all(root.join("src/new/inner")).unwrap();
+        fs::create_dir_all(root.join("node_modules/other")).unwrap();
+        let changes = [
+            Change::Upsert(root.join("src/new")),
+            Change::Upsert(root.join("node_modules/other")),
+        ];
+        let mut watches = watcher.inner.lock().unwrap();
+        watches.follow(&indexer, &roots, &changes).unwrap();
+        drop(watches);
+        assert_eq!(
+            watched(&watcher, root),
+            paths(&["", "src", "src/deep", "src/new", "src/new/inner"])
+        );
+
+        let mut watches = watcher.inner.lock().unwrap();
+        watches
+            .follow(&indexer, &roots, &[Change::Remove(root.join("src"))])
+            .unwrap();
+        drop(watches);
+        assert_eq!(watched(&watcher, root), paths(&[""]));
+    }
+
+    #[test]
+    fn watch_limit_falls_back_to_rescans() {
+        let (data, root) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let roots = vec![root.path().to_path_buf()];
+        let indexer = Indexer::load(data.path(), roots.clone(), IgnoreSettings::default());
+        let inner = Arc::new(Mutex::new(Watches {
+            notify: Some(notify::recommended_watcher(|_| {}).unwrap()),
+            dirs: HashSet::new(),
+            paused: false,
+        }));
+
+        let (tx, rx) = mpsc::channel();
+        tx.send(Err(notify::Error::new(ErrorKind::MaxFilesWatch)))
+            .unwrap();
+        let (watched, shared) = (indexer.clone(), Arc::downgrade(&inner));
+        // Returns without the sender being dropped: the watcher gave up.
+        thread::spawn(move || run(watched, rx, shared, roots, Duration::from_secs(3600)))
+            .join()
+            .unwrap();
+
+        assert!(inner.lock().unwrap().notify.is_none());
+        assert!(!indexer.status().watching);
+        assert!(indexer.0.rescanning.load(Ordering::SeqCst));
+        drop(tx);
+    }
+}
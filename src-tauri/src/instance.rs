@@ -0,0 +1,208 @@
+//! Single-instance lock over a Unix domain socket in the runtime directory.
+//!
+//! The first launcher binds the socket and keeps it for its lifetime. Later
+//! invocations connect instead and send one command per connection as a JSON
+//! line; the launcher answers with a JSON [`Reply`] line.
+//!
+//! Who owns the socket is settled by an `flock` on a lock file next to it,
+//! which the kernel releases when the launcher dies. Whoever holds it may
+//! replace a socket left behind by a crashed launcher without racing another
+//! launcher starting at the same time.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use crate::cli::{self, Command, Reply};
+
+const SOCKET_NAME: &str = "quicklauncher.sock";
+/// How long the launcher waits for a command to arrive.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long an invocation waits for the reply. Reindexing a directory is
+/// answered once it is done.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The bound socket of the running launcher.
+pub struct Lock {
+    listener: UnixListener,
+    path: PathBuf,
+    /// Holds the `flock` for as long as it is open.
+    file: File,
+}
+
+/// Managed state remembering the socket to remove on exit.
+struct SocketPath(PathBuf);
+
+/// `$XDG_RUNTIME_DIR/quicklauncher.sock`, or a private directory under the
+/// temp dir when there is no runtime dir.
+fn socket_path() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(dir).join(SOCKET_NAME));
+    }
+    // SAFETY: getuid cannot fail and has no preconditions.
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("quicklauncher-{uid}"));
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    // The temp dir is shared: someone else may have made the directory first,
+    // or put a symlink there, to have commands sent to their socket.
+    let meta = fs::symlink_metadata(&dir)?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is not a private directory of the current user",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(dir.join(SOCKET_NAME))
+}
+
+/// Open the lock file next to `socket` and try to `flock` it. `None` when
+/// another process holds the lock.
+fn try_lock(socket: &Path) -> io::Result<Option<File>> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(0o600)
+        .open(socket.with_extension("lock"))?;
+    // SAFETY: the descriptor is open for the duration of the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+    let e = io::Error::last_os_error();
+    if e.kind() == io::ErrorKind::WouldBlock {
+        Ok(None)
+    } else {
+        Err(e)
+    }
+}
+
+/// Send `command` to the running launcher. `None` when there is none.
+pub fn request(command: &Command) -> io::Result<Option<Reply>> {
+    let mut stream = match UnixStream::connect(socket_path()?) {
+        Ok(stream) => stream,
+        Err(e) if is_absent(&e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    serde_json::to_writer(&mut stream, command)?;
+    stream.write_all(b"\n")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(Some(serde_json::from_str(&reply)?))
+}
+
+/// No socket, or a stale one nobody listens on.
+fn is_absent(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// Take the lock. `None` when another launcher holds it, which can happen
+/// when two start at once.
+pub fn lock() -> io::Result<Option<Lock>> {
+    let path = socket_path()?;
+    let Some(file) = try_lock(&path)? else {
+        return Ok(None);
+    };
+    // Only a launcher holding the lock serves the socket, so one that is
+    // still there was left behind.
+    match fs::remove_file(&path) {
+        Ok(()) => log::info!("removed stale socket {}", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let listener = UnixListener::bind(&path)?;
+    Ok(Some(Lock {
+        listener,
+        path,
+        file,
+    }))
+}
+
+fn handle(app: &AppHandle, stream: UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = match serde_json::from_str::<Command>(&line) {
+        Ok(command) => {
+            log::info!("received {command:?}");
+            cli::execute(app, &command)
+        }
+        Err(e) => Reply::Error {
+            message: format!("malformed command: {e}"),
+        },
+    };
+    let mut out = serde_json::to_vec(&reply)?;
+    out.push(b'\n');
+    (&stream).write_all(&out)
+}
+
+impl Lock {
+    /// Answer commands for the rest of the process's life.
+    pub fn serve(self, app: AppHandle) {
+        app.manage(SocketPath(self.path));
+        let (listener, file) = (self.listener, self.file);
+        thread::spawn(move || {
+            // Never returns, so the lock is held until the process exits.
+            let _lock = file;
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("could not accept a command: {e}");
+                        continue;
+                    }
+                };
+                // A reindex can take a while; keep answering meanwhile.
+                let app = app.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle(&app, stream) {
+                        log::warn!("could not handle a command: {e}");
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Remove the socket so the next start does not have to detect it as stale.
+pub fn release(app: &AppHandle) {
+    if let Some(path) = app.try_state::<SocketPath>() {
+        let _ = fs::remove_file(&path.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn only_one_holder_of_the_lock() {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join(SOCKET_NAME);
+        let held = try_lock(&socket).unwrap();
+        assert!(held.is_some());
+        assert!(try_lock(&socket).unwrap().is_none());
+        drop(held);
+        assert!(try_lock(&socket).unwrap().is_some());
+    }
+}
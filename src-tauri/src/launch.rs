@@ -0,0 +1,304 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::apps::Apps;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::settings::SettingsStore;
+
+/// Extensions treated as executable content even without an exec bit, since
+/// the platform opener would run them rather than display them.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "appimage", "bat", "cmd", "com", "desktop", "exe", "msi", "ps1", "run", "sh",
+];
+
+/// What the `launch` command may open. Stored under `launch` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchSettings {
+    /// Only paths inside one of these directories can be opened.
+    pub allowed_roots: Vec<PathBuf>,
+    /// Lower-case extensions (without the dot) files must have. Empty allows
+    /// any extension.
+    pub allowed_extensions: Vec<String>,
+    /// Executables that may be run directly. Anything else that looks
+    /// executable is refused.
+    pub trusted_executables: Vec<PathBuf>,
+}
+
+impl Default for LaunchSettings {
+    fn default() -> Self {
+        Self {
+            allowed_roots: tauri::api::path::home_dir().into_iter().collect(),
+            allowed_extensions: Vec::new(),
+            trusted_executables: Vec::new(),
+        }
+    }
+}
+
+/// How a validated path should be started.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    /// Hand the path to the platform opener.
+    Open(PathBuf),
+    /// Run a trusted executable directly.
+    Run(PathBuf),
+}
+
+impl Target {
+    pub fn path(&self) -> &Path {
+        match self {
+            Target::Open(path) | Target::Run(path) => path,
+        }
+    }
+}
+
+fn denied(path: &Path, reason: impl Into<String>) -> Error {
+    Error::LaunchDenied {
+        path: path.to_path_buf(),
+        reason: reason.into(),
+    }
+}
+
+/// Canonicalize `path` and check that it lies inside one of the allowed
+/// roots. Symlinks are resolved first so a link inside an allowed root cannot
+/// point outside it.
+pub fn check_root(policy: &LaunchSettings, path: &Path) -> Result<PathBuf> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| denied(path, format!("cannot resolve path: {e}")))?;
+
+    let in_root = policy
+        .allowed_roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root));
+    if !in_root {
+        return Err(denied(&path, "outside the allowed roots"));
+    }
+    Ok(path)
+}
+
+/// Check `path` against the whole of `policy` and decide how to start it.
+pub fn validate(policy: &LaunchSettings, path: &Path) -> Result<Target> {
+    let path = check_root(policy, path)?;
+
+    if path.is_dir() {
+        return Ok(Target::Open(path));
+    }
+
+    if is_executable(&path) {
+        let trusted = policy
+            .trusted_executables
+            .iter()
+            .filter_map(|exe| exe.canonicalize().ok())
+            .any(|exe| exe == path);
+        return if trusted {
+            Ok(Target::Run(path))
+        } else {
+            Err(denied(&path, "executable is not trusted"))
+        };
+    }
+
+    if !policy.allowed_extensions.is_empty() {
+        let allowed = extension(&path)
+            .map(|ext| {
+                policy
+                    .allowed_extensions
+                    .iter()
+                    .any(|a| a.eq_ignore_ascii_case(&ext))
+            })
+            .unwrap_or(false);
+        if !allowed {
+            return Err(denied(&path, "extension is not allowed"));
+        }
+    }
+
+    Ok(Target::Open(path))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+fn is_executable(path: &Path) -> bool {
+    if extension(path).is_some_and(|ext| EXECUTABLE_EXTENSIONS.contains(&ext.as_str())) {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = path.metadata() {
+            return meta.permissions().mode() & 0o111 != 0;
+        }
+    }
+    false
+}
+
+pub fn opener(path: &Path) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]).arg(path);
+        cmd
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut cmd = Command::new("open");
+        cmd.arg(path);
+        cmd
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let mut cmd = Command::new("xdg-open");
+        cmd.arg(path);
+        cmd
+    }
+}
+
+/// Start `command` detached from the launcher and reap it in the background.
+pub fn spawn_detached(mut command: Command) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+pub fn start(target: &Target) -> Result<()> {
+    match target {
+        Target::Open(path) => spawn_detached(opener(path)),
+        Target::Run(path) => {
+            let mut cmd = Command::new(path);
+            if let Some(dir) = path.parent() {
+                cmd.current_dir(dir);
+            }
+            spawn_detached(cmd)
+        }
+    }
+}
+
+/// Open a search result. Paths of discovered `.desktop` entries start the
+/// application; anything else is validated against the launch policy.
+pub fn open(policy: &LaunchSettings, apps: &Apps, history: &History, path: &Path) -> Result<()> {
+    if let Some(app) = apps.find(path) {
+        app.launch(&[])?;
+        record(history, &app.path);
+        return Ok(());
+    }
+
+    let target = validate(policy, path)?;
+    log::info!("launching {target:?}");
+    start(&target)?;
+    record(history, target.path());
+    Ok(())
+}
+
+/// Note a launch in the history. A launch that succeeded should not be
+/// reported as failed just because the history could not be written.
+pub fn record(history: &History, path: &Path) {
+    if let Err(e) = history.record(path) {
+        log::warn!("could not record launch history: {e}");
+    }
+}
+
+/// Launch a search result.
+#[tauri::command]
+pub fn launch(
+    settings: State<'_, SettingsStore>,
+    apps: State<'_, Apps>,
+    history: State<'_, History>,
+    path: String,
+) -> Result<()> {
+    let policy = settings.get().launch.clone();
+    open(&policy, &apps, &history, Path::new(&path))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A policy allowing `root` only, and the canonical `root`.
+    fn policy(root: &Path) -> (LaunchSettings, PathBuf) {
+        let policy = LaunchSettings {
+            allowed_roots: vec![root.to_path_buf()],
+            allowed_extensions: Vec::new(),
+            trusted_executables: Vec::new(),
+        };
+        (policy, root.canonicalize().unwrap())
+    }
+
+    fn is_denied(result: Result<Target>) -> bool {
+        matches!(result, Err(Error::LaunchDenied { .. }))
+    }
+
+    #[test]
+    fn paths_stay_inside_the_roots() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        fs::write(dir.path().join("secret.txt"), "").unwrap();
+        symlink(dir.path().join("secret.txt"), root.join("link.txt")).unwrap();
+        let (policy, canonical) = policy(&root);
+
+        assert_eq!(
+            validate(&policy, &root.join("notes.txt")).unwrap(),
+            Target::Open(canonical.join("notes.txt"))
+        );
+        assert_eq!(validate(&policy, &root).unwrap(), Target::Open(canonical));
+        assert!(is_denied(validate(&policy, &dir.path().join("secret.txt"))));
+        assert!(is_denied(validate(&policy, &root.join("../secret.txt"))));
+        assert!(is_denied(validate(&policy, &root.join("link.txt"))));
+        assert!(is_denied(validate(&policy, &root.join("missing.txt"))));
+    }
+
+    #[test]
+    fn executables_must_be_trusted() {
+        let dir = TempDir::new().unwrap();
+        let tool = dir.path().join("tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = dir.path().join("install.SH");
+        fs::write(&script, "").unwrap();
+        let (mut policy, canonical) = policy(dir.path());
+
+        assert!(is_denied(validate(&policy, &tool)));
+        assert!(is_denied(validate(&policy, &script)));
+        policy.trusted_executables.push(tool.clone());
+        assert_eq!(
+            validate(&policy, &tool).unwrap(),
+            Target::Run(canonical.join("tool"))
+        );
+        assert!(is_denied(validate(&policy, &script)));
+    }
+
+    #[test]
+    fn extensions_are_allowed_regardless_of_case() {
+        let dir = TempDir::new().unwrap();
+        for name in ["report.PDF", "notes.txt", "README"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let (mut policy, _) = policy(dir.path());
+        policy.allowed_extensions = vec!["pdf".into()];
+
+        assert!(validate(&policy, &dir.path().join("report.PDF")).is_ok());
+        assert!(is_denied(validate(&policy, &dir.path().join("notes.txt"))));
+        assert!(is_denied(validate(&policy, &dir.path().join("README"))));
+        // Directories are opened whatever the extension list says.
+        assert!(validate(&policy, dir.path()).is_ok());
+    }
+}
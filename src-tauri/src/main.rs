@@ -1,34 +1,245 @@
-#![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
 
-use tauri::{Manager};
+mod actions;
+mod apps;
+mod autostart;
+mod backend;
+mod calc;
+mod cli;
+mod error;
+mod fuzzy;
+mod history;
+mod hotkey;
+mod icons;
+mod index;
+#[cfg(unix)]
+mod instance;
+mod launch;
+mod mime;
+mod plugins;
+mod providers;
+mod search;
+mod settings;
+mod trash;
+mod tray;
+mod window;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use tauri::{App, AppHandle, Manager, RunEvent};
+
+use crate::apps::Apps;
+use crate::backend::Backend;
+use crate::cli::{Cli, Command, PendingQuery};
+use crate::history::History;
+use crate::hotkey::HotkeyState;
+use crate::icons::Icons;
+use crate::index::{Indexer, Watcher};
+use crate::mime::MimeDb;
+use crate::plugins::Plugins;
+use crate::providers::{AppsProvider, FilesProvider, HistoryProvider, Providers};
+use crate::search::Searches;
+use crate::settings::SettingsStore;
 
 fn main() {
+    let cli = Cli::parse();
+    let commands = cli.commands();
+    // Only the launcher itself logs progress; a scripted command stays quiet
+    // unless something goes wrong.
+    let starts_launcher = commands.is_empty() || commands.iter().any(Command::needs_window);
+    let level = if starts_launcher { "info" } else { "warn" };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
+    let context = tauri::generate_context!();
+
+    // Only one launcher runs at a time. Commands go to it when it is running;
+    // those that need no window run right here otherwise.
+    if !starts_launcher {
+        std::process::exit(cli::dispatch(context.config(), &commands));
+    }
+    #[cfg(unix)]
+    let lock = match instance::lock() {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            // Starting the launcher again shows the running one.
+            let commands = if commands.is_empty() && !cli.hidden {
+                vec![Command::Show]
+            } else {
+                commands
+            };
+            std::process::exit(cli::dispatch(context.config(), &commands));
+        }
+        Err(e) => {
+            log::warn!("single-instance lock unavailable ({e}); starting anyway");
+            None
+        }
+    };
+
     tauri::Builder::default()
-        .setup(|app| {
-            // Optionally hide the window on startup; toggle via global shortcut.
-            if let Some(window) = app.get_window("main") {
-                let _ = window.hide();
-            }
+        .manage(HotkeyState::default())
+        .manage(PendingQuery::default())
+        .manage(Searches::default())
+        .setup(move |app| {
+            let config_dir = app
+                .path_resolver()
+                .app_config_dir()
+                .ok_or("could not resolve the app config directory")?;
+            app.manage(SettingsStore::load(&config_dir));
+            // The launcher starts hidden; the global shortcut shows it.
+            window::create(&app.handle())?;
+
+            // Register the toggle shortcut; a bad or taken accelerator falls back
+            // to a safe default instead of aborting startup.
+            hotkey::init(&app.handle());
+
+            let backend_settings = app.state::<SettingsStore>().get().backend.clone();
+            let backend = if backend_settings.enabled {
+                let root = backend_settings
+                    .root
+                    .clone()
+                    .or_else(|| default_backend_root(app))
+                    .ok_or("could not resolve the backend directory")?;
+                Backend::spawn(backend_settings, root)?
+            } else {
+                Backend::disabled()
+            };
+            app.manage(backend);
 
-            // Register a global shortcut: CmdOrCtrl+Space to toggle window visibility
-            let app_handle = app.handle();
-            let mut gsm = app.global_shortcut_manager();
-            gsm.register("CmdOrCtrl+Space", move || {
-                if let Some(window) = app_handle.get_window("main") {
-                    let is_visible = window.is_visible().unwrap_or(false);
-                    if is_visible {
-                        let _ = window.hide();
-                    } else {
-                        let _ = window.show();
-                        let _ = window.set_focus();
+            let data_dir = app
+                .path_resolver()
+                .app_data_dir()
+                .ok_or("could not resolve the app data directory")?;
+            let index_settings = app.state::<SettingsStore>().get().index.clone();
+            let indexer = Indexer::open(
+                &data_dir,
+                index_settings.roots.clone(),
+                index_settings.ignore.clone(),
+            );
+            let rescan = Duration::from_secs(index_settings.rescan_interval_secs);
+            if index_settings.watch {
+                match Watcher::start(indexer.clone(), &index_settings.roots, rescan) {
+                    Ok(watcher) => {
+                        app.manage(watcher);
+                    }
+                    Err(e) => {
+                        log::warn!("file watching unavailable ({e}); rescanning every {rescan:?}");
+                        indexer.spawn_rescan(rescan);
                     }
                 }
-            })?;
+            } else {
+                indexer.spawn_rescan(rescan);
+            }
+            app.manage(indexer);
+            tray::follow_indexer(app.handle());
+            autostart::repair();
+
+            app.manage(Apps::load());
+            let mime = MimeDb::load(&apps::data_dirs());
+            app.manage(mime.clone());
+
+            let cache_dir = app
+                .path_resolver()
+                .app_cache_dir()
+                .ok_or("could not resolve the app cache directory")?;
+            let icon_settings = app.state::<SettingsStore>().get().icons.clone();
+            app.manage(Icons::new(cache_dir, &icon_settings, mime));
+
+            let history_settings = app.state::<SettingsStore>().get().history.clone();
+            app.manage(History::open(&data_dir, history_settings));
+
+            let providers = Providers::default();
+            providers.register(Arc::new(AppsProvider::new(app.handle())));
+            providers.register(Arc::new(HistoryProvider::new(app.handle())));
+            providers.register(Arc::new(FilesProvider::new(app.handle())));
+            providers.register(Arc::new(calc::Calculator));
+            let settings = app.state::<SettingsStore>().get().clone();
+            app.manage(plugins::load(
+                &app.handle(),
+                &data_dir,
+                &settings,
+                &providers,
+            ));
+            app.manage(providers);
+
+            #[cfg(unix)]
+            if let Some(lock) = lock {
+                lock.serve(app.handle());
+            }
+            // The replies of `search` and `status` go to the terminal, as when
+            // a running launcher answers them.
+            for command in &commands {
+                cli::print(command, &cli::execute(&app.handle(), command));
+            }
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .system_tray(tray::build())
+        .on_system_tray_event(tray::on_event)
+        .on_window_event(window::on_event)
+        .register_uri_scheme_protocol(icons::SCHEME, icons::handle)
+        .invoke_handler(tauri::generate_handler![
+            actions::list_actions,
+            actions::run_action,
+            autostart::autostart_status,
+            autostart::set_autostart,
+            backend::backend_status,
+            backend::backend_url,
+            cli::take_query,
+            history::history_clear,
+            history::history_forget,
+            history::history_list,
+            hotkey::get_hotkey,
+            hotkey::set_hotkey,
+            index::index_dry_run,
+            index::index_status,
+            index::rebuild_index,
+            launch::launch,
+            providers::preview,
+            search::search,
+            search::search_stream,
+            trash::trash_list,
+            trash::trash_restore,
+        ])
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                shutdown(app);
+            }
+        });
 }
 
+/// Stop the backend and plugins, save state and give up the single-instance
+/// lock. Runs when the event loop exits, and before `AppHandle::exit`, which
+/// ends the process without an exit event.
+pub fn shutdown(app: &AppHandle) {
+    window::save_size(app);
+    if let Some(backend) = app.try_state::<Backend>() {
+        backend.shutdown();
+    }
+    if let Some(plugins) = app.try_state::<Plugins>() {
+        plugins.shutdown();
+    }
+    if let Some(indexer) = app.try_state::<Indexer>() {
+        indexer.persist();
+    }
+    #[cfg(unix)]
+    instance::release(app);
+}
 
+/// In development the backend is run straight from the repository checkout;
+/// bundled builds ship it as a resource.
+fn default_backend_root(app: &App) -> Option<PathBuf> {
+    if cfg!(debug_assertions) {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .map(PathBuf::from)
+    } else {
+        app.path_resolver().resource_dir()
+    }
+}
@@ -0,0 +1,149 @@
+//! File name → MIME type, and MIME type → icon names and parent types, using
+//! the shared-mime-info database.
+//!
+//! Only glob matching is done; files are never opened to sniff content.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DIRECTORY: &str = "inode/directory";
+const UNKNOWN: &str = "application/octet-stream";
+const TEXT: &str = "text/plain";
+
+#[derive(Debug, Default)]
+struct Tables {
+    /// Lower-case suffix after `*.` → type, for plain extension globs.
+    suffixes: HashMap<String, String>,
+    /// Lower-case exact file names, e.g. `makefile`.
+    literals: HashMap<String, String>,
+    icons: HashMap<String, String>,
+    generic_icons: HashMap<String, String>,
+    /// Type → the types it is a subclass of.
+    parents: HashMap<String, Vec<String>>,
+}
+
+/// The parts of `mime/` in the XDG data directories needed to pick icons and
+/// applications. Cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct MimeDb(Arc<Tables>);
+
+/// Lines of `type<sep>value` from one of the database's plain mapping files.
+fn pairs(path: &Path, sep: char) -> Vec<(String, String)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once(sep))
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect()
+}
+
+impl MimeDb {
+    /// Load from every `mime` directory, earlier directories taking
+    /// precedence.
+    pub fn load(data_dirs: &[PathBuf]) -> Self {
+        let mut db = Tables::default();
+        for dir in data_dirs.iter().map(|d| d.join("mime")) {
+            // `globs2` lines are `weight:type:glob[:flags]`, sorted by weight.
+            for line in fs::read_to_string(dir.join("globs2"))
+                .unwrap_or_default()
+                .lines()
+            {
+                if line.starts_with('#') {
+                    continue;
+                }
+                let mut fields = line.split(':');
+                let (Some(_weight), Some(mime), Some(glob)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    continue;
+                };
+                let is_pattern = |s: &str| s.contains(['*', '?', '[']);
+                if let Some(suffix) = glob.strip_prefix("*.").filter(|s| !is_pattern(s)) {
+                    db.suffixes
+                        .entry(suffix.to_lowercase())
+                        .or_insert_with(|| mime.to_string());
+                } else if !is_pattern(glob) {
+                    db.literals
+                        .entry(glob.to_lowercase())
+                        .or_insert_with(|| mime.to_string());
+                }
+            }
+            for (mime, icon) in pairs(&dir.join("icons"), ':') {
+                db.icons.entry(mime).or_insert(icon);
+            }
+            for (mime, icon) in pairs(&dir.join("generic-icons"), ':') {
+                db.generic_icons.entry(mime).or_insert(icon);
+            }
+            for (mime, parent) in pairs(&dir.join("subclasses"), ' ') {
+                let parents = db.parents.entry(mime).or_default();
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+            }
+        }
+        Self(Arc::new(db))
+    }
+
+    /// Guess the type of `path` from its name. The longest matching suffix
+    /// wins, so `a.tar.gz` is a compressed tarball rather than a gzip file.
+    pub fn guess(&self, path: &Path, is_dir: bool) -> &str {
+        if is_dir {
+            return DIRECTORY;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return UNKNOWN;
+        };
+        let lower = name.to_lowercase();
+        if let Some(mime) = self.0.literals.get(&lower) {
+            return mime;
+        }
+        lower
+            .match_indices('.')
+            .find_map(|(i, _)| self.0.suffixes.get(&lower[i + 1..]))
+            .map(String::as_str)
+            .unwrap_or(UNKNOWN)
+    }
+
+    /// Icon names to try for `mime`, most specific first.
+    pub fn icon_names(&self, mime: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        if mime == DIRECTORY {
+            names.push("folder".to_string());
+        }
+        names.extend(self.0.icons.get(mime).cloned());
+        names.push(mime.replace('/', "-"));
+        match self.0.generic_icons.get(mime) {
+            Some(generic) => names.push(generic.clone()),
+            None => {
+                let media = mime.split('/').next().unwrap_or_default();
+                names.push(format!("{media}-x-generic"));
+            }
+        }
+        names.push("text-x-generic".to_string());
+        let mut seen = HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+        names
+    }
+
+    /// `mime` followed by every type it is a subclass of, nearest first.
+    /// Every `text/*` type is also plain text, as the spec requires.
+    pub fn ancestors(&self, mime: &str) -> Vec<String> {
+        let mut out = vec![mime.to_string()];
+        let mut i = 0;
+        while i < out.len() {
+            let mut next: Vec<String> = self.0.parents.get(&out[i]).cloned().unwrap_or_default();
+            if out[i].starts_with("text/") && out[i] != TEXT {
+                next.push(TEXT.to_string());
+            }
+            for parent in next {
+                if !out.contains(&parent) {
+                    out.push(parent);
+                }
+            }
+            i += 1;
+        }
+        out
+    }
+}
@@ -0,0 +1,341 @@
+//! One supervised plugin process, registered as a search provider.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use super::result::{convert, Menus, PluginResult};
+use super::rpc::Connection;
+use super::Manifest;
+use crate::actions::ActionItem;
+use crate::error::{Error, Result};
+use crate::providers::{blocking, Provider, Query, QueryFuture};
+use crate::search::SearchResult;
+
+/// Version of the protocol sent with `initialize`.
+const PROTOCOL: u32 = 1;
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A run at least this long counts as healthy and resets the backoff.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+/// Calls in a row that may time out before the plugin counts as hung and is
+/// killed, so the supervisor starts it again.
+const MAX_TIMEOUTS: u32 = 3;
+
+struct Inner {
+    manifest: Manifest,
+    dir: PathBuf,
+    timeout: Duration,
+    child: Mutex<Option<Child>>,
+    /// Set once the running process has been initialized.
+    connection: Mutex<Option<Arc<Connection>>>,
+    menus: Menus,
+    shutdown: AtomicBool,
+}
+
+/// Handle to a supervised plugin process.
+#[derive(Clone)]
+pub struct Plugin(Arc<Inner>);
+
+impl Plugin {
+    /// Start the supervisor thread. It starts the plugin, initializes it, and
+    /// starts it again with exponential backoff whenever it exits.
+    pub fn spawn(manifest: Manifest, dir: PathBuf, timeout: Duration) -> io::Result<Self> {
+        let name = format!("plugin-{}", manifest.id);
+        let plugin = Self(Arc::new(Inner {
+            manifest,
+            dir,
+            timeout,
+            child: Mutex::new(None),
+            connection: Mutex::new(None),
+            menus: Menus::default(),
+            shutdown: AtomicBool::new(false),
+        }));
+        let supervisor = plugin.clone();
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || supervisor.supervise())?;
+        Ok(plugin)
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.0.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn supervise(&self) {
+        let mut backoff = MIN_BACKOFF;
+        while !self.is_shutting_down() {
+            let started = Instant::now();
+            match self.start() {
+                Ok(()) => self.wait_exit(),
+                Err(e) => log::error!(target: "plugin", "failed to start: {e}"),
+            }
+            self.0.connection.lock().unwrap().take();
+            if self.is_shutting_down() {
+                break;
+            }
+
+            if started.elapsed() >= STABLE_RUN {
+                backoff = MIN_BACKOFF;
+            }
+            log::warn!(
+                target: "plugin",
+                "plugin `{}` exited; restarting in {backoff:?}",
+                self.id()
+            );
+            self.sleep_unless_shutdown(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// The program to run: relative paths with a directory part are inside
+    /// the plugin's directory, bare names are looked up in `PATH`.
+    fn program(&self) -> PathBuf {
+        let program = &self.0.manifest.exec[0];
+        if program.contains('/') {
+            self.0.dir.join(program)
+        } else {
+            PathBuf::from(program)
+        }
+    }
+
+    fn start(&self) -> Result<()> {
+        let mut child = Command::new(self.program())
+            .args(&self.0.manifest.exec[1..])
+            .current_dir(&self.0.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.error(format!("cannot run {}: {e}", self.program().display())))?;
+        log::info!(target: "plugin", "started `{}` (pid {})", self.id(), child.id());
+
+        if let Some(stderr) = child.stderr.take() {
+            forward_stderr(self.id().to_string(), stderr);
+        }
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let connection = Arc::new(Connection::new(self.id(), stdin, stdout));
+        let mut slot = self.0.child.lock().unwrap();
+        if self.is_shutting_down() {
+            // Shutdown raced the spawn; don't leave an orphan behind.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(());
+        }
+        *slot = Some(child);
+        drop(slot);
+
+        let params = json!({ "protocol": PROTOCOL, "dir": self.0.dir });
+        if let Err(e) = connection.call("initialize", params, INITIALIZE_TIMEOUT) {
+            // Killed rather than left half started; `wait_exit` reaps it.
+            if let Some(child) = self.0.child.lock().unwrap().as_mut() {
+                let _ = child.kill();
+            }
+            log::error!(target: "plugin", "{e}");
+            return Ok(());
+        }
+        *self.0.connection.lock().unwrap() = Some(connection);
+        Ok(())
+    }
+
+    fn wait_exit(&self) {
+        loop {
+            let mut slot = self.0.child.lock().unwrap();
+            let Some(child) = slot.as_mut() else {
+                return;
+            };
+            match child.try_wait() {
+                Ok(None) => {}
+                Ok(Some(status)) => {
+                    log::info!(target: "plugin", "plugin `{}` exited with {status}", self.id());
+                    slot.take();
+                    return;
+                }
+                Err(e) => {
+                    log::error!(target: "plugin", "failed to poll plugin `{}`: {e}", self.id());
+                    return;
+                }
+            }
+            drop(slot);
+            thread::sleep(PROBE_INTERVAL);
+        }
+    }
+
+    fn sleep_unless_shutdown(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline && !self.is_shutting_down() {
+            thread::sleep(PROBE_INTERVAL);
+        }
+    }
+
+    /// Stop supervising and ask the plugin to exit by closing its stdin,
+    /// killing it if it does not exit within a short grace period.
+    pub fn shutdown(&self) {
+        if self.0.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(connection) = self.0.connection.lock().unwrap().take() {
+            connection.close();
+        }
+        let Some(mut child) = self.0.child.lock().unwrap().take() else {
+            return;
+        };
+        let deadline = Instant::now() + EXIT_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        log::warn!(target: "plugin", "plugin `{}` did not exit; killing it", self.id());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    /// Call `method` on the running plugin, within the plugin's timeout.
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let connection = self
+            .0
+            .connection
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| self.error("not running"))?;
+        let result = connection.call(method, params, self.0.timeout);
+        self.kill_if_hung(&connection);
+        result
+    }
+
+    fn search(&self, query: &Query) -> Result<Vec<SearchResult>> {
+        let Some(connection) = self.0.connection.lock().unwrap().clone() else {
+            // Starting or restarting; there is nothing to report per query.
+            log::debug!(target: "plugin", "plugin `{}` is not running", self.id());
+            return Ok(Vec::new());
+        };
+        let params = json!({ "query": query.text, "limit": query.limit });
+        let found = connection.call_until("query", params, self.0.timeout, || query.is_cancelled());
+        self.kill_if_hung(&connection);
+        let Some(found) = found? else {
+            return Ok(Vec::new());
+        };
+        let found: Vec<PluginResult> = serde_json::from_value(found)
+            .map_err(|e| self.error(format!("malformed results: {e}")))?;
+        Ok(convert(
+            self.id(),
+            &self.0.dir,
+            &query.text,
+            found,
+            &self.0.menus,
+        ))
+    }
+
+    /// Kill the process behind `connection` once too many calls in a row
+    /// have timed out; `wait_exit` reaps it and the supervisor restarts it.
+    fn kill_if_hung(&self, connection: &Arc<Connection>) {
+        if connection.timeouts() < MAX_TIMEOUTS {
+            return;
+        }
+        let mut current = self.0.connection.lock().unwrap();
+        // Another call may have got here first, and the restarted process
+        // must not be killed for its predecessor's timeouts.
+        if !current.as_ref().is_some_and(|c| Arc::ptr_eq(c, connection)) {
+            return;
+        }
+        current.take();
+        drop(current);
+        log::warn!(
+            target: "plugin",
+            "plugin `{}` timed out {MAX_TIMEOUTS} times in a row; killing it",
+            self.id()
+        );
+        if let Some(child) = self.0.child.lock().unwrap().as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Plugin(self.id().to_string(), message.into())
+    }
+}
+
+impl Provider for Plugin {
+    fn id(&self) -> &str {
+        &self.0.manifest.id
+    }
+
+    fn trigger(&self) -> Option<&str> {
+        self.0.manifest.trigger.as_deref()
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        let plugin = self.clone();
+        blocking(move || plugin.search(&query))
+    }
+
+    fn actions(&self, result: &SearchResult) -> Option<Vec<ActionItem>> {
+        self.0.menus.get(&result.path)
+    }
+
+    fn execute(&self, data: &Value) -> Result<()> {
+        self.call("execute", json!({ "data": data })).map(drop)
+    }
+}
+
+fn forward_stderr<R: Read + Send + 'static>(plugin: String, stream: R) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(|line| line.ok()) {
+            log::info!(target: "plugin", "{plugin}: {line}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::tests::STUB;
+
+    fn wait_for(what: &str, done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out waiting for {what}");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn pid(plugin: &Plugin) -> Option<u32> {
+        plugin.0.child.lock().unwrap().as_ref().map(Child::id)
+    }
+
+    #[test]
+    fn hung_plugins_are_restarted() {
+        let manifest: Manifest =
+            serde_json::from_value(json!({ "id": "stub", "exec": ["sh", "-c", STUB] })).unwrap();
+        let dir = std::env::temp_dir();
+        let plugin = Plugin::spawn(manifest, dir, Duration::from_millis(100)).unwrap();
+        let running = || plugin.0.connection.lock().unwrap().is_some();
+        wait_for("the plugin to start", running);
+        let first = pid(&plugin);
+
+        assert_eq!(plugin.call("echo", json!({})).unwrap(), "echo");
+        for _ in 1..MAX_TIMEOUTS {
+            assert!(plugin.call("hang", json!({})).is_err());
+            assert!(running());
+        }
+        assert!(plugin.call("hang", json!({})).is_err());
+        assert!(!running());
+
+        wait_for("a new process", || running() && pid(&plugin) != first);
+        assert_eq!(plugin.call("echo", json!({})).unwrap(), "echo");
+        plugin.shutdown();
+    }
+}
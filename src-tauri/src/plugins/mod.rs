@@ -0,0 +1,283 @@
+//! Out-of-process plugins. Each directory under a plugins directory that holds
+//! a `plugin.json` manifest is one plugin: a program started as a child
+//! process that speaks JSON-RPC 2.0 over stdin and stdout, one message per
+//! line. The launcher calls
+//!
+//! - `initialize` with `{"protocol": 1, "dir": ...}` once after each start;
+//! - `query` with `{"query": ..., "limit": ...}`, answered with an array of
+//!   results (see [`PluginResult`](result::PluginResult));
+//! - `execute` with `{"data": ...}` when the user picks one of the plugin's
+//!   actions.
+//!
+//! Every plugin is registered as a search provider under its id, so its
+//! results go through the same pipeline as the built-in sources. Calls time
+//! out, and a plugin that exits is started again with a growing delay.
+//! Closing its stdin asks a plugin to exit.
+//!
+//! Such a plugin runs with the user's privileges. A manifest can instead name
+//! a WebAssembly component, which runs sandboxed with only the capabilities
+//! the manifest grants; see the `wasm` module, built with the `wasm-plugins`
+//! feature.
+
+mod host;
+mod result;
+mod rpc;
+#[cfg(feature = "wasm-plugins")]
+mod wasm;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::providers::Providers;
+use crate::settings::Settings;
+
+pub use host::Plugin;
+
+const MANIFEST: &str = "plugin.json";
+
+/// How plugins are found and called. Stored under `plugins` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSettings {
+    pub enabled: bool,
+    /// Searched after the `plugins` directory in the app data directory.
+    pub dirs: Vec<PathBuf>,
+    /// Time a plugin gets to answer a call, in milliseconds, unless its
+    /// manifest says otherwise. Queries are also bound by the provider
+    /// timeout.
+    pub timeout_ms: u64,
+    /// Fuel a WebAssembly plugin gets per call, about one unit per
+    /// instruction. A call that uses it up is stopped.
+    pub fuel: u64,
+    /// Memory a WebAssembly plugin may use, in MiB.
+    pub memory_mb: u64,
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dirs: Vec::new(),
+            timeout_ms: 2000,
+            fuel: 200_000_000,
+            memory_mb: 64,
+        }
+    }
+}
+
+/// A plugin's `plugin.json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    /// Provider id; also the key of the plugin's provider settings.
+    pub id: String,
+    /// Program and arguments. A program given as a relative path with a
+    /// directory part is taken relative to the plugin's directory, which is
+    /// also its working directory.
+    #[serde(default)]
+    pub exec: Vec<String>,
+    /// WebAssembly component to run instead of a program, relative to the
+    /// plugin's directory.
+    #[serde(default)]
+    pub wasm: Option<PathBuf>,
+    #[serde(default)]
+    pub trigger: Option<String>,
+    /// Overrides the `timeout_ms` setting for this plugin.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// What a WebAssembly plugin may do. A program can do anything the user
+    /// can, so its manifest needs none.
+    #[serde(default)]
+    #[cfg_attr(not(feature = "wasm-plugins"), allow(dead_code))]
+    pub capabilities: Capabilities,
+}
+
+/// Access granted to a WebAssembly plugin beyond computing results.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Directories whose contents the plugin may read; `~` stands for the
+    /// home directory.
+    pub read: Vec<PathBuf>,
+    /// Putting text on the clipboard.
+    pub clipboard: bool,
+    /// Opening files and applications, within the launch policy.
+    pub launch: bool,
+}
+
+/// Managed state owning the running plugins.
+#[derive(Default)]
+pub struct Plugins(Vec<Plugin>);
+
+impl Plugins {
+    /// Stop every plugin.
+    pub fn shutdown(&self) {
+        for plugin in &self.0 {
+            plugin.shutdown();
+        }
+    }
+}
+
+/// Find the plugins in `dirs`, in directory order. A broken manifest is
+/// logged and skipped, as is a plugin whose id was already seen.
+pub fn discover(dirs: &[PathBuf]) -> Vec<(PathBuf, Manifest)> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut plugin_dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join(MANIFEST).is_file())
+            .collect();
+        plugin_dirs.sort();
+        for plugin_dir in plugin_dirs {
+            let manifest = match read_manifest(&plugin_dir) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    log::warn!("ignoring plugin in {}: {e}", plugin_dir.display());
+                    continue;
+                }
+            };
+            if !seen.insert(manifest.id.clone()) {
+                log::warn!(
+                    "ignoring plugin in {}: `{}` is already loaded",
+                    plugin_dir.display(),
+                    manifest.id
+                );
+                continue;
+            }
+            found.push((plugin_dir, manifest));
+        }
+    }
+    found
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    let raw = fs::read_to_string(dir.join(MANIFEST)).map_err(|e| e.to_string())?;
+    let manifest: Manifest = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+    if manifest.id.is_empty() {
+        return Err("`id` is empty".into());
+    }
+    if manifest.exec.is_empty() == manifest.wasm.is_none() {
+        return Err("exactly one of `exec` and `wasm` must be given".into());
+    }
+    Ok(manifest)
+}
+
+/// Start the plugins found in `data_dir/plugins` and the configured
+/// directories, and register each as a provider. Plugins disabled in the
+/// provider settings are not started, and none may replace a provider that
+/// is already registered.
+pub fn load(
+    app: &AppHandle,
+    data_dir: &Path,
+    settings: &Settings,
+    providers: &Providers,
+) -> Plugins {
+    if !settings.plugins.enabled {
+        return Plugins::default();
+    }
+    let mut dirs = vec![data_dir.join("plugins")];
+    dirs.extend(settings.plugins.dirs.iter().cloned());
+    let mut plugins = Vec::new();
+    for (dir, manifest) in discover(&dirs) {
+        if !settings.providers.source(&manifest.id).enabled {
+            continue;
+        }
+        if providers.get(&manifest.id).is_some() {
+            log::warn!(
+                "ignoring plugin in {}: `{}` is a built-in source",
+                dir.display(),
+                manifest.id
+            );
+            continue;
+        }
+        if let Some(wasm) = manifest.wasm.clone() {
+            load_wasm(app, manifest, dir, wasm, &settings.plugins, providers);
+            continue;
+        }
+        let timeout =
+            Duration::from_millis(manifest.timeout_ms.unwrap_or(settings.plugins.timeout_ms));
+        match Plugin::spawn(manifest, dir, timeout) {
+            Ok(plugin) => {
+                providers.register(Arc::new(plugin.clone()));
+                plugins.push(plugin);
+            }
+            Err(e) => log::warn!("could not start a plugin: {e}"),
+        }
+    }
+    Plugins(plugins)
+}
+
+#[cfg(feature = "wasm-plugins")]
+fn load_wasm(
+    app: &AppHandle,
+    manifest: Manifest,
+    dir: PathBuf,
+    wasm: PathBuf,
+    settings: &PluginSettings,
+    providers: &Providers,
+) {
+    match wasm::WasmPlugin::load(app, manifest, dir, wasm, settings) {
+        Ok(plugin) => providers.register(Arc::new(plugin)),
+        Err(e) => log::warn!("could not load a plugin: {e}"),
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+fn load_wasm(
+    _app: &AppHandle,
+    _manifest: Manifest,
+    dir: PathBuf,
+    _wasm: PathBuf,
+    _settings: &PluginSettings,
+    _providers: &Providers,
+) {
+    log::warn!(
+        "ignoring plugin in {}: built without WebAssembly plugin support",
+        dir.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin in shell for tests: answers every call with its method name,
+    /// `slow` after a delay and `hang` never.
+    pub const STUB: &str = r#"
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+    method=$(printf '%s' "$line" | sed 's/.*"method":"\([^"]*\)".*/\1/')
+    reply="{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":\"$method\"}"
+    case $method in
+        hang) ;;
+        slow) (sleep 0.3; echo "$reply") & ;;
+        *) echo "$reply" ;;
+    esac
+done
+"#;
+
+    fn manifest(json: &str) -> Result<Manifest, String> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(MANIFEST), json).unwrap();
+        read_manifest(dir.path())
+    }
+
+    #[test]
+    fn manifests_name_exactly_one_runtime() {
+        assert!(manifest(r#"{"id": "notes", "exec": ["./notes"]}"#).is_ok());
+        assert!(manifest(r#"{"id": "notes", "wasm": "notes.wasm"}"#).is_ok());
+        assert!(manifest(r#"{"id": "notes", "exec": ["./notes"], "wasm": "notes.wasm"}"#).is_err());
+        assert!(manifest(r#"{"id": "notes"}"#).is_err());
+        assert!(manifest(r#"{"id": "", "exec": ["./notes"]}"#).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Results as plugins report them, and their conversion for the search
+//! pipeline.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::actions::{Action, ActionItem};
+use crate::fuzzy::Pattern;
+use crate::icons;
+use crate::search::{ResultKind, SearchResult};
+
+/// One result as a plugin returns it from `query`.
+#[derive(Debug, Deserialize)]
+pub struct PluginResult {
+    /// Tells the result apart from the plugin's others.
+    pub id: String,
+    pub title: String,
+    /// The file the result stands for. A result without one should have an
+    /// `action`.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Theme icon name, or the path of an image inside the plugin's
+    /// directory, relative to it.
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub score: f64,
+    /// Passed to `execute` when the result is chosen, instead of opening
+    /// `path`.
+    #[serde(default)]
+    pub action: Option<Value>,
+    /// Further entries for the result's menu. They replace the actions
+    /// offered for `path`.
+    #[serde(default)]
+    pub actions: Vec<PluginAction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PluginAction {
+    pub label: String,
+    /// Passed to `execute` when the action is chosen.
+    pub data: Value,
+}
+
+/// Menu entries of a plugin's latest results, by result path.
+#[derive(Default)]
+pub struct Menus(Mutex<HashMap<String, Vec<ActionItem>>>);
+
+impl Menus {
+    pub fn get(&self, path: &str) -> Option<Vec<ActionItem>> {
+        self.0.lock().unwrap().get(path).cloned()
+    }
+}
+
+/// Turn the results `plugin` found for `query` into search results, and
+/// remember their menu entries in `menus`.
+pub fn convert(
+    plugin: &str,
+    dir: &Path,
+    query: &str,
+    found: Vec<PluginResult>,
+    menus: &Menus,
+) -> Vec<SearchResult> {
+    let pattern = Pattern::parse(query);
+    let execute = |data| Action::Execute {
+        provider: plugin.to_string(),
+        data,
+    };
+    let mut entries = HashMap::new();
+    let results = found
+        .into_iter()
+        .map(|found| {
+            let path = match &found.path {
+                Some(path) => dir.join(path).to_string_lossy().into_owned(),
+                None => format!("plugin:{plugin}/{}", found.id),
+            };
+            let mut items: Vec<ActionItem> = found
+                .action
+                .iter()
+                .map(|data| ActionItem::new(execute(data.clone()), "Open"))
+                .collect();
+            items.extend(
+                found
+                    .actions
+                    .into_iter()
+                    .map(|action| ActionItem::new(execute(action.data), action.label)),
+            );
+            // A result without a file has no generic actions to fall back on.
+            if found.path.is_none() || !items.is_empty() {
+                entries.insert(path.clone(), items);
+            }
+            let icon = found.icon.and_then(|icon| plugin_icon(dir, &icon));
+            SearchResult {
+                ranges: pattern
+                    .positions(&found.title)
+                    .map(|matched| matched.ranges)
+                    .unwrap_or_default(),
+                title: found.title,
+                path,
+                kind: ResultKind::File,
+                icon,
+                score: found.score,
+                source: String::new(),
+                action: found.action.map(execute),
+            }
+        })
+        .collect();
+    *menus.0.lock().unwrap() = entries;
+    results
+}
+
+/// URL of a plugin's icon: a theme icon name, or an image the plugin ships.
+/// A path is resolved against `dir` and must stay inside it.
+fn plugin_icon(dir: &Path, icon: &str) -> Option<String> {
+    if !icon.contains(['/', '\\']) {
+        return Some(icons::name_url(icon));
+    }
+    let dir = dir.canonicalize().ok()?;
+    let path = dir.join(icon).canonicalize().ok()?;
+    path.starts_with(&dir)
+        .then(|| icons::name_url(&path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn icons_stay_inside_the_plugin() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("plugin");
+        fs::create_dir_all(dir.join("icons")).unwrap();
+        fs::write(dir.join("icons/notes.png"), "").unwrap();
+        fs::write(root.path().join("secret.png"), "").unwrap();
+
+        assert_eq!(
+            plugin_icon(&dir, "text-x-generic"),
+            Some(icons::name_url("text-x-generic"))
+        );
+        assert!(plugin_icon(&dir, "icons/notes.png").is_some());
+        assert!(plugin_icon(&dir, "icons/../icons/notes.png").is_some());
+        assert!(plugin_icon(&dir, "../secret.png").is_none());
+        assert!(plugin_icon(&dir, "icons/../../secret.png").is_none());
+        assert!(plugin_icon(&dir, "icons/missing.png").is_none());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.path().join("secret.png"), dir.join("link.png"))
+                .unwrap();
+            assert!(plugin_icon(&dir, "./link.png").is_none());
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! JSON-RPC 2.0 client over a plugin's stdin and stdout, one message per
+//! line. A reader thread hands each response to the call waiting for its id;
+//! anything else the plugin prints is ignored.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, ChildStdout};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+#[derive(Serialize)]
+struct Request<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    /// Absent for notifications, and `null` for errors about a request the
+    /// plugin could not parse.
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    error: Option<ErrorObject>,
+}
+
+#[derive(Deserialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+/// How often a call waiting for its response checks whether it was
+/// cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(25);
+
+type Pending = Arc<Mutex<HashMap<u64, Sender<Response>>>>;
+
+/// The calling side of one plugin process.
+pub struct Connection {
+    plugin: String,
+    stdin: Mutex<Option<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    closed: Arc<AtomicBool>,
+    /// Calls in a row that ran out of time; any response resets it.
+    timeouts: AtomicU32,
+}
+
+impl Connection {
+    pub fn new(plugin: &str, stdin: ChildStdin, stdout: ChildStdout) -> Self {
+        let pending = Pending::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader = (plugin.to_string(), pending.clone(), closed.clone());
+        thread::spawn(move || {
+            let (plugin, pending, closed) = reader;
+            for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                let response = match serde_json::from_str::<Response>(&line) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::debug!(target: "plugin", "{plugin}: ignoring {line:?}: {e}");
+                        continue;
+                    }
+                };
+                let Some(id) = response.id else {
+                    continue;
+                };
+                // The caller may have given up on it already.
+                if let Some(waiter) = pending.lock().unwrap().remove(&id) {
+                    let _ = waiter.send(response);
+                }
+            }
+            // Dropping the senders wakes every call still waiting.
+            closed.store(true, Ordering::SeqCst);
+            pending.lock().unwrap().clear();
+        });
+        Self {
+            plugin: plugin.to_string(),
+            stdin: Mutex::new(Some(stdin)),
+            pending,
+            next_id: AtomicU64::new(1),
+            closed,
+            timeouts: AtomicU32::new(0),
+        }
+    }
+
+    /// Whether the plugin closed its end, which it does by exiting.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// How many calls in a row have timed out. Cancelled calls don't count.
+    pub fn timeouts(&self) -> u32 {
+        self.timeouts.load(Ordering::SeqCst)
+    }
+
+    /// Close the plugin's stdin, which asks it to exit.
+    pub fn close(&self) {
+        self.stdin.lock().unwrap().take();
+    }
+
+    /// Call `method` and wait up to `timeout` for its result.
+    pub fn call(&self, method: &str, params: Value, timeout: Duration) -> Result<Value> {
+        self.call_until(method, params, timeout, || false)
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Like `call`, but stop waiting and return `None` once `cancelled`
+    /// returns true. It is checked every [`CANCEL_POLL`]; a late response is
+    /// dropped.
+    pub fn call_until(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+        cancelled: impl Fn() -> bool,
+    ) -> Result<Option<Value>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        // Checked after registering, so a plugin exiting in between is not
+        // waited on for the whole timeout.
+        if self.is_closed() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(self.error("not running"));
+        }
+        if let Err(e) = self.send(id, method, params) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(self.error(format!("cannot send `{method}`: {e}")));
+        }
+        let deadline = Instant::now() + timeout;
+        let response = loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(left.min(CANCEL_POLL)) {
+                Ok(response) => break response,
+                Err(RecvTimeoutError::Timeout) if left.is_zero() => {
+                    self.pending.lock().unwrap().remove(&id);
+                    self.timeouts.fetch_add(1, Ordering::SeqCst);
+                    return Err(self.error(format!("`{method}` took longer than {timeout:?}")));
+                }
+                Err(RecvTimeoutError::Timeout) if cancelled() => {
+                    self.pending.lock().unwrap().remove(&id);
+                    return Ok(None);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(self.error(format!("exited during `{method}`")))
+                }
+            }
+        };
+        self.timeouts.store(0, Ordering::SeqCst);
+        match response.error {
+            Some(error) => Err(self.error(format!(
+                "`{method}` failed: {} ({})",
+                error.message, error.code
+            ))),
+            None => Ok(Some(response.result)),
+        }
+    }
+
+    fn send(&self, id: u64, method: &str, params: Value) -> std::io::Result<()> {
+        let request = Request {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        };
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        let mut stdin = self.stdin.lock().unwrap();
+        let stdin = stdin
+            .as_mut()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        stdin.write_all(&line)?;
+        stdin.flush()
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Plugin(self.plugin.clone(), message.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Child, Command, Stdio};
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::plugins::tests::STUB;
+
+    fn stub() -> (Child, Connection) {
+        let mut child = Command::new("sh")
+            .args(["-c", STUB])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        (child, Connection::new("stub", stdin, stdout))
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn responses_reach_their_callers() {
+        let (mut child, connection) = stub();
+        let connection = Arc::new(connection);
+        let slow = {
+            let connection = connection.clone();
+            thread::spawn(move || connection.call("slow", json!({}), TIMEOUT))
+        };
+        // Answered while `slow` is still waiting.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(connection.call("fast", json!({}), TIMEOUT).unwrap(), "fast");
+        assert_eq!(slow.join().unwrap().unwrap(), "slow");
+        connection.close();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn timeouts_in_a_row_are_counted() {
+        let (mut child, connection) = stub();
+        let short = Duration::from_millis(100);
+        assert!(connection.call("hang", json!({}), short).is_err());
+        assert!(connection.call("hang", json!({}), short).is_err());
+        assert_eq!(connection.timeouts(), 2);
+        assert_eq!(connection.call("fast", json!({}), short).unwrap(), "fast");
+        assert_eq!(connection.timeouts(), 0);
+        connection.close();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn cancelled_calls_return_nothing() {
+        let (mut child, connection) = stub();
+        let result = connection.call_until("hang", json!({}), TIMEOUT, || true);
+        assert!(result.unwrap().is_none());
+        assert_eq!(connection.timeouts(), 0);
+        connection.close();
+        child.wait().unwrap();
+    }
+}
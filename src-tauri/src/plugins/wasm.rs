@@ -0,0 +1,568 @@
+//! Sandboxed WebAssembly plugins. A plugin whose manifest names a `wasm`
+//! component runs in-process under wasmtime and implements the `plugin` world
+//! in `wit/plugin.wit`. It reaches the host only through that world's
+//! imports, each of which checks the capabilities granted in the manifest.
+//!
+//! Every call gets a fixed amount of fuel and each instance a memory limit,
+//! so a runaway plugin traps instead of holding up searches. A trapped
+//! instance is dropped and a fresh one made for the next call.
+//!
+//! Fuel only counts the plugin's own code. Time spent in a host import, such
+//! as `read-file` on a slow network mount, is not metered, and the instance
+//! stays locked meanwhile, so later calls to the plugin wait behind it. The
+//! imports refuse what could block indefinitely or grow without bound: files
+//! that are not regular files, and directories with too many entries.
+//!
+//! A plugin without the `launch` capability cannot have the launcher open
+//! files for it either: the `path` of its results is dropped, so choosing one
+//! only runs its `action`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use serde_json::Value;
+use tauri::{AppHandle, ClipboardManager, Manager};
+use wasmtime::component::{Component, HasSelf, Linker};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+use super::result::{convert, Menus, PluginAction, PluginResult};
+use super::{Capabilities, Manifest, PluginSettings};
+use crate::actions::ActionItem;
+use crate::apps::Apps;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::launch;
+use crate::providers::{blocking, Provider, Query, QueryFuture};
+use crate::search::SearchResult;
+use crate::settings::SettingsStore;
+
+/// Core instances, tables and memories an instance may create. A component
+/// needs a few of each for itself and its adapters.
+const MAX_INSTANCES: usize = 32;
+const MAX_TABLES: usize = 32;
+const MAX_MEMORIES: usize = 4;
+const MAX_TABLE_ELEMENTS: usize = 64 * 1024;
+/// Most names `read-dir` lists.
+const MAX_DIR_ENTRIES: usize = 10_000;
+
+mod bindings {
+    wasmtime::component::bindgen!({ path: "wit/plugin.wit", world: "plugin" });
+}
+
+use bindings::quicklauncher::plugin::{host, types};
+
+/// What the `copy-text` and `open` imports do once the capability check has
+/// passed. The launcher's app handle does it for real.
+trait Shell: Send + Sync {
+    fn copy_text(&self, text: String) -> std::result::Result<(), String>;
+    fn open(&self, path: &Path) -> std::result::Result<(), String>;
+}
+
+impl Shell for AppHandle {
+    fn copy_text(&self, text: String) -> std::result::Result<(), String> {
+        self.clipboard_manager()
+            .write_text(text)
+            .map_err(|e| e.to_string())
+    }
+
+    fn open(&self, path: &Path) -> std::result::Result<(), String> {
+        let policy = self.state::<SettingsStore>().get().launch.clone();
+        launch::open(
+            &policy,
+            &self.state::<Apps>(),
+            &self.state::<History>(),
+            path,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// What a plugin's imports run with.
+struct HostState {
+    plugin: String,
+    shell: Arc<dyn Shell>,
+    /// Canonical read roots.
+    read: Vec<PathBuf>,
+    clipboard: bool,
+    launch: bool,
+    /// Largest file `read-file` returns; nothing bigger fits in the plugin's
+    /// memory anyway.
+    max_file: u64,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    /// `path`, canonicalized, if it is inside a read root.
+    fn readable(&self, path: &str) -> std::result::Result<PathBuf, String> {
+        let path = Path::new(path)
+            .canonicalize()
+            .map_err(|e| format!("cannot resolve {path}: {e}"))?;
+        if self.read.iter().any(|root| path.starts_with(root)) {
+            Ok(path)
+        } else {
+            Err(format!(
+                "{} is outside the granted read roots",
+                path.display()
+            ))
+        }
+    }
+}
+
+impl types::Host for HostState {}
+
+impl host::Host for HostState {
+    fn log(&mut self, message: String) {
+        log::info!(target: "plugin", "{}: {message}", self.plugin);
+    }
+
+    fn read_file(&mut self, path: String) -> std::result::Result<Vec<u8>, String> {
+        let path = self.readable(&path)?;
+        let meta = fs::metadata(&path).map_err(|e| e.to_string())?;
+        // Reading a FIFO or a device could block forever.
+        if !meta.is_file() {
+            return Err(format!("{} is not a regular file", path.display()));
+        }
+        if meta.len() > self.max_file {
+            return Err(format!("{} is too large", path.display()));
+        }
+        fs::read(&path).map_err(|e| e.to_string())
+    }
+
+    fn read_dir(&mut self, path: String) -> std::result::Result<Vec<String>, String> {
+        let path = self.readable(&path)?;
+        let entries = fs::read_dir(&path).map_err(|e| e.to_string())?;
+        let names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .take(MAX_DIR_ENTRIES + 1)
+            .collect();
+        if names.len() > MAX_DIR_ENTRIES {
+            return Err(format!(
+                "{} has more than {MAX_DIR_ENTRIES} entries",
+                path.display()
+            ));
+        }
+        Ok(names)
+    }
+
+    fn copy_text(&mut self, text: String) -> std::result::Result<(), String> {
+        if !self.clipboard {
+            return Err("the `clipboard` capability was not granted".into());
+        }
+        self.shell.copy_text(text)
+    }
+
+    fn open(&mut self, path: String) -> std::result::Result<(), String> {
+        if !self.launch {
+            return Err("the `launch` capability was not granted".into());
+        }
+        self.shell.open(Path::new(&path))
+    }
+}
+
+/// An instantiated plugin.
+struct Instance {
+    store: Store<HostState>,
+    bindings: bindings::Plugin,
+}
+
+struct Inner {
+    manifest: Manifest,
+    dir: PathBuf,
+    shell: Arc<dyn Shell>,
+    fuel: u64,
+    memory: usize,
+    engine: Engine,
+    linker: Linker<HostState>,
+    /// Set once compiled, which happens in the background.
+    component: OnceLock<Component>,
+    instance: Mutex<Option<Instance>>,
+    menus: Menus,
+}
+
+/// Handle to a WebAssembly plugin.
+#[derive(Clone)]
+pub struct WasmPlugin(Arc<Inner>);
+
+impl WasmPlugin {
+    /// Set up the runtime for the plugin and compile its component in the
+    /// background. Queries find nothing until that is done.
+    pub fn load(
+        app: &AppHandle,
+        manifest: Manifest,
+        dir: PathBuf,
+        wasm: PathBuf,
+        settings: &PluginSettings,
+    ) -> Result<Self> {
+        Self::with_shell(Arc::new(app.clone()), manifest, dir, wasm, settings)
+    }
+
+    fn with_shell(
+        shell: Arc<dyn Shell>,
+        manifest: Manifest,
+        dir: PathBuf,
+        wasm: PathBuf,
+        settings: &PluginSettings,
+    ) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| runtime_error(&manifest.id, e))?;
+        let mut linker = Linker::new(&engine);
+        bindings::Plugin::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state)
+            .map_err(|e| runtime_error(&manifest.id, e))?;
+        let plugin = Self(Arc::new(Inner {
+            manifest,
+            dir,
+            shell,
+            fuel: settings.fuel,
+            memory: usize::try_from(settings.memory_mb.saturating_mul(1 << 20))
+                .unwrap_or(usize::MAX),
+            engine,
+            linker,
+            component: OnceLock::new(),
+            instance: Mutex::new(None),
+            menus: Menus::default(),
+        }));
+        let compiler = plugin.clone();
+        thread::Builder::new()
+            .name(format!("plugin-{}", plugin.id()))
+            .spawn(move || {
+                let path = compiler.0.dir.join(wasm);
+                match Component::from_file(&compiler.0.engine, &path) {
+                    Ok(component) => {
+                        log::info!(target: "plugin", "loaded `{}`", compiler.id());
+                        let _ = compiler.0.component.set(component);
+                    }
+                    Err(e) => log::error!(
+                        target: "plugin",
+                        "cannot load {}: {}",
+                        path.display(),
+                        runtime_error(compiler.id(), e)
+                    ),
+                }
+            })?;
+        Ok(plugin)
+    }
+
+    fn instantiate(&self, component: &Component) -> Result<Instance> {
+        let capabilities = &self.0.manifest.capabilities;
+        let state = HostState {
+            plugin: self.id().to_string(),
+            shell: self.0.shell.clone(),
+            read: read_roots(capabilities),
+            clipboard: capabilities.clipboard,
+            launch: capabilities.launch,
+            max_file: self.0.memory as u64,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.0.memory)
+                .instances(MAX_INSTANCES)
+                .tables(MAX_TABLES)
+                .memories(MAX_MEMORIES)
+                .table_elements(MAX_TABLE_ELEMENTS)
+                .build(),
+        };
+        let mut store = Store::new(&self.0.engine, state);
+        store.limiter(|state| &mut state.limits);
+        // Instantiating runs the component's start code, which burns fuel too.
+        store
+            .set_fuel(self.0.fuel)
+            .map_err(|e| runtime_error(self.id(), e))?;
+        let bindings = bindings::Plugin::instantiate(&mut store, component, &self.0.linker)
+            .map_err(|e| runtime_error(self.id(), e))?;
+        Ok(Instance { store, bindings })
+    }
+
+    /// Run `f` on the plugin's instance with a full tank of fuel. A trap
+    /// drops the instance.
+    fn call<T>(
+        &self,
+        f: impl FnOnce(
+            &bindings::Plugin,
+            &mut Store<HostState>,
+        ) -> wasmtime::Result<std::result::Result<T, String>>,
+    ) -> Result<T> {
+        let component = self
+            .0
+            .component
+            .get()
+            .ok_or_else(|| self.error("not loaded"))?;
+        let mut slot = self.0.instance.lock().unwrap();
+        let instance = match slot.as_mut() {
+            Some(instance) => instance,
+            None => slot.insert(self.instantiate(component)?),
+        };
+        instance
+            .store
+            .set_fuel(self.0.fuel)
+            .map_err(|e| runtime_error(self.id(), e))?;
+        match f(&instance.bindings, &mut instance.store) {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(message)) => Err(self.error(message)),
+            Err(e) => {
+                slot.take();
+                Err(runtime_error(self.id(), e))
+            }
+        }
+    }
+
+    fn search(&self, query: &Query) -> Result<Vec<SearchResult>> {
+        if self.0.component.get().is_none() {
+            log::debug!(target: "plugin", "plugin `{}` is not loaded yet", self.id());
+            return Ok(Vec::new());
+        }
+        let limit = u32::try_from(query.limit).unwrap_or(u32::MAX);
+        let found = self.call(|plugin, store| {
+            // The previous query may have held the instance until now.
+            if query.is_cancelled() {
+                return Ok(Ok(Vec::new()));
+            }
+            plugin.call_query(store, &query.text, limit)
+        })?;
+        let launch = self.0.manifest.capabilities.launch;
+        let found = found
+            .into_iter()
+            .map(|found| plugin_result(found, launch))
+            .collect();
+        Ok(convert(
+            self.id(),
+            &self.0.dir,
+            &query.text,
+            found,
+            &self.0.menus,
+        ))
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Plugin(self.id().to_string(), message.into())
+    }
+}
+
+impl Provider for WasmPlugin {
+    fn id(&self) -> &str {
+        &self.0.manifest.id
+    }
+
+    fn trigger(&self) -> Option<&str> {
+        self.0.manifest.trigger.as_deref()
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        let plugin = self.clone();
+        blocking(move || plugin.search(&query))
+    }
+
+    fn actions(&self, result: &SearchResult) -> Option<Vec<ActionItem>> {
+        self.0.menus.get(&result.path)
+    }
+
+    fn execute(&self, data: &Value) -> Result<()> {
+        let data = match data {
+            Value::String(data) => data.clone(),
+            data => data.to_string(),
+        };
+        self.call(|plugin, store| plugin.call_execute(store, &data))
+    }
+}
+
+/// A result as the rest of the launcher takes it. Without `launch` its path
+/// is dropped: opening it would be launching on the plugin's behalf.
+fn plugin_result(found: types::SearchResult, launch: bool) -> PluginResult {
+    PluginResult {
+        id: found.id,
+        title: found.title,
+        path: found.path.filter(|_| launch).map(PathBuf::from),
+        icon: found.icon,
+        score: found.score,
+        action: found.action.map(Value::String),
+        actions: found
+            .actions
+            .into_iter()
+            .map(|action| PluginAction {
+                label: action.label,
+                data: Value::String(action.data),
+            })
+            .collect(),
+    }
+}
+
+/// Describe a wasmtime error, naming the limits a runaway plugin hits.
+fn runtime_error(plugin: &str, e: wasmtime::Error) -> Error {
+    let message = match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => "ran out of fuel".to_string(),
+        Some(trap) => format!("trapped: {trap}"),
+        None => format!("{e:#}"),
+    };
+    Error::Plugin(plugin.to_string(), message)
+}
+
+/// The granted read roots that exist, canonicalized. `~` stands for the home
+/// directory.
+fn read_roots(capabilities: &Capabilities) -> Vec<PathBuf> {
+    capabilities
+        .read
+        .iter()
+        .filter_map(|root| {
+            let root = match root.strip_prefix("~") {
+                Ok(rest) => tauri::api::path::home_dir()?.join(rest),
+                Err(_) => root.clone(),
+            };
+            root.canonicalize().ok()
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "wasm-plugins"))]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    /// Records what would have reached the clipboard or been opened.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Shell for Recorder {
+        fn copy_text(&self, text: String) -> std::result::Result<(), String> {
+            self.0.lock().unwrap().push(text);
+            Ok(())
+        }
+
+        fn open(&self, path: &Path) -> std::result::Result<(), String> {
+            self.0.lock().unwrap().push(path.display().to_string());
+            Ok(())
+        }
+    }
+
+    /// A component whose `query` never returns.
+    const SPIN: &str = r#"
+        (component
+          (core module $m
+            (memory (export "memory") 1)
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+              i32.const 16)
+            (func (export "query") (param i32 i32 i32) (result i32)
+              (loop $spin (br $spin))
+              unreachable)
+            (func (export "execute") (param i32 i32) (result i32)
+              i32.const 0))
+          (core instance $i (instantiate $m))
+          (type $menu-action' (record (field "label" string) (field "data" string)))
+          (export $menu-action "menu-action" (type $menu-action'))
+          (type $search-result' (record
+            (field "id" string)
+            (field "title" string)
+            (field "path" (option string))
+            (field "icon" (option string))
+            (field "score" f64)
+            (field "action" (option string))
+            (field "actions" (list $menu-action))))
+          (export $search-result "search-result" (type $search-result'))
+          (func (export "query") (param "query" string) (param "limit" u32)
+            (result (result (list $search-result) (error string)))
+            (canon lift (core func $i "query") (memory $i "memory")
+              (realloc (func $i "realloc"))))
+          (func (export "execute") (param "data" string) (result (result (error string)))
+            (canon lift (core func $i "execute") (memory $i "memory")
+              (realloc (func $i "realloc")))))
+    "#;
+
+    fn state(shell: Arc<dyn Shell>, read: Vec<PathBuf>, granted: bool) -> HostState {
+        HostState {
+            plugin: "test".into(),
+            shell,
+            read,
+            clipboard: granted,
+            launch: granted,
+            max_file: 1 << 20,
+            limits: StoreLimits::default(),
+        }
+    }
+
+    #[test]
+    fn reads_stay_inside_the_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let root = dir.join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt")).unwrap();
+        let state = state(Arc::new(Recorder::default()), vec![root.clone()], false);
+
+        let readable = |path: PathBuf| state.readable(path.to_str().unwrap());
+        assert_eq!(
+            readable(root.join("notes.txt")).unwrap(),
+            root.join("notes.txt")
+        );
+        assert!(readable(dir.join("secret.txt")).is_err());
+        assert!(readable(root.join("../secret.txt")).is_err());
+        assert!(readable(root.join("link.txt")).is_err());
+    }
+
+    #[test]
+    fn the_shell_needs_capabilities() {
+        let recorder = Arc::new(Recorder::default());
+        let mut denied = state(recorder.clone(), Vec::new(), false);
+        assert!(host::Host::copy_text(&mut denied, "text".into()).is_err());
+        assert!(host::Host::open(&mut denied, "/usr/bin/true".into()).is_err());
+        assert!(recorder.0.lock().unwrap().is_empty());
+
+        let mut granted = state(recorder.clone(), Vec::new(), true);
+        host::Host::copy_text(&mut granted, "text".into()).unwrap();
+        host::Host::open(&mut granted, "/usr/bin/true".into()).unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), ["text", "/usr/bin/true"]);
+    }
+
+    #[test]
+    fn paths_need_the_launch_capability() {
+        let found = || types::SearchResult {
+            id: "1".into(),
+            title: "Notes".into(),
+            path: Some("/home/user/notes.txt".into()),
+            icon: None,
+            score: 1.0,
+            action: None,
+            actions: Vec::new(),
+        };
+        assert_eq!(plugin_result(found(), false).path, None);
+        assert_eq!(
+            plugin_result(found(), true).path,
+            Some(PathBuf::from("/home/user/notes.txt"))
+        );
+    }
+
+    #[test]
+    fn looping_plugins_run_out_of_fuel() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("spin.wasm"), wat::parse_str(SPIN).unwrap()).unwrap();
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"id": "spin", "wasm": "spin.wasm"}"#).unwrap();
+        let settings = PluginSettings {
+            fuel: 1_000_000,
+            ..PluginSettings::default()
+        };
+        let plugin = WasmPlugin::with_shell(
+            Arc::new(Recorder::default()),
+            manifest,
+            dir.path().to_path_buf(),
+            "spin.wasm".into(),
+            &settings,
+        )
+        .unwrap();
+
+        let started = Instant::now();
+        while plugin.0.component.get().is_none() {
+            assert!(started.elapsed() < Duration::from_secs(30), "never loaded");
+            thread::sleep(Duration::from_millis(10));
+        }
+        match plugin.search(&Query::new("notes", 5)) {
+            Err(Error::Plugin(id, message)) => {
+                assert_eq!(id, "spin");
+                assert_eq!(message, "ran out of fuel");
+            }
+            other => panic!("expected a trap, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! The providers built into the launcher: installed applications, launch
+//! history and the file index. They read the app's managed state when
+//! queried, and score on the same scale: fuzzy match plus frecency boost.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use super::{blocking, Preview, Provider, Query, QueryFuture};
+use crate::apps::Apps;
+use crate::fuzzy::Pattern;
+use crate::history::History;
+use crate::icons::Icons;
+use crate::index::Indexer;
+use crate::mime::MimeDb;
+use crate::search::{self, SearchResult};
+
+/// How much of a text file a preview shows.
+const PREVIEW_BYTES: u64 = 4096;
+
+/// Launched applications are recorded by their `.desktop` path; these keep
+/// them from resurfacing as files.
+fn app_paths(app: &AppHandle) -> HashSet<PathBuf> {
+    app.state::<Apps>()
+        .all()
+        .iter()
+        .map(|app| app.path.clone())
+        .collect()
+}
+
+pub struct AppsProvider {
+    app: AppHandle,
+}
+
+impl AppsProvider {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl Provider for AppsProvider {
+    fn id(&self) -> &str {
+        "apps"
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        let app = self.app.clone();
+        blocking(move || {
+            let pattern = Pattern::parse(&query.text);
+            if pattern.is_empty() {
+                return Ok(Vec::new());
+            }
+            let history = app.state::<History>();
+            let mut results = Vec::new();
+            for desktop in app.state::<Apps>().all().iter() {
+                if query.is_cancelled() {
+                    return Ok(Vec::new());
+                }
+                results.extend(search::rank_app(&pattern, &history, desktop));
+            }
+            Ok(results)
+        })
+    }
+}
+
+/// Previously launched files. Matching ones are always considered, so a
+/// frequently used file can outrank better textual matches the index alone
+/// would have cut off.
+pub struct HistoryProvider {
+    app: AppHandle,
+}
+
+impl HistoryProvider {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl Provider for HistoryProvider {
+    fn id(&self) -> &str {
+        "history"
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        let app = self.app.clone();
+        blocking(move || {
+            let pattern = Pattern::parse(&query.text);
+            if pattern.is_empty() {
+                return Ok(Vec::new());
+            }
+            let apps = app_paths(&app);
+            let history = app.state::<History>();
+            let icons = app.state::<Icons>();
+            let mut results = Vec::new();
+            for path in history.paths().iter().filter(|path| !apps.contains(*path)) {
+                if query.is_cancelled() {
+                    return Ok(Vec::new());
+                }
+                // Only matches are looked up on disk.
+                results.extend(
+                    search::rank(&pattern, &history, &icons, path)
+                        .filter(|result| Path::new(&result.path).exists()),
+                );
+            }
+            Ok(results)
+        })
+    }
+
+    fn preview(&self, result: &SearchResult) -> Option<Preview> {
+        text_preview(&self.app, Path::new(&result.path))
+    }
+}
+
+/// The in-process file index.
+pub struct FilesProvider {
+    app: AppHandle,
+}
+
+impl FilesProvider {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl Provider for FilesProvider {
+    fn id(&self) -> &str {
+        "files"
+    }
+
+    fn query(&self, query: Query) -> QueryFuture {
+        let app = self.app.clone();
+        blocking(move || {
+            let pattern = Pattern::parse(&query.text);
+            let indexer = app.state::<Indexer>();
+            let index = indexer.read();
+            let Some(hits) = index.search_until(&pattern, query.limit, || query.is_cancelled())
+            else {
+                return Ok(Vec::new());
+            };
+            let apps = app_paths(&app);
+            let paths: Vec<PathBuf> = hits
+                .into_iter()
+                .map(|hit| index.entry(hit.id).path())
+                .filter(|path| !apps.contains(path))
+                .collect();
+            drop(index);
+            let history = app.state::<History>();
+            let icons = app.state::<Icons>();
+            Ok(paths
+                .iter()
+                .filter_map(|path| search::rank(&pattern, &history, &icons, path))
+                .collect())
+        })
+    }
+
+    fn preview(&self, result: &SearchResult) -> Option<Preview> {
+        text_preview(&self.app, Path::new(&result.path))
+    }
+}
+
+/// The start of a text file.
+fn text_preview(app: &AppHandle, path: &Path) -> Option<Preview> {
+    let mime = app.state::<MimeDb>();
+    let guess = mime.guess(path, path.is_dir()).to_string();
+    if !mime.ancestors(&guess).iter().any(|t| t == "text/plain") {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(PREVIEW_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    // A NUL byte means the extension lied about the contents.
+    if bytes.contains(&0) {
+        return None;
+    }
+    Some(Preview {
+        mime: guess,
+        text: String::from_utf8_lossy(&bytes).into_owned(),
+    })
+}
@@ -0,0 +1,539 @@
+//! Search providers: the sources a query fans out to.
+//!
+//! Every source of results implements [`Provider`] and is registered in the
+//! managed [`Providers`] registry. A search runs all enabled providers
+//! concurrently, each under its own timeout, then normalizes their scores so
+//! the results can be merged. A query that starts with a provider's trigger
+//! keyword and a space goes to that provider alone.
+
+mod builtin;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use tokio::task::JoinSet;
+
+use crate::actions::ActionItem;
+use crate::error::{Error, Result};
+use crate::search::{SearchResult, Searches};
+
+/// How often a search waiting on its providers checks whether it was
+/// cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(25);
+
+pub use builtin::{AppsProvider, FilesProvider, HistoryProvider};
+
+/// What `Provider::query` returns.
+pub type QueryFuture = Pin<Box<dyn Future<Output = Result<Vec<SearchResult>>> + Send>>;
+
+/// A source of search results.
+pub trait Provider: Send + Sync {
+    /// Stable identifier, used as the key in settings and as the `source` of
+    /// the provider's results.
+    fn id(&self) -> &str;
+
+    /// Keyword that, followed by a space, sends the rest of the query to this
+    /// provider alone. Settings can replace it.
+    fn trigger(&self) -> Option<&str> {
+        None
+    }
+
+    /// Find results for `query`, best first. Runs concurrently with the other
+    /// providers and is abandoned once its timeout passes. Abandoning drops
+    /// the future but cannot stop work already running on the blocking pool:
+    /// that runs to the end and keeps its thread busy meanwhile. Long-running
+    /// work should check [`Query::is_cancelled`] to stop early at least once
+    /// a newer search has replaced this one.
+    fn query(&self, query: Query) -> QueryFuture;
+
+    /// Actions for one of this provider's results. `None` offers the generic
+    /// actions for the result's path.
+    fn actions(&self, _result: &SearchResult) -> Option<Vec<ActionItem>> {
+        None
+    }
+
+    /// A preview of one of this provider's results, if it has one. Called on
+    /// the blocking pool.
+    fn preview(&self, _result: &SearchResult) -> Option<Preview> {
+        None
+    }
+
+    /// Run an [`Execute`](crate::actions::Action::Execute) action this
+    /// provider attached to a result. Called off the main thread.
+    fn execute(&self, _data: &serde_json::Value) -> Result<()> {
+        Err(Error::Action(format!(
+            "`{}` has no actions to run",
+            self.id()
+        )))
+    }
+}
+
+/// Content shown next to the result list for the selected result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preview {
+    /// MIME type of `text`.
+    pub mime: String,
+    pub text: String,
+}
+
+/// One query as a provider sees it: the text without any trigger keyword.
+#[derive(Clone)]
+pub struct Query {
+    pub text: String,
+    pub limit: usize,
+    generation: Option<(Searches, u64)>,
+}
+
+impl Query {
+    pub fn new(text: impl Into<String>, limit: usize) -> Self {
+        Self {
+            text: text.into(),
+            limit,
+            generation: None,
+        }
+    }
+
+    /// Tie the query to a streamed search, which a newer one cancels.
+    pub fn streamed(mut self, searches: Searches, generation: u64) -> Self {
+        self.generation = Some((searches, generation));
+        self
+    }
+
+    /// Whether a newer search replaced this one. Providers doing a lot of
+    /// work should check this now and then and give up early.
+    pub fn is_cancelled(&self) -> bool {
+        self.generation
+            .as_ref()
+            .is_some_and(|(searches, generation)| !searches.is_current(*generation))
+    }
+}
+
+/// Run `f` on the blocking pool, for providers whose work is synchronous.
+pub fn blocking<F>(f: F) -> QueryFuture
+where
+    F: FnOnce() -> Result<Vec<SearchResult>> + Send + 'static,
+{
+    Box::pin(async move {
+        tauri::async_runtime::spawn_blocking(f)
+            .await
+            .map_err(|e| Error::Provider(e.to_string()))?
+    })
+}
+
+/// How providers run and how their scores are merged. Stored under
+/// `providers` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSettings {
+    pub normalization: Normalization,
+    /// Time each provider gets per query, in milliseconds, before its results
+    /// are dropped.
+    pub timeout_ms: u64,
+    /// Per-provider settings, keyed by provider id.
+    pub sources: HashMap<String, SourceSettings>,
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            normalization: Normalization::default(),
+            timeout_ms: 500,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl ProviderSettings {
+    pub(crate) fn source(&self, id: &str) -> SourceSettings {
+        self.sources.get(id).cloned().unwrap_or_default()
+    }
+}
+
+/// How one provider's scores are made comparable with another's, before its
+/// weight is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    /// Keep scores as they are. The built-in providers share one scale.
+    #[default]
+    Raw,
+    /// Divide by the provider's best score, so each provider's best result
+    /// scores 1.
+    Max,
+    /// Score by position alone: 1 for a provider's first result, 1/2 for the
+    /// second, and so on.
+    Rank,
+}
+
+/// Settings of one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceSettings {
+    pub enabled: bool,
+    /// Replaces the provider's own trigger keyword; an empty string removes
+    /// it.
+    pub trigger: Option<String>,
+    /// Overrides `timeout_ms` for this provider.
+    pub timeout_ms: Option<u64>,
+    /// Multiplies the provider's normalized scores.
+    pub weight: f64,
+}
+
+impl Default for SourceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trigger: None,
+            timeout_ms: None,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Rescale `results`, best first, according to `normalization` and `weight`.
+fn normalize(results: &mut [SearchResult], normalization: Normalization, weight: f64) {
+    let best = results.first().map_or(0.0, |r| r.score);
+    for (i, result) in results.iter_mut().enumerate() {
+        let score = match normalization {
+            Normalization::Raw => result.score,
+            Normalization::Max if best > 0.0 => result.score / best,
+            Normalization::Max => 0.0,
+            Normalization::Rank => 1.0 / (i + 1) as f64,
+        };
+        result.score = score * weight;
+    }
+}
+
+/// Managed registry of providers, in registration order.
+#[derive(Clone, Default)]
+pub struct Providers(Arc<RwLock<Vec<Arc<dyn Provider>>>>);
+
+impl Providers {
+    /// Add `provider`, replacing one registered earlier with the same id.
+    pub fn register(&self, provider: Arc<dyn Provider>) {
+        let mut providers = self.0.write().unwrap();
+        match providers.iter_mut().find(|p| p.id() == provider.id()) {
+            Some(existing) => *existing = provider,
+            None => providers.push(provider),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn Provider>> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|p| p.id() == id)
+            .cloned()
+    }
+
+    /// The providers `text` goes to, and the text they get.
+    fn route(&self, settings: &ProviderSettings, text: &str) -> (Vec<Arc<dyn Provider>>, String) {
+        let enabled: Vec<Arc<dyn Provider>> = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|p| settings.source(p.id()).enabled)
+            .cloned()
+            .collect();
+        for provider in &enabled {
+            let trigger = match settings.source(provider.id()).trigger {
+                Some(trigger) => trigger,
+                None => provider.trigger().unwrap_or_default().to_string(),
+            };
+            if trigger.is_empty() {
+                continue;
+            }
+            if let Some(rest) = text
+                .strip_prefix(&trigger)
+                .and_then(|r| r.strip_prefix(' '))
+            {
+                return (vec![provider.clone()], rest.to_string());
+            }
+        }
+        (enabled, text.to_string())
+    }
+
+    /// Run `query` on the providers it goes to, concurrently. As each one
+    /// finishes, its results, normalized and tagged with the provider id, are
+    /// passed to `batch`. A provider that fails or runs out of time is
+    /// skipped. Once a newer search cancels `query`, the providers still
+    /// running are abandoned and no more batches are passed on.
+    pub async fn fan_out<F>(&self, settings: &ProviderSettings, query: Query, mut batch: F)
+    where
+        F: FnMut(&str, Vec<SearchResult>),
+    {
+        let (providers, text) = self.route(settings, &query.text);
+        let query = Query { text, ..query };
+        let mut tasks = JoinSet::new();
+        for provider in providers {
+            let id = provider.id().to_string();
+            let source = settings.source(&id);
+            let timeout = Duration::from_millis(source.timeout_ms.unwrap_or(settings.timeout_ms));
+            let future = provider.query(query.clone());
+            tasks.spawn(async move {
+                let outcome = tokio::time::timeout(timeout, future).await;
+                (id, source.weight, timeout, outcome)
+            });
+        }
+        loop {
+            if query.is_cancelled() {
+                tasks.abort_all();
+                return;
+            }
+            let joined = match tokio::time::timeout(CANCEL_POLL, tasks.join_next()).await {
+                Ok(Some(joined)) => joined,
+                Ok(None) => break,
+                Err(_) => continue,
+            };
+            let (id, weight, timeout, outcome) = match joined {
+                Ok(finished) => finished,
+                Err(e) => {
+                    log::warn!("search provider panicked: {e}");
+                    continue;
+                }
+            };
+            let mut results = match outcome {
+                Ok(Ok(results)) => results,
+                Ok(Err(e)) => {
+                    log::warn!("search provider `{id}` failed: {e}");
+                    continue;
+                }
+                Err(_) => {
+                    log::warn!("search provider `{id}` took longer than {timeout:?}");
+                    continue;
+                }
+            };
+            results.sort_by(|a, b| b.score.total_cmp(&a.score));
+            results.truncate(query.limit);
+            normalize(&mut results, settings.normalization, weight);
+            for result in &mut results {
+                result.source.clone_from(&id);
+            }
+            batch(&id, results);
+        }
+    }
+}
+
+/// Preview a result, asking the provider that produced it.
+#[tauri::command]
+pub async fn preview(
+    providers: State<'_, Providers>,
+    result: SearchResult,
+) -> Result<Option<Preview>> {
+    let Some(provider) = providers.get(&result.source) else {
+        return Ok(None);
+    };
+    let id = provider.id().to_string();
+    tauri::async_runtime::spawn_blocking(move || provider.preview(&result))
+        .await
+        .map_err(|e| Error::Provider(format!("`{id}` could not preview: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy::Ranges;
+    use crate::search::{ResultKind, Searches};
+
+    /// Answers after `delay` with results scored `scores`.
+    struct Fixed {
+        id: &'static str,
+        trigger: Option<&'static str>,
+        delay: Duration,
+        scores: Vec<f64>,
+    }
+
+    impl Fixed {
+        fn new(id: &'static str, scores: &[f64]) -> Self {
+            Self {
+                id,
+                trigger: None,
+                delay: Duration::ZERO,
+                scores: scores.to_vec(),
+            }
+        }
+    }
+
+    impl Provider for Fixed {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn trigger(&self) -> Option<&str> {
+            self.trigger
+        }
+
+        fn query(&self, _query: Query) -> QueryFuture {
+            let delay = self.delay;
+            let results = self.scores.iter().map(|&score| result(score)).collect();
+            Box::pin(async move {
+                tokio::time::sleep(delay).await;
+                Ok(results)
+            })
+        }
+    }
+
+    fn result(score: f64) -> SearchResult {
+        SearchResult {
+            title: score.to_string(),
+            path: String::new(),
+            kind: ResultKind::File,
+            icon: None,
+            ranges: Ranges::new(),
+            score,
+            source: String::new(),
+            action: None,
+        }
+    }
+
+    fn registry(providers: Vec<Fixed>) -> Providers {
+        let registry = Providers::default();
+        for provider in providers {
+            registry.register(Arc::new(provider));
+        }
+        registry
+    }
+
+    fn route(
+        providers: &Providers,
+        settings: &ProviderSettings,
+        text: &str,
+    ) -> (Vec<String>, String) {
+        let (providers, text) = providers.route(settings, text);
+        let ids = providers.iter().map(|p| p.id().to_string()).collect();
+        (ids, text)
+    }
+
+    fn source<'a>(settings: &'a mut ProviderSettings, id: &str) -> &'a mut SourceSettings {
+        settings.sources.entry(id.to_string()).or_default()
+    }
+
+    #[test]
+    fn triggers_route_to_one_provider() {
+        let providers = registry(vec![
+            Fixed {
+                trigger: Some("f"),
+                ..Fixed::new("files", &[])
+            },
+            Fixed::new("apps", &[]),
+        ]);
+        let mut settings = ProviderSettings::default();
+        assert_eq!(
+            route(&providers, &settings, "f notes"),
+            (vec!["files".into()], "notes".into())
+        );
+        assert_eq!(
+            route(&providers, &settings, "firefox"),
+            (vec!["files".into(), "apps".into()], "firefox".into())
+        );
+
+        source(&mut settings, "files").trigger = Some("file".into());
+        assert_eq!(route(&providers, &settings, "f notes").0, ["files", "apps"]);
+        assert_eq!(
+            route(&providers, &settings, "file notes"),
+            (vec!["files".into()], "notes".into())
+        );
+
+        source(&mut settings, "files").trigger = Some(String::new());
+        assert_eq!(
+            route(&providers, &settings, "file notes").0,
+            ["files", "apps"]
+        );
+        assert_eq!(route(&providers, &settings, " notes").0, ["files", "apps"]);
+    }
+
+    #[test]
+    fn disabled_providers_are_skipped() {
+        let providers = registry(vec![
+            Fixed {
+                trigger: Some("f"),
+                ..Fixed::new("files", &[])
+            },
+            Fixed::new("apps", &[]),
+        ]);
+        let mut settings = ProviderSettings::default();
+        source(&mut settings, "files").enabled = false;
+        assert_eq!(route(&providers, &settings, "firefox").0, ["apps"]);
+        assert_eq!(
+            route(&providers, &settings, "f notes"),
+            (vec!["apps".into()], "f notes".into())
+        );
+    }
+
+    #[test]
+    fn scores_are_normalized_then_weighted() {
+        let scores = |normalization, weight| {
+            let mut results = vec![result(80.0), result(40.0), result(20.0)];
+            normalize(&mut results, normalization, weight);
+            results.iter().map(|r| r.score).collect::<Vec<_>>()
+        };
+        assert_eq!(scores(Normalization::Raw, 1.0), [80.0, 40.0, 20.0]);
+        assert_eq!(scores(Normalization::Raw, 0.5), [40.0, 20.0, 10.0]);
+        assert_eq!(scores(Normalization::Max, 1.0), [1.0, 0.5, 0.25]);
+        assert_eq!(scores(Normalization::Max, 2.0), [2.0, 1.0, 0.5]);
+        assert_eq!(scores(Normalization::Rank, 1.0), [1.0, 0.5, 1.0 / 3.0]);
+        assert_eq!(scores(Normalization::Rank, 3.0), [3.0, 1.5, 1.0]);
+
+        let mut zero = vec![result(0.0)];
+        normalize(&mut zero, Normalization::Max, 1.0);
+        assert_eq!(zero[0].score, 0.0);
+    }
+
+    #[test]
+    fn slow_providers_are_dropped() {
+        let providers = registry(vec![
+            Fixed::new("fast", &[10.0, 30.0]),
+            Fixed {
+                delay: Duration::from_secs(5),
+                ..Fixed::new("slow", &[20.0])
+            },
+        ]);
+        let mut settings = ProviderSettings::default();
+        source(&mut settings, "slow").timeout_ms = Some(50);
+        let mut batches = Vec::new();
+        tauri::async_runtime::block_on(providers.fan_out(
+            &settings,
+            Query::new("notes", 10),
+            |id, results| batches.push((id.to_string(), results)),
+        ));
+        assert_eq!(batches.len(), 1);
+        let (id, results) = &batches[0];
+        assert_eq!(id, "fast");
+        assert_eq!(
+            results.iter().map(|r| r.score).collect::<Vec<_>>(),
+            [30.0, 10.0]
+        );
+        assert!(results.iter().all(|r| r.source == "fast"));
+    }
+
+    #[test]
+    fn cancelled_searches_send_nothing_more() {
+        let providers = registry(vec![
+            Fixed::new("fast", &[10.0]),
+            Fixed {
+                delay: Duration::from_millis(200),
+                ..Fixed::new("slow", &[20.0])
+            },
+        ]);
+        let searches = Searches::default();
+        let query = Query::new("notes", 10).streamed(searches.clone(), searches.begin());
+        let mut batches = Vec::new();
+        tauri::async_runtime::block_on(providers.fan_out(
+            &ProviderSettings::default(),
+            query,
+            |id, _| {
+                batches.push(id.to_string());
+                // A newer search starts.
+                searches.begin();
+            },
+        ));
+        assert_eq!(batches, ["fast"]);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{State, Window};
+
+use crate::actions::Action;
+use crate::apps::{Apps, DesktopApp};
+use crate::calc;
+use crate::error::Result;
+use crate::fuzzy::{Pattern, Ranges};
+use crate::history::History;
+use crate::icons::{self, Icons};
+use crate::index::Index;
+use crate::providers::{Providers, Query};
+use crate::settings::SettingsStore;
+
+pub const DEFAULT_LIMIT: usize = 10;
+
+/// Event carrying one source's results for a streamed search.
+pub const RESULTS_EVENT: &str = "search://results";
+
+/// Where a result comes from. Variants are in display order: a calculator
+/// answer, then every matching application, then files and the rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultKind {
+    Answer,
+    App,
+    #[default]
+    File,
+}
+
+/// One search hit, in the `{title, path}` shape the webview renders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    /// The file itself, or the `.desktop` file of an application.
+    pub path: String,
+    #[serde(default)]
+    pub kind: ResultKind,
+    /// `icon://` URL of the result's icon.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Matched `[start, end)` character ranges of `title`, for highlighting.
+    #[serde(default)]
+    pub ranges: Ranges,
+    /// Match score plus frecency boost; higher is better. Lets results from
+    /// different sources be merged.
+    #[serde(default)]
+    pub score: f64,
+    /// Id of the provider that produced the result.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source: String,
+    /// What choosing the result does, when that is not opening `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+}
+
+pub(crate) fn rank(
+    pattern: &Pattern,
+    history: &History,
+    icons: &Icons,
+    path: &Path,
+) -> Option<SearchResult> {
+    let title = path.file_name()?.to_str()?;
+    // Positions are only recovered for candidates that can be returned.
+    let matched = pattern.positions(title)?;
+    Some(SearchResult {
+        title: title.to_string(),
+        path: path.to_string_lossy().into_owned(),
+        kind: ResultKind::File,
+        icon: Some(icons.file_url(path, path.is_dir())),
+        ranges: matched.ranges,
+        score: matched.score as f64 + history.boost(path),
+        source: String::new(),
+        action: None,
+    })
+}
+
+/// Rank an application by its name, or failing that by its generic name and
+/// keywords. Those secondary matches count half and are not highlighted.
+pub(crate) fn rank_app(
+    pattern: &Pattern,
+    history: &History,
+    app: &DesktopApp,
+) -> Option<SearchResult> {
+    let (score, ranges) = match pattern.positions(&app.name) {
+        Some(matched) => (matched.score, matched.ranges),
+        None => {
+            let best = app
+                .generic_name
+                .iter()
+                .chain(&app.keywords)
+                .filter_map(|field| pattern.score(field))
+                .max()?;
+            (best / 2, Ranges::new())
+        }
+    };
+    Some(SearchResult {
+        title: app.name.clone(),
+        path: app.path.to_string_lossy().into_owned(),
+        kind: ResultKind::App,
+        icon: app.icon.as_deref().map(icons::name_url),
+        ranges,
+        score: score as f64 + history.boost(&app.path),
+        source: String::new(),
+        action: None,
+    })
+}
+
+/// Merge results from several providers: where two share a path the higher
+/// score wins, then the best `limit` are kept.
+pub(crate) fn merge(results: Vec<SearchResult>, limit: usize) -> Vec<SearchResult> {
+    let mut by_path: HashMap<String, SearchResult> = HashMap::new();
+    for result in results {
+        match by_path.get(&result.path) {
+            Some(kept) if kept.score >= result.score => {}
+            _ => {
+                by_path.insert(result.path.clone(), result);
+            }
+        }
+    }
+    best(by_path.into_values().collect(), limit)
+}
+
+/// Order results by kind, then best first with shorter titles breaking ties,
+/// and keep the first `limit`.
+fn best(mut results: Vec<SearchResult>, limit: usize) -> Vec<SearchResult> {
+    results.sort_by(|a, b| {
+        a.kind
+            .cmp(&b.kind)
+            .then_with(|| b.score.total_cmp(&a.score))
+            .then_with(|| a.title.len().cmp(&b.title.len()))
+    });
+    results.truncate(limit);
+    results
+}
+
+/// Gather what could match: index hits plus previously launched paths, minus
+/// the `.desktop` files of `apps`.
+///
+/// Previously launched paths matching the query are always considered, so a
+/// frequently used file can outrank better textual matches the index alone
+/// would have cut off.
+fn candidates(
+    index: &Index,
+    apps: &[DesktopApp],
+    recent: Vec<PathBuf>,
+    pattern: &Pattern,
+    limit: usize,
+) -> Vec<PathBuf> {
+    // Launched applications are recorded by their `.desktop` path; keep
+    // them from resurfacing as files.
+    let mut seen: HashSet<PathBuf> = apps.iter().map(|app| app.path.clone()).collect();
+    let mut candidates: Vec<PathBuf> = index
+        .search(pattern, limit)
+        .into_iter()
+        .map(|hit| index.entry(hit.id).path())
+        .filter(|path| seen.insert(path.clone()))
+        .collect();
+    for path in recent {
+        // Match the name first, so only paths that could be returned are
+        // looked up on disk.
+        let matches = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| pattern.score(name).is_some());
+        if matches && path.exists() && seen.insert(path.clone()) {
+            candidates.push(path);
+        }
+    }
+    candidates
+}
+
+/// Score applications and file candidates together and keep the best
+/// `limit`, applications first.
+fn rank_all(
+    pattern: &Pattern,
+    apps: &[DesktopApp],
+    candidates: &[PathBuf],
+    history: &History,
+    icons: &Icons,
+    limit: usize,
+) -> Vec<SearchResult> {
+    let results = apps
+        .iter()
+        .filter_map(|app| rank_app(pattern, history, app))
+        .chain(
+            candidates
+                .iter()
+                .filter_map(|path| rank(pattern, history, icons, path)),
+        )
+        .collect();
+    best(results, limit)
+}
+
+/// Search the built-in sources on the calling thread, without the provider
+/// registry. Used by the command line when no launcher is running; the
+/// running launcher answers with [`search_providers`].
+pub fn search_blocking(
+    index: &Index,
+    apps: &Apps,
+    history: &History,
+    icons: &Icons,
+    query: &str,
+    limit: usize,
+) -> Vec<SearchResult> {
+    let pattern = Pattern::parse(query);
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let apps = apps.all();
+    let candidates = candidates(index, &apps, history.paths(), &pattern, limit);
+    let mut results = rank_all(&pattern, &apps, &candidates, history, icons, limit);
+    if let Some(answer) = calc::answer(query).filter(|_| limit > 0) {
+        results.truncate(limit.saturating_sub(1));
+        results.insert(0, answer);
+    }
+    results
+}
+
+/// Search every enabled provider and merge their results. Providers run
+/// concurrently, each under its own timeout.
+#[tauri::command]
+pub async fn search(
+    providers: State<'_, Providers>,
+    settings: State<'_, SettingsStore>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchResult>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    Ok(search_providers(&providers, &settings, query, limit).await)
+}
+
+/// Run `query` on every enabled provider and merge what they return.
+pub async fn search_providers(
+    providers: &Providers,
+    settings: &SettingsStore,
+    query: String,
+    limit: usize,
+) -> Vec<SearchResult> {
+    let provider_settings = settings.get().providers.clone();
+    let mut results = Vec::new();
+    providers
+        .fan_out(&provider_settings, Query::new(query, limit), |_, batch| {
+            results.extend(batch)
+        })
+        .await;
+    merge(results, limit)
+}
+
+/// Payload of `search://results`.
+#[derive(Debug, Clone, Serialize)]
+struct ResultsEvent<'a> {
+    generation: u64,
+    /// Id of the provider the results come from.
+    source: &'a str,
+    results: &'a [SearchResult],
+}
+
+/// Managed counter of streamed searches. Starting a search makes every older
+/// one stale; a stale search stops at its next check and sends nothing more.
+#[derive(Clone, Default)]
+pub struct Searches(Arc<AtomicU64>);
+
+impl Searches {
+    pub(crate) fn begin(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.0.load(Ordering::SeqCst) == generation
+    }
+}
+
+/// Start a search whose results arrive as `search://results` events, one per
+/// provider, each tagged with the returned generation. Generations only grow,
+/// so the page can drop any event older than the newest it has seen.
+#[tauri::command]
+pub fn search_stream(
+    window: Window,
+    searches: State<'_, Searches>,
+    providers: State<'_, Providers>,
+    settings: State<'_, SettingsStore>,
+    query: String,
+    limit: Option<usize>,
+) -> u64 {
+    let generation = searches.begin();
+    let query = Query::new(query, limit.unwrap_or(DEFAULT_LIMIT))
+        .streamed(searches.inner().clone(), generation);
+    let providers = providers.inner().clone();
+    let provider_settings = settings.get().providers.clone();
+    tauri::async_runtime::spawn(async move {
+        providers
+            .fan_out(&provider_settings, query.clone(), |source, results| {
+                if query.is_cancelled() {
+                    return;
+                }
+                let event = ResultsEvent {
+                    generation,
+                    source,
+                    results: &results,
+                };
+                if let Err(e) = window.emit(RESULTS_EVENT, event) {
+                    log::warn!("could not send search results: {e}");
+                }
+            })
+            .await;
+    });
+    generation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(title: &str, kind: ResultKind, score: f64) -> SearchResult {
+        SearchResult {
+            title: title.into(),
+            path: format!("/{title}"),
+            kind,
+            icon: None,
+            ranges: Ranges::new(),
+            score,
+            source: String::new(),
+            action: None,
+        }
+    }
+
+    #[test]
+    fn best_orders_by_kind_then_score() {
+        let results = vec![
+            result("app", ResultKind::App, 40.0),
+            result("file", ResultKind::File, 60.0),
+            result("tied-file", ResultKind::File, 40.0),
+            result("answer", ResultKind::Answer, 40.0),
+        ];
+        let titles: Vec<String> = best(results, 3).into_iter().map(|r| r.title).collect();
+        assert_eq!(titles, ["answer", "app", "file"]);
+    }
+
+    #[test]
+    fn merge_pins_the_answer_first() {
+        let results = vec![
+            result("frequent", ResultKind::File, 500.0),
+            result("calculator", ResultKind::App, 300.0),
+            result("4", ResultKind::Answer, 10.0),
+        ];
+        let titles: Vec<String> = merge(results, 3).into_iter().map(|r| r.title).collect();
+        assert_eq!(titles, ["4", "calculator", "frequent"]);
+    }
+
+    #[test]
+    fn a_matching_app_outranks_a_better_file() {
+        let results = vec![
+            result("report", ResultKind::File, 90.0),
+            result("notes", ResultKind::File, 50.0),
+            result("writer", ResultKind::App, 20.0),
+        ];
+        let titles: Vec<String> = merge(results, 2).into_iter().map(|r| r.title).collect();
+        assert_eq!(titles, ["writer", "report"]);
+    }
+}
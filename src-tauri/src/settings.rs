@@ -0,0 +1,109 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::BackendSettings;
+use crate::error::Result;
+use crate::history::HistorySettings;
+use crate::hotkey::DEFAULT_HOTKEY;
+use crate::icons::IconSettings;
+use crate::index::IndexSettings;
+use crate::launch::LaunchSettings;
+use crate::plugins::PluginSettings;
+use crate::providers::ProviderSettings;
+use crate::window::WindowSettings;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// User settings persisted as JSON in the app config directory. Unknown or
+/// missing fields fall back to their defaults so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Accelerator that toggles the launcher window, e.g. `CmdOrCtrl+Space`.
+    pub hotkey: String,
+    pub backend: BackendSettings,
+    pub launch: LaunchSettings,
+    pub index: IndexSettings,
+    pub history: HistorySettings,
+    pub icons: IconSettings,
+    pub window: WindowSettings,
+    pub providers: ProviderSettings,
+    pub plugins: PluginSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            hotkey: DEFAULT_HOTKEY.to_string(),
+            backend: BackendSettings::default(),
+            launch: LaunchSettings::default(),
+            index: IndexSettings::default(),
+            history: HistorySettings::default(),
+            icons: IconSettings::default(),
+            window: WindowSettings::default(),
+            providers: ProviderSettings::default(),
+            plugins: PluginSettings::default(),
+        }
+    }
+}
+
+/// Managed state owning the settings file and its in-memory copy.
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    /// Load settings from `dir`, creating the file with defaults if it does not
+    /// exist. A malformed file is left untouched and defaults are used instead.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(SETTINGS_FILE);
+        let settings = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                log::warn!("ignoring malformed {}: {e}", path.display());
+                Settings::default()
+            }),
+            Err(_) => {
+                let settings = Settings::default();
+                if let Err(e) = write(&path, &settings) {
+                    log::warn!("could not write {}: {e}", path.display());
+                }
+                settings
+            }
+        };
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    /// The settings file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self) -> MutexGuard<'_, Settings> {
+        self.settings.lock().unwrap()
+    }
+
+    /// Apply `f` to the settings and persist the result.
+    pub fn update<F: FnOnce(&mut Settings)>(&self, f: F) -> Result<()> {
+        let mut settings = self.get();
+        f(&mut settings);
+        write(&self.path, &settings)
+    }
+}
+
+fn write(path: &Path, settings: &Settings) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write to a sibling file first so a crash never leaves a truncated file.
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(settings)?)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
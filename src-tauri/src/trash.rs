@@ -0,0 +1,637 @@
+//! Move to trash and restore, following the freedesktop Trash specification.
+//!
+//! Files on the home filesystem go to `$XDG_DATA_HOME/Trash`. Files on other
+//! mounts go to the mount's `.Trash/$uid` when an administrator has set up a
+//! shared, sticky `.Trash`, and to `.Trash-$uid` otherwise, so trashing never
+//! copies data across filesystems. Each trashed file gets a `.trashinfo`
+//! recording where it came from and when, which is what makes it restorable
+//! here and from any file manager.
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
+use tauri::State;
+
+use crate::apps::{self, KeyFile};
+use crate::error::{Error, Result};
+use crate::index::{Change, Indexer};
+use crate::launch;
+use crate::settings::SettingsStore;
+
+const INFO_GROUP: &str = "Trash Info";
+const INFO_SUFFIX: &str = ".trashinfo";
+
+/// Bytes left as-is in the `Path` key; everything else is percent-encoded.
+const PATH_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// A file currently in one of the trashes.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashEntry {
+    /// The file inside the trash's `files` directory; pass it to
+    /// `trash_restore`.
+    pub id: PathBuf,
+    /// Where the file was trashed from.
+    pub path: PathBuf,
+    /// Local time of deletion, `YYYY-MM-DDThh:mm:ss`.
+    pub deleted: String,
+    pub is_dir: bool,
+}
+
+/// One trash directory, holding `files` and `info`.
+struct TrashDir {
+    dir: PathBuf,
+    /// Top directory of the mount for per-mount trashes, whose `Path` keys
+    /// are relative to it. `None` for the home trash.
+    topdir: Option<PathBuf>,
+}
+
+impl TrashDir {
+    fn files(&self) -> PathBuf {
+        self.dir.join("files")
+    }
+
+    fn info(&self) -> PathBuf {
+        self.dir.join("info")
+    }
+
+    /// Create the trash unless something is in the way: a symlink or a
+    /// directory of another user there could be used to read or replace what
+    /// is trashed.
+    fn create(&self) -> io::Result<()> {
+        if fs::symlink_metadata(&self.dir).is_ok() && !self.is_own() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is not a directory of the current user",
+                    self.dir.display()
+                ),
+            ));
+        }
+        create_private_dir(&self.files())?;
+        create_private_dir(&self.info())
+    }
+
+    /// Whether the trash is a real directory owned by the current user.
+    fn is_own(&self) -> bool {
+        fs::symlink_metadata(&self.dir).is_ok_and(|meta| is_own_dir(&meta))
+    }
+
+    /// The `Path` value recorded for `path`.
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        self.topdir
+            .as_deref()
+            .and_then(|top| path.strip_prefix(top).ok())
+            .unwrap_or(path)
+    }
+
+    fn entry(&self, info_file: &Path) -> Option<TrashEntry> {
+        let name = info_file.file_name()?.to_str()?.strip_suffix(INFO_SUFFIX)?;
+        let text = fs::read_to_string(info_file).ok()?;
+        let info = KeyFile::parse(&text);
+        let info = info.group(INFO_GROUP)?;
+        let recorded = path_from_info(&info.string("Path")?);
+        let path = match &self.topdir {
+            Some(top) if recorded.is_relative() => top.join(recorded),
+            _ => recorded,
+        };
+        let id = self.files().join(name);
+        let meta = fs::symlink_metadata(&id).ok()?;
+        Some(TrashEntry {
+            id,
+            path,
+            deleted: info.string("DeletionDate").unwrap_or_default(),
+            is_dir: meta.is_dir(),
+        })
+    }
+}
+
+fn path_to_info(path: &Path) -> String {
+    percent_encode(path_bytes(path).as_ref(), PATH_SET).to_string()
+}
+
+fn path_from_info(value: &str) -> PathBuf {
+    path_from_bytes(percent_decode_str(value).collect())
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    path.to_string_lossy().into_owned().into_bytes().into()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+    // SAFETY: getuid cannot fail and has no preconditions.
+    unsafe { libc::getuid() }
+}
+
+#[cfg(unix)]
+fn is_own_dir(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.is_dir() && meta.uid() == uid()
+}
+
+#[cfg(not(unix))]
+fn is_own_dir(meta: &fs::Metadata) -> bool {
+    meta.is_dir()
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path).map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the trash is not supported on this platform",
+    ))
+}
+
+/// The current local time in the `DeletionDate` format.
+#[cfg(unix)]
+fn deletion_date() -> String {
+    // SAFETY: `localtime_r` only writes to the `tm` we own; a zeroed `tm` is
+    // a valid value to start from.
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(not(unix))]
+fn deletion_date() -> String {
+    // Unreachable in practice: without the XDG layout there is no trash.
+    String::new()
+}
+
+/// `$XDG_DATA_HOME/Trash`, if this platform follows the XDG layout.
+fn home_trash() -> Option<TrashDir> {
+    let data_home = apps::data_dirs().into_iter().next()?;
+    Some(TrashDir {
+        dir: data_home.join("Trash"),
+        topdir: None,
+    })
+}
+
+/// The top directory of the mount holding `path`: its highest ancestor on
+/// the same device.
+fn mount_point(path: &Path, dev: u64) -> io::Result<PathBuf> {
+    let mut top = path.parent().unwrap_or(path);
+    while let Some(parent) = top.parent() {
+        if device(parent)? != dev {
+            break;
+        }
+        top = parent;
+    }
+    Ok(top.to_path_buf())
+}
+
+/// The per-user trash directories a mount may have: the shared `.Trash/$uid`
+/// when `.Trash` is a real, sticky directory, and `.Trash-$uid`.
+#[cfg(unix)]
+fn mount_trashes(topdir: &Path) -> Vec<TrashDir> {
+    use std::os::unix::fs::PermissionsExt;
+    let uid = uid();
+    let shared = topdir.join(".Trash");
+    // A symlinked or non-sticky `.Trash` could let other users tamper with
+    // our files, so the spec requires skipping it.
+    let shared_ok = fs::symlink_metadata(&shared)
+        .is_ok_and(|m| m.is_dir() && m.permissions().mode() & 0o1000 != 0);
+    let mut dirs = Vec::new();
+    if shared_ok {
+        dirs.push(TrashDir {
+            dir: shared.join(uid.to_string()),
+            topdir: Some(topdir.to_path_buf()),
+        });
+    }
+    dirs.push(TrashDir {
+        dir: topdir.join(format!(".Trash-{uid}")),
+        topdir: Some(topdir.to_path_buf()),
+    });
+    dirs
+}
+
+#[cfg(not(unix))]
+fn mount_trashes(_topdir: &Path) -> Vec<TrashDir> {
+    Vec::new()
+}
+
+/// Mount points from `/proc/self/mounts`, with the octal escapes of the
+/// mount table resolved.
+fn mount_points() -> Vec<PathBuf> {
+    let Ok(text) = fs::read_to_string("/proc/self/mounts") else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .map(|field| {
+            let mut bytes = Vec::with_capacity(field.len());
+            let mut rest = field.as_bytes();
+            while let Some((&b, tail)) = rest.split_first() {
+                let octal = tail
+                    .get(..3)
+                    .and_then(|d| std::str::from_utf8(d).ok())
+                    .and_then(|d| u8::from_str_radix(d, 8).ok());
+                match (b, octal) {
+                    (b'\\', Some(value)) => {
+                        bytes.push(value);
+                        rest = &tail[3..];
+                    }
+                    _ => {
+                        bytes.push(b);
+                        rest = tail;
+                    }
+                }
+            }
+            path_from_bytes(bytes)
+        })
+        .collect()
+}
+
+/// Every trash directory that currently exists.
+fn trash_dirs() -> Vec<TrashDir> {
+    home_trash()
+        .into_iter()
+        .chain(mount_points().iter().flat_map(|top| mount_trashes(top)))
+        .filter(|trash| trash.is_own() && trash.info().is_dir())
+        .collect()
+}
+
+/// Pick the trash for `path`, which lives on device `dev`, creating it if
+/// needed.
+fn trash_for(path: &Path, dev: u64) -> Result<TrashDir> {
+    let home = home_trash().ok_or_else(|| Error::Trash("no trash on this platform".into()))?;
+    home.create()?;
+    if device(&home.dir)? == dev {
+        return Ok(home);
+    }
+    let topdir = mount_point(path, dev)?;
+    for trash in mount_trashes(&topdir) {
+        if trash.create().is_ok() {
+            return Ok(trash);
+        }
+    }
+    Err(Error::Trash(format!(
+        "no writable trash on the filesystem of {}",
+        path.display()
+    )))
+}
+
+/// `name`, or `name.N.ext` for the `n`th attempt, as file managers do.
+fn candidate(name: &OsStr, n: u32) -> OsString {
+    if n == 1 {
+        return name.to_os_string();
+    }
+    let path = Path::new(name);
+    let mut out = path.file_stem().unwrap_or(name).to_os_string();
+    out.push(format!(".{n}"));
+    if let Some(ext) = path.extension() {
+        out.push(".");
+        out.push(ext);
+    }
+    out
+}
+
+/// Move `path` to the trash of its filesystem. `path` itself is trashed, not
+/// what it links to.
+pub fn trash(path: &Path) -> Result<TrashEntry> {
+    let meta = fs::symlink_metadata(path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::Trash(format!("cannot trash {}", path.display())))?;
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    let trash = trash_for(&path, device(&path)?)?;
+    let deleted = deletion_date();
+    let recorded = path_to_info(trash.relative(&path));
+
+    // Claiming the info file with `create_new` reserves the name, so two
+    // launchers trashing the same name never overwrite each other.
+    let mut n = 1;
+    let (name, info_file) = loop {
+        let candidate = candidate(name, n);
+        let mut info_name = candidate.clone();
+        info_name.push(INFO_SUFFIX);
+        let info_file = trash.info().join(info_name);
+        if !trash.files().join(&candidate).exists() {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_file)
+            {
+                Ok(mut file) => {
+                    let written = write!(
+                        file,
+                        "[{INFO_GROUP}]\nPath={recorded}\nDeletionDate={deleted}\n"
+                    );
+                    if let Err(e) = written {
+                        let _ = fs::remove_file(&info_file);
+                        return Err(e.into());
+                    }
+                    break (candidate, info_file);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        n += 1;
+    };
+
+    let id = trash.files().join(&name);
+    if let Err(e) = fs::rename(&path, &id) {
+        let _ = fs::remove_file(&info_file);
+        return Err(e.into());
+    }
+    log::info!("trashed {} to {}", path.display(), id.display());
+    Ok(TrashEntry {
+        id,
+        path,
+        deleted,
+        is_dir: meta.is_dir(),
+    })
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn list() -> Vec<TrashEntry> {
+    let mut entries: Vec<TrashEntry> = trash_dirs()
+        .iter()
+        .flat_map(|trash| {
+            fs::read_dir(trash.info())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|file| trash.entry(&file.path()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    entries.sort_by(|a, b| b.deleted.cmp(&a.deleted));
+    entries
+}
+
+/// The trashed file `id`, and its info file.
+fn find(id: &Path) -> Result<(TrashEntry, PathBuf)> {
+    let (trash, name) = trash_dirs()
+        .into_iter()
+        .find_map(|trash| {
+            let name = id.strip_prefix(trash.files()).ok()?.to_path_buf();
+            (name.components().count() == 1).then_some((trash, name))
+        })
+        .ok_or_else(|| Error::Trash(format!("{} is not in the trash", id.display())))?;
+    let mut info_name = name.into_os_string();
+    info_name.push(INFO_SUFFIX);
+    let info_file = trash.info().join(info_name);
+    let entry = trash
+        .entry(&info_file)
+        .ok_or_else(|| Error::Trash(format!("no trash info for {}", id.display())))?;
+    Ok((entry, info_file))
+}
+
+/// Where the trashed file `id` came from.
+pub fn origin(id: &Path) -> Result<PathBuf> {
+    find(id).map(|(entry, _)| entry.path)
+}
+
+/// Move `from` to `to`, failing with `AlreadyExists` instead of replacing
+/// whatever is at `to`, even if it appeared a moment ago.
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        let c_from = CString::new(from.as_os_str().as_bytes())?;
+        let c_to = CString::new(to.as_os_str().as_bytes())?;
+        // SAFETY: both paths are NUL-terminated strings that outlive the call.
+        let rc = unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                c_from.as_ptr(),
+                libc::AT_FDCWD,
+                c_to.as_ptr(),
+                libc::RENAME_NOREPLACE,
+            )
+        };
+        if rc == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        // Filesystems without RENAME_NOREPLACE say EINVAL; fall back below.
+        if e.raw_os_error() != Some(libc::EINVAL) {
+            return Err(e);
+        }
+    }
+    if fs::symlink_metadata(from)?.is_dir() {
+        // Directories cannot be hard-linked; checking first is the best left.
+        if fs::symlink_metadata(to).is_ok() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        return fs::rename(from, to);
+    }
+    // Linking fails if `to` exists, so nothing there is ever replaced.
+    fs::hard_link(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Move the trashed file `id` back to where it came from. Refuses to
+/// overwrite a file that has since taken its place.
+pub fn restore(id: &Path) -> Result<PathBuf> {
+    let (entry, info_file) = find(id)?;
+    if let Some(parent) = entry.path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match rename_noreplace(&entry.id, &entry.path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(Error::Trash(format!(
+                "{} already exists",
+                entry.path.display()
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    fs::remove_file(&info_file)?;
+    log::info!("restored {}", entry.path.display());
+    Ok(entry.path)
+}
+
+#[tauri::command]
+pub fn trash_list() -> Vec<TrashEntry> {
+    list()
+}
+
+/// Restore a trashed file and put it back into the index right away. Like
+/// trashing, restoring is limited to the launch policy's allowed roots.
+#[tauri::command]
+pub fn trash_restore(
+    settings: State<'_, SettingsStore>,
+    indexer: State<'_, Indexer>,
+    id: PathBuf,
+) -> Result<PathBuf> {
+    let origin = origin(&id)?;
+    // The directory may be gone too; check what is left of it.
+    let existing = origin
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("/"));
+    launch::check_root(&settings.get().launch, existing)?;
+    let path = restore(&id)?;
+    indexer.apply(&[Change::Upsert(path.clone())]);
+    Ok(path)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn refuses_a_symlinked_trash() {
+        let top = TempDir::new().unwrap();
+        let elsewhere = top.path().join("elsewhere");
+        fs::create_dir(&elsewhere).unwrap();
+        let link = top.path().join(format!(".Trash-{}", uid()));
+        std::os::unix::fs::symlink(&elsewhere, &link).unwrap();
+        let trash = TrashDir {
+            dir: link,
+            topdir: Some(top.path().to_path_buf()),
+        };
+        assert!(!trash.is_own());
+        assert!(trash.create().is_err());
+        assert!(!elsewhere.join("files").exists());
+
+        let trash = TrashDir {
+            dir: top.path().join("real"),
+            topdir: Some(top.path().to_path_buf()),
+        };
+        trash.create().unwrap();
+        assert!(trash.is_own());
+    }
+
+    /// Held by tests that point `XDG_DATA_HOME` at a temporary directory.
+    static DATA_HOME: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// A temporary directory standing in for the home filesystem, with
+    /// `XDG_DATA_HOME` inside it.
+    fn data_home() -> (TempDir, std::sync::MutexGuard<'static, ()>) {
+        let guard = DATA_HOME.lock().unwrap_or_else(|e| e.into_inner());
+        let home = TempDir::new().unwrap();
+        std::env::set_var("XDG_DATA_HOME", home.path().join("share"));
+        (home, guard)
+    }
+
+    #[test]
+    fn info_paths_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(OsStr::from_bytes(b"/home/me/my notes/100%/\xff.txt"));
+        let recorded = path_to_info(path);
+        assert_eq!(recorded, "/home/me/my%20notes/100%25/%FF.txt");
+        assert_eq!(path_from_info(&recorded), path);
+    }
+
+    #[test]
+    fn candidates_number_the_stem() {
+        let name = |name: &str, n| candidate(OsStr::new(name), n);
+        assert_eq!(name("a.txt", 1), "a.txt");
+        assert_eq!(name("a.txt", 2), "a.2.txt");
+        assert_eq!(name("archive.tar.gz", 3), "archive.tar.3.gz");
+        assert_eq!(name("README", 2), "README.2");
+        assert_eq!(name(".bashrc", 2), ".bashrc.2");
+    }
+
+    #[test]
+    fn trashed_files_are_restored() {
+        let (home, _guard) = data_home();
+        let path = home.path().join("docs").join("a.txt");
+        fs::create_dir(path.parent().unwrap()).unwrap();
+        fs::write(&path, "first").unwrap();
+        let first = trash(&path).unwrap();
+        fs::write(&path, "second").unwrap();
+        let second = trash(&path).unwrap();
+
+        let files = home.path().join("share/Trash/files");
+        assert_eq!(first.id, files.join("a.txt"));
+        assert_eq!(second.id, files.join("a.2.txt"));
+        assert!(!path.exists());
+        let info =
+            fs::read_to_string(home.path().join("share/Trash/info/a.txt.trashinfo")).unwrap();
+        assert!(info.contains(&format!("Path={}", path_to_info(&path))));
+
+        assert_eq!(restore(&second.id).unwrap(), path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert!(!second.id.exists());
+        assert!(!home
+            .path()
+            .join("share/Trash/info/a.2.txt.trashinfo")
+            .exists());
+    }
+
+    #[test]
+    fn restoring_never_overwrites() {
+        let (home, _guard) = data_home();
+        let path = home.path().join("a.txt");
+        fs::write(&path, "trashed").unwrap();
+        let entry = trash(&path).unwrap();
+        fs::write(&path, "newer").unwrap();
+
+        assert!(matches!(restore(&entry.id), Err(Error::Trash(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newer");
+        assert_eq!(fs::read_to_string(&entry.id).unwrap(), "trashed");
+    }
+}
@@ -0,0 +1,142 @@
+//! The system tray icon, the way to reach the launcher while its window is
+//! hidden and to quit it cleanly.
+
+use tauri::{
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem,
+};
+
+use crate::autostart;
+use crate::index::{Indexer, Watcher};
+use crate::launch;
+use crate::settings::SettingsStore;
+use crate::window;
+
+const STATUS: &str = "status";
+const OPEN: &str = "open";
+const REBUILD: &str = "rebuild";
+const WATCH: &str = "watch";
+const AUTOSTART: &str = "autostart";
+const SETTINGS: &str = "settings";
+const QUIT: &str = "quit";
+
+pub fn build() -> SystemTray {
+    let mut autostart_item = CustomMenuItem::new(AUTOSTART, "Start at Login");
+    match autostart::status() {
+        Ok(status) if status.enabled => autostart_item = autostart_item.selected(),
+        Ok(_) => {}
+        Err(_) => autostart_item = autostart_item.disabled(),
+    }
+    let menu = SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new(STATUS, "Starting…").disabled())
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(OPEN, "Open Launcher"))
+        .add_item(CustomMenuItem::new(REBUILD, "Rebuild Index"))
+        .add_item(CustomMenuItem::new(WATCH, "Pause File Watching"))
+        .add_item(autostart_item)
+        .add_item(CustomMenuItem::new(SETTINGS, "Open Settings"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(QUIT, "Quit"));
+    SystemTray::new().with_menu(menu)
+}
+
+/// Handler for `Builder::on_system_tray_event`.
+pub fn on_event(app: &AppHandle, event: SystemTrayEvent) {
+    match event {
+        SystemTrayEvent::LeftClick { .. } => window::toggle(app),
+        SystemTrayEvent::MenuItemClick { id, .. } => on_menu(app, &id),
+        _ => {}
+    }
+}
+
+fn on_menu(app: &AppHandle, id: &str) {
+    match id {
+        OPEN => window::show(app),
+        REBUILD => {
+            if let Some(indexer) = app.try_state::<Indexer>() {
+                indexer.rebuild();
+            }
+        }
+        WATCH => {
+            if let Some(watcher) = app.try_state::<Watcher>() {
+                let result = if watcher.is_paused() {
+                    watcher.resume()
+                } else {
+                    watcher.pause()
+                };
+                if let Err(e) = result {
+                    log::warn!("could not toggle file watching: {e}");
+                }
+            }
+        }
+        AUTOSTART => {
+            let enable = !autostart::status().is_ok_and(|status| status.enabled);
+            let result = if enable {
+                autostart::enable()
+            } else {
+                autostart::disable()
+            };
+            match result {
+                Ok(_) => refresh(app),
+                Err(e) => log::warn!("could not change autostart: {e}"),
+            }
+        }
+        SETTINGS => {
+            let path = app.state::<SettingsStore>().path().to_path_buf();
+            if let Err(e) = launch::spawn_detached(launch::opener(&path)) {
+                log::warn!("could not open {}: {e}", path.display());
+            }
+        }
+        QUIT => {
+            crate::shutdown(app);
+            app.exit(0);
+        }
+        _ => {}
+    }
+}
+
+/// Keep the status line and the watching toggle in step with the indexer.
+/// Tray menus have no portable "about to open" event, so the menu is updated
+/// whenever the indexer reports a change instead.
+pub fn follow_indexer(app: AppHandle) {
+    if let Some(indexer) = app.try_state::<Indexer>() {
+        let handle = app.clone();
+        indexer.on_status_change(move || refresh(&handle));
+    }
+    refresh(&app);
+}
+
+/// Bring the menu up to date on the main thread. Safe to call with the
+/// indexer's or the watcher's locks held, as the menu is read later.
+pub fn refresh(app: &AppHandle) {
+    let handle = app.clone();
+    if let Err(e) = app.run_on_main_thread(move || update(&handle)) {
+        log::warn!("could not update the tray menu: {e}");
+    }
+}
+
+fn update(app: &AppHandle) {
+    let Some(indexer) = app.try_state::<Indexer>() else {
+        return;
+    };
+    let status = indexer.status();
+    let (watch, can_watch) = match app.try_state::<Watcher>() {
+        Some(watcher) if !watcher.is_available() => ("File Watching Unavailable", false),
+        Some(watcher) if watcher.is_paused() => ("Resume File Watching", true),
+        Some(_) => ("Pause File Watching", true),
+        None => ("File Watching Unavailable", false),
+    };
+    let status = if status.building {
+        "Indexing…".to_string()
+    } else {
+        format!("{} files indexed", status.entries)
+    };
+    let autostart = autostart::status().is_ok_and(|status| status.enabled);
+
+    let tray = app.tray_handle();
+    let _ = tray.get_item(STATUS).set_title(status);
+    let item = tray.get_item(WATCH);
+    let _ = item.set_title(watch);
+    let _ = item.set_enabled(can_watch);
+    let _ = tray.get_item(AUTOSTART).set_selected(autostart);
+}
@@ -0,0 +1,317 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, GlobalWindowEvent, LogicalPosition, Manager, Monitor, Window, WindowBuilder,
+    WindowEvent, WindowUrl,
+};
+
+use crate::settings::SettingsStore;
+
+pub const MAIN_WINDOW: &str = "main";
+
+/// How the launcher window behaves. Stored under `window` in settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// Hide the launcher as soon as it loses focus.
+    pub hide_on_blur: bool,
+    /// Move the launcher to the monitor under the cursor each time it is
+    /// shown, centered horizontally in the upper third of the screen.
+    pub center_on_show: bool,
+    /// Reopen at the size the user last resized the launcher to.
+    pub remember_size: bool,
+    /// The remembered size, in logical pixels.
+    pub size: Option<WindowSize>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            hide_on_blur: true,
+            center_on_show: true,
+            remember_size: true,
+            size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Managed state tracking a resize that has not been saved yet. Resizing
+/// fires an event per frame, so the size is only written out when the window
+/// is hidden or the app exits.
+#[derive(Default)]
+pub struct WindowState {
+    pending_size: Mutex<Option<WindowSize>>,
+}
+
+/// Default size of the launcher, in logical pixels.
+const DEFAULT_SIZE: WindowSize = WindowSize {
+    width: 420.0,
+    height: 560.0,
+};
+
+/// Create the launcher window: frameless, transparent, kept out of the
+/// taskbar and above other windows, and hidden until first shown. Building it
+/// here rather than in `tauri.conf.json` means it never flashes on screen at
+/// startup.
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    app.manage(WindowState::default());
+    let settings = app.state::<SettingsStore>().get().window.clone();
+    let size = settings
+        .size
+        .filter(|_| settings.remember_size)
+        .unwrap_or(DEFAULT_SIZE);
+
+    let builder = WindowBuilder::new(app, MAIN_WINDOW, WindowUrl::default())
+        .title("Quick Launcher")
+        .inner_size(size.width, size.height)
+        .resizable(true)
+        .decorations(false)
+        .skip_taskbar(true)
+        .always_on_top(true)
+        .visible(false);
+    // Transparency needs private APIs on macOS, which the App Store rejects.
+    #[cfg(not(target_os = "macos"))]
+    let builder = builder.transparent(true);
+    let window = builder.build()?;
+    handle_escape(app, &window)
+}
+
+/// Dismiss the launcher on Escape. The key press is caught on the GTK
+/// toplevel before the webview sees it, whatever has focus in the page.
+#[cfg(target_os = "linux")]
+fn handle_escape(app: &AppHandle, window: &Window) -> tauri::Result<()> {
+    use gtk::prelude::*;
+    let handle = app.clone();
+    window
+        .gtk_window()?
+        .connect_key_press_event(move |_, event| {
+            if event.keyval() == gdk::keys::constants::Escape {
+                dismiss(&handle);
+                gtk::Inhibit(true)
+            } else {
+                gtk::Inhibit(false)
+            }
+        });
+    Ok(())
+}
+
+/// Elsewhere Escape is registered as a shortcut while the launcher has focus;
+/// see `on_event`.
+#[cfg(not(target_os = "linux"))]
+fn handle_escape(_app: &AppHandle, _window: &Window) -> tauri::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn grab_escape(app: &AppHandle, grab: bool) {
+    use tauri::GlobalShortcutManager;
+    const ESCAPE: &str = "Escape";
+    let mut shortcuts = app.global_shortcut_manager();
+    let registered = shortcuts.is_registered(ESCAPE).unwrap_or(false);
+    let result = if grab && !registered {
+        let handle = app.clone();
+        shortcuts.register(ESCAPE, move || dismiss(&handle))
+    } else if !grab && registered {
+        shortcuts.unregister(ESCAPE)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = result {
+        log::debug!("could not update the Escape shortcut: {e}");
+    }
+}
+
+/// Show the launcher window if it is hidden, hide it otherwise.
+pub fn toggle(app: &AppHandle) {
+    if let Some(window) = app.get_window(MAIN_WINDOW) {
+        let is_visible = window.is_visible().unwrap_or(false);
+        if is_visible {
+            dismiss(app);
+        } else {
+            show(app);
+        }
+    }
+}
+
+/// Show and focus the launcher. Safe to call from any thread: positioning
+/// queries the windowing system, so the work is done on the main thread.
+pub fn show(app: &AppHandle) {
+    let handle = app.clone();
+    let shown = app.run_on_main_thread(move || {
+        let Some(window) = handle.get_window(MAIN_WINDOW) else {
+            return;
+        };
+        remember_focus();
+        if handle.state::<SettingsStore>().get().window.center_on_show {
+            if let Err(e) = place(&window) {
+                log::debug!("could not position the launcher: {e}");
+            }
+        }
+        let _ = window.show();
+        let _ = window.set_focus();
+    });
+    if let Err(e) = shown {
+        log::warn!("could not show the launcher: {e}");
+    }
+}
+
+pub fn hide(app: &AppHandle) {
+    if let Some(window) = app.get_window(MAIN_WINDOW) {
+        let _ = window.hide();
+    }
+    save_size(app);
+}
+
+/// Hide the launcher and hand focus back to the application that had it
+/// before the launcher was shown.
+pub fn dismiss(app: &AppHandle) {
+    hide(app);
+    restore_focus(app);
+}
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    /// The window that was active when the launcher was shown. GDK objects
+    /// are bound to the main thread, so this is only touched there.
+    static PREVIOUS_FOCUS: std::cell::RefCell<Option<gdk::Window>> = Default::default();
+}
+
+// `active_window` is deprecated without a replacement in GTK 3; it still
+// reads `_NET_ACTIVE_WINDOW` on X11.
+#[cfg(target_os = "linux")]
+#[allow(deprecated)]
+fn remember_focus() {
+    let active = gdk::Screen::default().and_then(|screen| screen.active_window());
+    PREVIOUS_FOCUS.with(|previous| *previous.borrow_mut() = active);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remember_focus() {}
+
+/// Refocus the window remembered by `remember_focus`. Wayland does not let
+/// clients see other windows, so there the compositor decides.
+#[cfg(target_os = "linux")]
+fn restore_focus(app: &AppHandle) {
+    let _ = app.run_on_main_thread(|| {
+        if let Some(window) = PREVIOUS_FOCUS.with(|previous| previous.borrow_mut().take()) {
+            window.focus(gdk::ffi::GDK_CURRENT_TIME as u32);
+        }
+    });
+}
+
+/// Hiding the app rather than just its window makes macOS reactivate the
+/// previous application.
+#[cfg(target_os = "macos")]
+fn restore_focus(app: &AppHandle) {
+    let _ = app.hide();
+}
+
+/// Windows activates the next window in z-order, the previous application,
+/// when ours is hidden.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn restore_focus(_app: &AppHandle) {}
+
+/// Write out a size changed since the last save.
+pub fn save_size(app: &AppHandle) {
+    let Some(state) = app.try_state::<WindowState>() else {
+        return;
+    };
+    let Some(size) = state.pending_size.lock().unwrap().take() else {
+        return;
+    };
+    let store = app.state::<SettingsStore>();
+    if store.get().window.size == Some(size) {
+        return;
+    }
+    if let Err(e) = store.update(|s| s.window.size = Some(size)) {
+        log::warn!("could not save the window size: {e}");
+    }
+}
+
+/// Handler for `Builder::on_window_event`.
+pub fn on_event(event: GlobalWindowEvent) {
+    let window = event.window();
+    if window.label() != MAIN_WINDOW {
+        return;
+    }
+    let app = window.app_handle();
+    let settings = app.state::<SettingsStore>().get().window.clone();
+    #[cfg(not(target_os = "linux"))]
+    if let WindowEvent::Focused(focused) = event.event() {
+        grab_escape(&app, *focused);
+    }
+    match event.event() {
+        WindowEvent::Focused(false) if settings.hide_on_blur => hide(&app),
+        WindowEvent::Resized(size) if settings.remember_size => {
+            // Hiding or minimizing reports a zero size on some platforms.
+            if size.width == 0 || size.height == 0 || !window.is_visible().unwrap_or(false) {
+                return;
+            }
+            let scale = window.scale_factor().unwrap_or(1.0);
+            let logical = size.to_logical::<f64>(scale);
+            *app.state::<WindowState>().pending_size.lock().unwrap() = Some(WindowSize {
+                width: logical.width,
+                height: logical.height,
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Center `window` horizontally on the monitor under the cursor, with a third
+/// of the free vertical space above it and two thirds below.
+fn place(window: &Window) -> tauri::Result<()> {
+    let monitors = window.available_monitors()?;
+    let monitor = cursor_position()
+        .and_then(|(x, y)| {
+            monitors.iter().find(|m| {
+                let (left, top, width, height) = logical_bounds(m);
+                (left..left + width).contains(&x) && (top..top + height).contains(&y)
+            })
+        })
+        .cloned()
+        .or(window.current_monitor()?)
+        .or(window.primary_monitor()?);
+    let Some(monitor) = monitor else {
+        return Ok(());
+    };
+
+    let size = window
+        .outer_size()?
+        .to_logical::<f64>(window.scale_factor()?);
+    let (left, top, width, height) = logical_bounds(&monitor);
+    let x = left + ((width - size.width) / 2.0).max(0.0);
+    let y = top + ((height - size.height) / 3.0).max(0.0);
+    window.set_position(LogicalPosition::new(x, y))
+}
+
+/// A monitor's position and size in logical pixels.
+fn logical_bounds(monitor: &Monitor) -> (f64, f64, f64, f64) {
+    let scale = monitor.scale_factor();
+    let position = monitor.position().to_logical::<f64>(scale);
+    let size = monitor.size().to_logical::<f64>(scale);
+    (position.x, position.y, size.width, size.height)
+}
+
+/// The pointer position in logical desktop coordinates. Wayland does not
+/// expose a global pointer position, so there the result only reflects the
+/// last position GTK saw.
+#[cfg(target_os = "linux")]
+fn cursor_position() -> Option<(f64, f64)> {
+    let pointer = gdk::Display::default()?.default_seat()?.pointer()?;
+    let (_, x, y) = pointer.position();
+    Some((x as f64, y as f64))
+}
+
+/// Other platforms fall back to the monitor the window is already on.
+#[cfg(not(target_os = "linux"))]
+fn cursor_position() -> Option<(f64, f64)> {
+    None
+}